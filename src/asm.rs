@@ -0,0 +1,519 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Label, Register, Terminator};
+use crate::linux;
+use crate::x86::Alu::*;
+use crate::x86::Inst::{self, *};
+use crate::x86::Reg::*;
+use crate::x86::Size::*;
+use crate::x86::Unary::*;
+use crate::x86::{imm, indexed, mem, rip, Cond, Operand, Program, Reg, Size, Symbol};
+use crate::{Options, Overflow, StorageKind, Width, ZeroDivision};
+
+// Registers the runtime preserves.
+const REGISTERS: [Reg; 9] = [Rbx, Rbp, R12, R13, R14, R15, R8, R9, R10];
+
+// Where a virtual register lives inside its block.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Register(usize),
+    /// 8-byte slot above `%rsp`.
+    Slot(usize),
+    /// Constant fitting in a sign-extended 32-bit immediate.
+    Immediate(i64),
+    /// `%rax`, used as scratch.
+    Scratch,
+}
+
+struct Emitter<'a> {
+    program: Program,
+    options: &'a Options,
+    /// Messages of the traps, jumped to as [`Symbol::Trap`].
+    traps: Vec<String>,
+    /// Number of stack slots the largest block needs.
+    slots: usize,
+}
+
+// Lowers `blocks` to x86-64 code, starting at `_start` and followed by the runtime.
+pub(crate) fn program(blocks: &[Block], options: &Options) -> Program {
+    let mut emitter = Emitter {
+        program: Program::default(),
+        options,
+        traps: vec![],
+        slots: 0,
+    };
+    for (label, block) in blocks.iter().enumerate() {
+        emitter.block(label, block);
+    }
+    let mut program = emitter.program;
+    let mut start = vec![Label(Symbol::Named("_start"))];
+    if emitter.slots > 0 {
+        start.push(Alu(Sub, Q, imm(emitter.slots as i64 * 8), Rsp.into()));
+    }
+    program.text.splice(0..0, start);
+    for (index, message) in emitter.traps.into_iter().enumerate() {
+        program.extend([
+            Label(Symbol::Trap(index)),
+            Lea(Operand::Rip(Symbol::Message(index), 0), Rsi),
+            Mov(L, imm(message.len() as i64 + 1), Rdx.into()),
+            Jmp(Symbol::Named("trap")),
+        ]);
+        program
+            .rodata
+            .push((Symbol::Message(index), format!("{message}\n").into_bytes()));
+    }
+    linux::runtime(&mut program);
+    program
+}
+
+// Prints `program` in GNU assembly.
+pub(crate) fn emit(program: &Program) -> String {
+    let mut output = "\t.bss\n\t.p2align 4\n".to_string();
+    for (symbol, size) in &program.bss {
+        writeln!(output, "{symbol}:\n\t.zero {size}").ok();
+    }
+    output.push_str("\n\t.text\n\t.globl _start\n");
+    for inst in &program.text {
+        writeln!(output, "{inst}").ok();
+    }
+    output.push_str("\n\t.section .rodata\n");
+    for (symbol, bytes) in &program.rodata {
+        write!(output, "{symbol}:\n\t.ascii \"").ok();
+        for &byte in bytes {
+            match byte {
+                b'\n' => output.push_str("\\n"),
+                b'"' | b'\\' => {
+                    output.push('\\');
+                    output.push(byte as char);
+                }
+                _ => output.push(byte as char),
+            }
+        }
+        output.push_str("\"\n");
+    }
+    output
+}
+
+impl Emitter<'_> {
+    fn block(&mut self, label: Label, block: &Block) {
+        self.program.push(Label(Symbol::Block(label)));
+        // Registers are freed after their last use, to be reused by later definitions.
+        let count = block
+            .instructions
+            .iter()
+            .filter_map(Instruction::defined)
+            .max()
+            .map_or(0, |register| register + 1);
+        let mut last_use = vec![None; count];
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            for register in uses(instruction) {
+                last_use[register] = Some(index);
+            }
+        }
+        let mut locations = vec![Location::Scratch; count];
+        let mut registers: Vec<usize> = (0..REGISTERS.len()).rev().collect();
+        let mut slots: Vec<usize> = vec![];
+        let mut next_slot = 0;
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            for register in uses(instruction) {
+                if last_use[register] == Some(index) {
+                    match locations[register] {
+                        Location::Register(r) => registers.push(r),
+                        Location::Slot(s) => slots.push(s),
+                        Location::Immediate(_) | Location::Scratch => {}
+                    }
+                }
+            }
+            let Some(dst) = instruction.defined() else {
+                self.instruction(instruction, &locations);
+                continue;
+            };
+            locations[dst] = match *instruction {
+                Instruction::Const { value, .. } if i32::try_from(value).is_ok() => {
+                    Location::Immediate(value as i64)
+                }
+                _ if last_use[dst].is_none() => Location::Scratch,
+                _ => {
+                    if let Some(r) = registers.pop() {
+                        Location::Register(r)
+                    } else if let Some(s) = slots.pop() {
+                        Location::Slot(s)
+                    } else {
+                        next_slot += 1;
+                        Location::Slot(next_slot - 1)
+                    }
+                }
+            };
+            self.instruction(instruction, &locations);
+        }
+        self.slots = self.slots.max(next_slot);
+        self.terminator(label, &block.terminator);
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, locations: &[Location]) {
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                self.pop(storage);
+                self.store(locations[dst]);
+            }
+            Instruction::Push { storage, src } => self.push(storage, locations[src]),
+            Instruction::PushFront { storage, src } => {
+                self.program.extend([
+                    Lea(field(storage, 0), Rdi),
+                    Mov(Q, operand(locations[src]), Rsi.into()),
+                    call("push_front"),
+                ]);
+            }
+            Instruction::Const { dst, value } => {
+                if let Location::Immediate(_) = locations[dst] {
+                    return;
+                }
+                self.program.push(Movabs(value as i64, Rax));
+                self.store(locations[dst]);
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                let (lhs, rhs, dst) = (locations[lhs], locations[rhs], locations[dst]);
+                if matches!(op, BinaryOp::Divide | BinaryOp::Remainder) {
+                    self.divide(op, lhs, rhs, dst, cell);
+                } else {
+                    self.arithmetic(op, lhs, rhs, dst, cell);
+                }
+            }
+            Instruction::PrintDecimal(src) => {
+                self.argument(locations[src]);
+                self.program.push(call("print_decimal"));
+            }
+            Instruction::PrintUnicode(src) => {
+                self.argument(locations[src]);
+                self.program.push(call("print_utf8"));
+            }
+            Instruction::ScanDecimal(dst) => {
+                self.program.push(call("scan_decimal"));
+                self.store(locations[dst]);
+            }
+            Instruction::ScanUnicode(dst) => {
+                self.program.push(call("scan_utf8"));
+                self.store(locations[dst]);
+            }
+        }
+    }
+
+    fn arithmetic(
+        &mut self,
+        op: BinaryOp,
+        lhs: Location,
+        rhs: Location,
+        dst: Location,
+        cell: (usize, usize),
+    ) {
+        let w = self.size();
+        if op == BinaryOp::Compare {
+            let lhs = match lhs {
+                Location::Register(_) => operand(lhs),
+                _ => {
+                    self.program.push(Mov(w, operand(lhs), Rcx.into()));
+                    Rcx.into()
+                }
+            };
+            self.program.extend([
+                Alu(Xor, L, Rax.into(), Rax.into()),
+                Alu(Cmp, w, operand(rhs), lhs),
+                Setge(Rax),
+            ]);
+            self.store(dst);
+            return;
+        }
+        // Addition and multiplication commute, so `dst` can take the place of `lhs`.
+        let (lhs, rhs) = if dst == rhs && op != BinaryOp::Subtract {
+            (rhs, lhs)
+        } else {
+            (lhs, rhs)
+        };
+        // The result is computed in place if `dst` is a register that only `lhs` may occupy.
+        let acc = match dst {
+            Location::Register(r) if dst != rhs || dst == lhs => REGISTERS[r],
+            _ => Rax,
+        };
+        if operand(lhs) != acc.into() {
+            self.program.push(Mov(w, operand(lhs), acc.into()));
+        }
+        self.program.push(match (op, rhs) {
+            (BinaryOp::Add, _) => Alu(Add, w, operand(rhs), acc.into()),
+            (BinaryOp::Subtract, _) => Alu(Sub, w, operand(rhs), acc.into()),
+            (_, Location::Immediate(value)) => ImulImm(w, value, acc.into(), acc),
+            _ => Imul(w, operand(rhs), acc),
+        });
+        if self.options.overflow == Overflow::Trap {
+            let trap = self.trap(format!(
+                "overflow in {op} at line {}, column {}",
+                cell.0 + 1,
+                cell.1 + 1
+            ));
+            self.program.push(J(Cond::O, trap));
+        }
+        if acc == Rax {
+            self.store(dst);
+        }
+    }
+
+    fn divide(
+        &mut self,
+        op: BinaryOp,
+        lhs: Location,
+        rhs: Location,
+        dst: Location,
+        (r, c): (usize, usize),
+    ) {
+        let w = self.size();
+        let [negative, zero, done] = self.program.locals();
+        self.program.extend([
+            Mov(w, operand(lhs), Rax.into()),
+            Mov(w, operand(rhs), Rcx.into()),
+        ]);
+        let zero = match self.options.zero_division {
+            ZeroDivision::Trap => {
+                let trap = self.trap(format!(
+                    "division by zero at line {}, column {}",
+                    r + 1,
+                    c + 1
+                ));
+                self.program
+                    .extend([Test(w, Rcx.into(), Rcx.into()), J(Cond::E, trap)]);
+                None
+            }
+            ZeroDivision::Zero => {
+                self.program
+                    .extend([Test(w, Rcx.into(), Rcx.into()), J(Cond::E, zero)]);
+                Some(zero)
+            }
+            // Reflection happens before the division, in a separate block.
+            ZeroDivision::Undefined | ZeroDivision::Reflect => None,
+        };
+        // The remainder of `MIN / -1` is `0`, even though the quotient overflows.
+        let negative = (op == BinaryOp::Remainder || self.options.overflow != Overflow::Undefined)
+            .then_some(negative);
+        if let Some(negative) = negative {
+            self.program
+                .extend([Alu(Cmp, w, imm(-1), Rcx.into()), J(Cond::E, negative)]);
+        }
+        self.program.extend([
+            match self.options.width {
+                Width::W32 => Cltd,
+                _ => Cqto,
+            },
+            Unary(Idiv, w, Rcx.into()),
+        ]);
+        if op == BinaryOp::Remainder {
+            self.program.push(Mov(Q, Rdx.into(), Rax.into()));
+        }
+        if let Some(negative) = negative {
+            self.program.extend([Jmp(done), Label(negative)]);
+            if op == BinaryOp::Remainder {
+                self.program.push(Alu(Xor, L, Rax.into(), Rax.into()));
+            } else {
+                self.program.push(Unary(Neg, w, Rax.into()));
+                if self.options.overflow == Overflow::Trap {
+                    let trap = self.trap(format!(
+                        "overflow in {op} at line {}, column {}",
+                        r + 1,
+                        c + 1
+                    ));
+                    self.program.push(J(Cond::O, trap));
+                }
+            }
+        }
+        if let Some(zero) = zero {
+            self.program
+                .extend([Jmp(done), Label(zero), Alu(Xor, L, Rax.into(), Rax.into())]);
+        }
+        if zero.is_some() || negative.is_some() {
+            self.program.push(Label(done));
+        }
+        self.store(dst);
+    }
+
+    fn terminator(&mut self, label: Label, terminator: &Terminator) {
+        let w = self.size();
+        match *terminator {
+            Terminator::Goto(target) => {
+                if target != label + 1 {
+                    self.program.push(Jmp(Symbol::Block(target)));
+                }
+            }
+            Terminator::Jsl {
+                storage,
+                size,
+                less,
+                otherwise,
+            } => {
+                self.program
+                    .push(Alu(Cmp, Q, imm(size as i64), field(storage, 16)));
+                self.branch(label, (Cond::B, Cond::Ae), less, otherwise);
+            }
+            Terminator::Jnz {
+                storage,
+                nonzero,
+                zero,
+            } => {
+                self.pop(storage);
+                self.program.push(Test(w, Rax.into(), Rax.into()));
+                self.branch(label, (Cond::Ne, Cond::E), nonzero, zero);
+            }
+            Terminator::Jz {
+                storage,
+                zero,
+                otherwise,
+            } => {
+                if StorageKind::from(storage) == StorageKind::Queue {
+                    self.program
+                        .extend([Lea(field(storage, 0), Rdi), call("peek_front")]);
+                } else {
+                    self.program.extend([
+                        Mov(Q, field(storage, 16), Rax.into()),
+                        Mov(Q, field(storage, 0), Rcx.into()),
+                        Mov(Q, indexed(Some(Rcx), Rax, 8, -8), Rax.into()),
+                    ]);
+                }
+                self.program.push(Test(w, Rax.into(), Rax.into()));
+                self.branch(label, (Cond::E, Cond::Ne), zero, otherwise);
+            }
+            Terminator::Halt { storage } => {
+                let name = if StorageKind::from(storage) == StorageKind::Queue {
+                    "halt_front"
+                } else {
+                    "halt_back"
+                };
+                self.program
+                    .extend([Lea(field(storage, 0), Rdi), call(name)]);
+            }
+        }
+    }
+
+    // Jumps to `taken` on `jump.0` and to `otherwise` on `jump.1`, falling through if possible.
+    fn branch(&mut self, label: Label, jump: (Cond, Cond), taken: Label, otherwise: Label) {
+        if taken == label + 1 {
+            self.program.push(J(jump.1, Symbol::Block(otherwise)));
+        } else {
+            self.program.push(J(jump.0, Symbol::Block(taken)));
+            if otherwise != label + 1 {
+                self.program.push(Jmp(Symbol::Block(otherwise)));
+            }
+        }
+    }
+
+    // Pops a value from `storage` to `%rax`.
+    fn pop(&mut self, storage: usize) {
+        if StorageKind::from(storage) == StorageKind::Queue {
+            self.program
+                .extend([Lea(field(storage, 0), Rdi), call("pop_front")]);
+            return;
+        }
+        self.program.extend([
+            Mov(Q, field(storage, 16), Rcx.into()),
+            Unary(Dec, Q, Rcx.into()),
+            Mov(Q, Rcx.into(), field(storage, 16)),
+            Mov(Q, field(storage, 0), Rax.into()),
+            Mov(Q, indexed(Some(Rax), Rcx, 8, 0), Rax.into()),
+        ]);
+    }
+
+    fn push(&mut self, storage: usize, src: Location) {
+        if StorageKind::from(storage) == StorageKind::Queue {
+            self.program.extend([
+                Lea(field(storage, 0), Rdi),
+                Mov(Q, operand(src), Rsi.into()),
+                call("push_back"),
+            ]);
+            return;
+        }
+        let [room] = self.program.locals();
+        self.program.extend([
+            Mov(Q, field(storage, 16), Rax.into()),
+            Alu(Cmp, Q, field(storage, 24), Rax.into()),
+            J(Cond::B, room),
+            Lea(field(storage, 0), Rdi),
+            call("grow"),
+            Mov(Q, field(storage, 16), Rax.into()),
+            Label(room),
+            Mov(Q, field(storage, 0), Rcx.into()),
+        ]);
+        let src = if let Location::Slot(_) = src {
+            self.program.push(Mov(Q, operand(src), Rdx.into()));
+            Rdx.into()
+        } else {
+            operand(src)
+        };
+        self.program.extend([
+            Mov(Q, src, indexed(Some(Rcx), Rax, 8, 0)),
+            Unary(Inc, Q, field(storage, 16)),
+        ]);
+    }
+
+    // Moves `src`, sign-extended to 64 bits, to `%rdi`.
+    fn argument(&mut self, src: Location) {
+        self.program.push(match (self.options.width, src) {
+            (Width::W32, Location::Immediate(_)) | (Width::W64 | Width::W128, _) => {
+                Mov(Q, operand(src), Rdi.into())
+            }
+            (Width::W32, _) => Movslq(operand(src), Rdi),
+        });
+    }
+
+    // Moves `%rax` to `dst`.
+    fn store(&mut self, dst: Location) {
+        if dst != Location::Scratch {
+            self.program.push(Mov(Q, Rax.into(), operand(dst)));
+        }
+    }
+
+    // Registers a trap printing `message`, and returns its label.
+    fn trap(&mut self, message: String) -> Symbol {
+        self.traps.push(message);
+        Symbol::Trap(self.traps.len() - 1)
+    }
+
+    // Size of the values.
+    fn size(&self) -> Size {
+        match self.options.width {
+            Width::W32 => L,
+            _ => Q,
+        }
+    }
+}
+
+fn operand(location: Location) -> Operand {
+    match location {
+        Location::Register(r) => REGISTERS[r].into(),
+        Location::Slot(s) => mem(Rsp, s as i32 * 8),
+        Location::Immediate(value) => imm(value),
+        Location::Scratch => Rax.into(),
+    }
+}
+
+fn call(name: &'static str) -> Inst {
+    Call(Symbol::Named(name))
+}
+
+// Registers read by `instruction`.
+fn uses(instruction: &Instruction) -> Vec<Register> {
+    match *instruction {
+        Instruction::Push { src, .. }
+        | Instruction::PushFront { src, .. }
+        | Instruction::PrintDecimal(src)
+        | Instruction::PrintUnicode(src) => vec![src],
+        Instruction::Binary { lhs, rhs, .. } if lhs == rhs => vec![lhs],
+        Instruction::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+        _ => vec![],
+    }
+}
+
+// Address of the field at `offset` of the `storage`th storage.
+fn field(storage: usize, offset: usize) -> Operand {
+    rip("storage", (storage * 32 + offset) as i32)
+}
@@ -0,0 +1,404 @@
+/*
+ * Arbitrary-precision integers.
+ *
+ * An integer is either a small value v in [-2^62, 2^62) stored as v << 1, or a pointer to a
+ * reference-counted Big with its lowest bit set. Zero is always small, so an integer is nonzero
+ * exactly when its representation is.
+ */
+typedef struct {
+    int refs;
+    int negative;
+    int len;
+    uint32_t limb[];
+} Big;
+
+#define SMALL_MIN (-((integer)1 << 62))
+#define SMALL_MAX (((integer)1 << 62) - 1)
+#define IS_BIG(v) ((v) & 1)
+#define AS_BIG(v) ((Big*)((v) - 1))
+
+Big* big_alloc(int len) {
+    Big* b = malloc(sizeof(Big) + len * sizeof(uint32_t));
+    b->refs = 1;
+    b->negative = 0;
+    b->len = len;
+    return b;
+}
+
+integer big_retain(integer v) {
+    if (IS_BIG(v)) AS_BIG(v)->refs++;
+    return v;
+}
+
+void big_release(integer v) {
+    if (IS_BIG(v) && !--AS_BIG(v)->refs) free(AS_BIG(v));
+}
+
+integer big_finish(Big* b) {
+    while (b->len && !b->limb[b->len - 1]) b->len--;
+    if (b->len <= 2) {
+        uint64_t m = b->len ? b->limb[0] : 0;
+        if (b->len == 2) m |= (uint64_t)b->limb[1] << 32;
+        if (m <= (uint64_t)SMALL_MAX || (b->negative && m == (uint64_t)SMALL_MAX + 1)) {
+            integer v = b->negative ? -(integer)m : (integer)m;
+            free(b);
+            return v * 2;
+        }
+    }
+    return (integer)b + 1;
+}
+
+integer int_from(int64_t v) {
+    if (SMALL_MIN <= v && v <= SMALL_MAX) return v * 2;
+    uint64_t m = v < 0 ? -(uint64_t)v : (uint64_t)v;
+    Big* b = big_alloc(2);
+    b->negative = v < 0;
+    b->limb[0] = m;
+    b->limb[1] = m >> 32;
+    return big_finish(b);
+}
+
+typedef struct {
+    int negative;
+    int len;
+    const uint32_t* limb;
+    uint32_t small[2];
+} View;
+
+void view(View* w, integer v) {
+    if (IS_BIG(v)) {
+        Big* b = AS_BIG(v);
+        w->negative = b->negative;
+        w->len = b->len;
+        w->limb = b->limb;
+    } else {
+        int64_t s = v >> 1;
+        uint64_t m = s < 0 ? -(uint64_t)s : (uint64_t)s;
+        w->negative = s < 0;
+        w->small[0] = m;
+        w->small[1] = m >> 32;
+        w->len = m >> 32 ? 2 : m ? 1 : 0;
+        w->limb = w->small;
+    }
+}
+
+int mag_cmp(const View* a, const View* b) {
+    if (a->len != b->len) return a->len < b->len ? -1 : 1;
+    for (int i = a->len - 1; i >= 0; --i) {
+        if (a->limb[i] != b->limb[i]) return a->limb[i] < b->limb[i] ? -1 : 1;
+    }
+    return 0;
+}
+
+Big* mag_add(const View* a, const View* b) {
+    if (a->len < b->len) {
+        const View* t = a;
+        a = b;
+        b = t;
+    }
+    Big* r = big_alloc(a->len + 1);
+    uint64_t carry = 0;
+    for (int i = 0; i < a->len; ++i) {
+        carry += (uint64_t)a->limb[i] + (i < b->len ? b->limb[i] : 0);
+        r->limb[i] = carry;
+        carry >>= 32;
+    }
+    r->limb[a->len] = carry;
+    return r;
+}
+
+// Requires |a| >= |b|.
+Big* mag_sub(const View* a, const View* b) {
+    Big* r = big_alloc(a->len);
+    int64_t borrow = 0;
+    for (int i = 0; i < a->len; ++i) {
+        int64_t d = (int64_t)a->limb[i] - (i < b->len ? b->limb[i] : 0) - borrow;
+        borrow = d < 0;
+        r->limb[i] = d + (borrow << 32);
+    }
+    return r;
+}
+
+integer big_add(integer x, integer y, int negate) {
+    View a, b;
+    view(&a, x);
+    view(&b, y);
+    b.negative ^= negate;
+    Big* r;
+    if (a.negative == b.negative) {
+        r = mag_add(&a, &b);
+        r->negative = a.negative;
+    } else if (mag_cmp(&a, &b) >= 0) {
+        r = mag_sub(&a, &b);
+        r->negative = a.negative;
+    } else {
+        r = mag_sub(&b, &a);
+        r->negative = b.negative;
+    }
+    return big_finish(r);
+}
+
+integer big_mul(integer x, integer y) {
+    View a, b;
+    view(&a, x);
+    view(&b, y);
+    Big* r = big_alloc(a.len + b.len);
+    memset(r->limb, 0, r->len * sizeof(uint32_t));
+    for (int i = 0; i < a.len; ++i) {
+        uint64_t carry = 0;
+        for (int j = 0; j < b.len; ++j) {
+            carry += (uint64_t)a.limb[i] * b.limb[j] + r->limb[i + j];
+            r->limb[i + j] = carry;
+            carry >>= 32;
+        }
+        r->limb[i + b.len] = carry;
+    }
+    r->negative = a.negative != b.negative;
+    return big_finish(r);
+}
+
+// Knuth's algorithm D on magnitudes, requires |u| >= |v| > 0. Both results are truncated.
+void mag_divmod(const View* u, const View* v, Big* q, Big* r) {
+    int m = u->len, n = v->len;
+    if (n == 1) {
+        uint64_t k = 0;
+        for (int j = m - 1; j >= 0; --j) {
+            k = k << 32 | u->limb[j];
+            q->limb[j] = k / v->limb[0];
+            k %= v->limb[0];
+        }
+        r->limb[0] = k;
+        return;
+    }
+    int s = __builtin_clz(v->limb[n - 1]);
+    uint32_t* vn = malloc(n * sizeof(uint32_t));
+    uint32_t* un = malloc((m + 1) * sizeof(uint32_t));
+    for (int i = n - 1; i > 0; --i) {
+        vn[i] = v->limb[i] << s | (uint64_t)v->limb[i - 1] >> (32 - s);
+    }
+    vn[0] = v->limb[0] << s;
+    un[m] = (uint64_t)u->limb[m - 1] >> (32 - s);
+    for (int i = m - 1; i > 0; --i) {
+        un[i] = u->limb[i] << s | (uint64_t)u->limb[i - 1] >> (32 - s);
+    }
+    un[0] = u->limb[0] << s;
+    for (int j = m - n; j >= 0; --j) {
+        uint64_t top = (uint64_t)un[j + n] << 32 | un[j + n - 1];
+        uint64_t qhat = top / vn[n - 1];
+        uint64_t rhat = top % vn[n - 1];
+        while (qhat >> 32 || qhat * vn[n - 2] > (rhat << 32 | un[j + n - 2])) {
+            qhat--;
+            rhat += vn[n - 1];
+            if (rhat >> 32) break;
+        }
+        int64_t k = 0, t;
+        for (int i = 0; i < n; ++i) {
+            uint64_t p = qhat * vn[i];
+            t = un[i + j] - k - (int64_t)(p & 0xFFFFFFFF);
+            un[i + j] = t;
+            k = (p >> 32) - (t >> 32);
+        }
+        t = un[j + n] - k;
+        un[j + n] = t;
+        q->limb[j] = qhat;
+        if (t < 0) {
+            q->limb[j]--;
+            uint64_t c = 0;
+            for (int i = 0; i < n; ++i) {
+                c += (uint64_t)un[i + j] + vn[i];
+                un[i + j] = c;
+                c >>= 32;
+            }
+            un[j + n] += c;
+        }
+    }
+    for (int i = 0; i < n - 1; ++i) {
+        r->limb[i] = un[i] >> s | (uint64_t)un[i + 1] << (32 - s);
+    }
+    r->limb[n - 1] = un[n - 1] >> s;
+    free(vn);
+    free(un);
+}
+
+// Truncating division of integers which are not both small. `y` is never zero.
+integer big_divmod(integer x, integer y, int rem) {
+    View a, b;
+    view(&a, x);
+    view(&b, y);
+    if (mag_cmp(&a, &b) < 0) return rem ? big_retain(x) : 0;
+    Big* q = big_alloc(a.len);
+    Big* r = big_alloc(b.len);
+    memset(q->limb, 0, q->len * sizeof(uint32_t));
+    mag_divmod(&a, &b, q, r);
+    q->negative = a.negative != b.negative;
+    r->negative = a.negative;
+    if (rem) {
+        free(q);
+        return big_finish(r);
+    }
+    free(r);
+    return big_finish(q);
+}
+
+int big_compare(integer x, integer y) {
+    View a, b;
+    view(&a, x);
+    view(&b, y);
+    if (a.negative != b.negative) return a.negative ? -1 : 1;
+    int c = mag_cmp(&a, &b);
+    return a.negative ? -c : c;
+}
+
+static inline integer int_add(integer a, integer b) {
+    integer r;
+    if (!((a | b) & 1) && !__builtin_add_overflow(a, b, &r)) return r;
+    return big_add(a, b, 0);
+}
+
+static inline integer int_sub(integer a, integer b) {
+    integer r;
+    if (!((a | b) & 1) && !__builtin_sub_overflow(a, b, &r)) return r;
+    return big_add(a, b, 1);
+}
+
+static inline integer int_mul(integer a, integer b) {
+    integer r;
+    if (!((a | b) & 1) && !__builtin_mul_overflow(a >> 1, b, &r)) return r;
+    return big_mul(a, b);
+}
+
+static inline integer int_div(integer a, integer b) {
+    if (!((a | b) & 1)) return int_from((a >> 1) / (b >> 1));
+    return big_divmod(a, b, 0);
+}
+
+static inline integer int_rem(integer a, integer b) {
+    if (!((a | b) & 1)) return (a >> 1) % (b >> 1) * 2;
+    return big_divmod(a, b, 1);
+}
+
+static inline integer int_ge(integer a, integer b) {
+    if (!((a | b) & 1)) return (a >= b) * 2;
+    return (big_compare(a, b) >= 0) * 2;
+}
+
+static inline int nonzero(integer v) {
+    big_release(v);
+    return v != 0;
+}
+
+int exit_code(integer v) {
+    if (!IS_BIG(v)) return v >> 1;
+    Big* b = AS_BIG(v);
+    return b->negative ? -b->limb[0] : b->limb[0];
+}
+
+void print_bytes(IO* io, const char* bytes, int len) {
+    while (len) {
+        if (io->off == DEF_BUFSIZ) flush(io);
+        int n = DEF_BUFSIZ - io->off < len ? DEF_BUFSIZ - io->off : len;
+        memcpy(io->buffer + io->off, bytes, n);
+        io->off += n;
+        bytes += n;
+        len -= n;
+    }
+}
+
+void print_big(IO* io, integer v) {
+    if (!IS_BIG(v)) {
+        print_decimal(io, v >> 1);
+        return;
+    }
+    Big* b = AS_BIG(v);
+    int len = b->len;
+    uint32_t* mag = malloc(len * sizeof(uint32_t));
+    memcpy(mag, b->limb, len * sizeof(uint32_t));
+    // Every limb holds less than 10 decimal digits, plus a sign.
+    int size = len * 10 + 1;
+    char* temp = malloc(size);
+    int off = size;
+    while (len) {
+        uint64_t k = 0;
+        for (int i = len - 1; i >= 0; --i) {
+            k = k << 32 | mag[i];
+            mag[i] = k / 1000000000;
+            k %= 1000000000;
+        }
+        while (len && !mag[len - 1]) len--;
+        for (int i = 0; i < 9 && (len || k); ++i) {
+            temp[--off] = k % 10 + '0';
+            k /= 10;
+        }
+    }
+    if (b->negative) temp[--off] = '-';
+    print_bytes(io, temp + off, size - off);
+    free(temp);
+    free(mag);
+}
+
+integer scan_big(IO* io) {
+    signed char c;
+    while (1) {
+        c = peek_or_refill(io);
+        if (c == -1) return -1 * 2;
+        if (c > ' ') break;
+        consume(io);
+    }
+    int sign = c == '-';
+    if (sign) {
+        consume(io);
+        c = peek_or_refill(io);
+    }
+    integer v = 0;
+    while ('0' <= c && c <= '9') {
+        // Gather up to 18 digits at once, which always fit in a small integer.
+        int64_t chunk = 0, scale = 1;
+        for (int i = 0; i < 18 && '0' <= c && c <= '9'; ++i) {
+            chunk = chunk * 10 + c - '0';
+            scale *= 10;
+            consume(io);
+            c = peek_or_refill(io);
+        }
+        integer t = int_mul(v, scale * 2);
+        big_release(v);
+        v = int_add(t, chunk * 2);
+        big_release(t);
+    }
+    if (sign) {
+        integer t = int_sub(0, v);
+        big_release(v);
+        v = t;
+    }
+    return v;
+}
+
+#undef PRINTD
+#undef PRINTU
+#undef SCAND
+#undef SCANU
+#undef HALTS
+#undef HALTQ
+#undef HALTE
+#undef JNZQ
+#undef JNZS
+#undef JNZE
+#define INT(v) ((integer)(v) * 2)
+#define ADD(i, j) int_add(v ## i, v ## j)
+#define SUB(i, j) int_sub(v ## i, v ## j)
+#define MUL(i, j) int_mul(v ## i, v ## j)
+#define DIV(i, j) int_div(v ## i, v ## j)
+#define REM(i, j) int_rem(v ## i, v ## j)
+#define GE(i, j) int_ge(v ## i, v ## j)
+#define RETAIN(i) big_retain(v ## i)
+#define RELEASE(i) big_release(v ## i)
+#define PRINTD(i) print_big(&output, v ## i)
+#define PRINTU(i) print_utf8(&output, IS_BIG(v ## i) ? -1 : v ## i >> 1)
+#define SCAND scan_big(&input)
+#define SCANU INT(scan_utf8(&input))
+#define HALTS(i) return (flush(&output), size[i] ? exit_code(storage[i].stack.memory[--size[i]]) : 0)
+#define HALTQ(i) return (flush(&output), size[i] ? exit_code(pop_queue(&storage[i].queue)) : 0)
+#define HALTE(i) return (flush(&output), aheui_ext_size() ? exit_code(aheui_ext_pop()) : 0)
+#define JNZQ(i, j, k) if (nonzero(POPQ(i))) goto B ## j; else goto B ## k;
+#define JNZS(i, j, k) if (nonzero(POPS(i))) goto B ## j; else goto B ## k;
+#define JNZE(i, j, k) if (nonzero(POPE(i))) goto B ## j; else goto B ## k;
+
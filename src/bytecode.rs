@@ -0,0 +1,458 @@
+//! Serialized form of linearized aheui code, which runs without the source or a C compiler.
+//!
+//! Bytecode starts with a header:
+//!
+//! - the magic `PAHB` and the format version as a little-endian `u16`,
+//! - the integer width in bits, the [`Overflow`] mode and the [`ZeroDivision`] mode as bytes,
+//! - the number of blocks as a little-endian `u32`.
+//!
+//! The block table follows, giving each block the offset of its code in the opcode stream and the
+//! number of its registers, both as little-endian `u32`. The rest is the opcode stream, where a
+//! block is its instructions followed by its terminator, each an opcode byte and its operands.
+//! Operands are unsigned LEB128, except for constants, which are signed LEB128.
+
+use std::io::{Read, Write};
+
+use crate::ir::{BinaryOp, Block, Instruction, Terminator};
+use crate::{
+    linearize_with, parse, vm, Error, Extension, Integer, Options, Overflow, Width, ZeroDivision,
+    QUEUE,
+};
+
+/// Version of the format written by [`encode`], the only one [`decode`] accepts.
+pub const VERSION: u16 = 2;
+
+const MAGIC: &[u8; 4] = b"PAHB";
+const HEADER: usize = 13;
+
+const POP: u8 = 0x00;
+const PUSH: u8 = 0x01;
+const CONST: u8 = 0x02;
+const PRINT_DECIMAL: u8 = 0x03;
+const PRINT_UNICODE: u8 = 0x04;
+const SCAN_DECIMAL: u8 = 0x05;
+const SCAN_UNICODE: u8 = 0x06;
+const PUSH_FRONT: u8 = 0x07;
+// Followed by the index of the operation in `OPS`.
+const BINARY: u8 = 0x10;
+const GOTO: u8 = 0x20;
+const JSL: u8 = 0x21;
+const JNZ: u8 = 0x22;
+const JZ: u8 = 0x23;
+const HALT: u8 = 0x24;
+
+const OPS: [BinaryOp; 6] = [
+    BinaryOp::Add,
+    BinaryOp::Subtract,
+    BinaryOp::Multiply,
+    BinaryOp::Divide,
+    BinaryOp::Remainder,
+    BinaryOp::Compare,
+];
+
+/// Linearizes aheui `code` and encodes it as bytecode for `options`.
+///
+/// Big integers are not supported.
+pub fn compile(code: &str, options: &Options) -> Result<Vec<u8>, Error> {
+    if options.bigint {
+        return Err(Error::Unsupported {
+            option: "bigint",
+            backend: "bytecode",
+        });
+    }
+    Ok(encode(&linearize_with(&parse(code), options)?, options))
+}
+
+/// Runs `bytecode`, reading from `input` and writing to `output`.
+///
+/// Behaves like [`crate::interpret_with`] with the options the bytecode was compiled with, and
+/// returns the value the program halts with. The ㅎ storage is a plain stack.
+pub fn run(bytecode: &[u8], input: impl Read, output: impl Write) -> Result<Integer, Error> {
+    run_with_extension(bytecode, input, output, &mut Vec::new())
+}
+
+/// Runs `bytecode` like [`run`], forwarding the ㅎ storage to `extension` like
+/// [`crate::interpret_with_extension`].
+pub fn run_with_extension(
+    bytecode: &[u8],
+    input: impl Read,
+    output: impl Write,
+    extension: &mut dyn Extension,
+) -> Result<Integer, Error> {
+    let (blocks, options) = decode(bytecode)?;
+    vm::execute(&blocks, &options, input, output, extension)
+}
+
+/// Encodes `blocks` linearized with `options`.
+///
+/// Only the options needed to run the blocks are kept, and `bigint` is not one of them.
+pub fn encode(blocks: &[Block], options: &Options) -> Vec<u8> {
+    let mut code = vec![];
+    let mut table = vec![];
+    for block in blocks {
+        let registers = block
+            .instructions
+            .iter()
+            .filter_map(Instruction::defined)
+            .max()
+            .map_or(0, |r| r + 1);
+        table.push((code.len() as u32, registers as u32));
+        for instruction in &block.instructions {
+            encode_instruction(&mut code, instruction);
+        }
+        encode_terminator(&mut code, &block.terminator);
+    }
+
+    let mut output = MAGIC.to_vec();
+    output.extend(VERSION.to_le_bytes());
+    output.push(options.width.bits() as u8);
+    output.push(match options.overflow {
+        Overflow::Undefined => 0,
+        Overflow::Wrap => 1,
+        Overflow::Trap => 2,
+    });
+    output.push(match options.zero_division {
+        ZeroDivision::Undefined => 0,
+        ZeroDivision::Trap => 1,
+        ZeroDivision::Zero => 2,
+        ZeroDivision::Reflect => 3,
+    });
+    output.extend((blocks.len() as u32).to_le_bytes());
+    for (offset, registers) in table {
+        output.extend(offset.to_le_bytes());
+        output.extend(registers.to_le_bytes());
+    }
+    output.extend(code);
+    output
+}
+
+fn encode_instruction(code: &mut Vec<u8>, instruction: &Instruction) {
+    match *instruction {
+        Instruction::Pop { dst, storage } => {
+            code.push(POP);
+            unsigned(code, dst);
+            unsigned(code, storage);
+        }
+        Instruction::Push { storage, src } => {
+            code.push(PUSH);
+            unsigned(code, storage);
+            unsigned(code, src);
+        }
+        Instruction::PushFront { storage, src } => {
+            code.push(PUSH_FRONT);
+            unsigned(code, storage);
+            unsigned(code, src);
+        }
+        Instruction::Const { dst, value } => {
+            code.push(CONST);
+            unsigned(code, dst);
+            signed(code, value);
+        }
+        Instruction::Binary {
+            dst,
+            op,
+            lhs,
+            rhs,
+            cell: (r, c),
+        } => {
+            code.push(BINARY);
+            code.push(OPS.iter().position(|&o| o == op).unwrap() as u8);
+            for operand in [dst, lhs, rhs, r, c] {
+                unsigned(code, operand);
+            }
+        }
+        Instruction::PrintDecimal(src) => {
+            code.push(PRINT_DECIMAL);
+            unsigned(code, src);
+        }
+        Instruction::PrintUnicode(src) => {
+            code.push(PRINT_UNICODE);
+            unsigned(code, src);
+        }
+        Instruction::ScanDecimal(dst) => {
+            code.push(SCAN_DECIMAL);
+            unsigned(code, dst);
+        }
+        Instruction::ScanUnicode(dst) => {
+            code.push(SCAN_UNICODE);
+            unsigned(code, dst);
+        }
+    }
+}
+
+fn encode_terminator(code: &mut Vec<u8>, terminator: &Terminator) {
+    let (opcode, operands) = match *terminator {
+        Terminator::Goto(target) => (GOTO, vec![target]),
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => (JSL, vec![storage, size, less, otherwise]),
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => (JNZ, vec![storage, nonzero, zero]),
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => (JZ, vec![storage, zero, otherwise]),
+        Terminator::Halt { storage } => (HALT, vec![storage]),
+    };
+    code.push(opcode);
+    for operand in operands {
+        unsigned(code, operand);
+    }
+}
+
+fn unsigned(code: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        code.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    code.push(v as u8);
+}
+
+fn signed(code: &mut Vec<u8>, mut v: Integer) {
+    loop {
+        let byte = v as u8 & 0x7f;
+        v >>= 7;
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            return code.push(byte);
+        }
+        code.push(byte | 0x80);
+    }
+}
+
+/// Decodes `bytecode` into the blocks and the options it was compiled with.
+///
+/// Every register, storage and label is checked to be in range, and every register to be assigned
+/// before it is read, so the blocks are safe to run.
+pub fn decode(bytecode: &[u8]) -> Result<(Vec<Block>, Options), Error> {
+    let invalid = Error::InvalidBytecode;
+    if bytecode.len() < HEADER || &bytecode[..4] != MAGIC {
+        return Err(invalid("not palheui bytecode"));
+    }
+    if u16::from_le_bytes([bytecode[4], bytecode[5]]) != VERSION {
+        return Err(invalid("unsupported version"));
+    }
+    let width = match bytecode[6] {
+        32 => Width::W32,
+        64 => Width::W64,
+        128 => Width::W128,
+        _ => return Err(invalid("unknown width")),
+    };
+    let overflow = match bytecode[7] {
+        0 => Overflow::Undefined,
+        1 => Overflow::Wrap,
+        2 => Overflow::Trap,
+        _ => return Err(invalid("unknown overflow mode")),
+    };
+    let zero_division = match bytecode[8] {
+        0 => ZeroDivision::Undefined,
+        1 => ZeroDivision::Trap,
+        2 => ZeroDivision::Zero,
+        3 => ZeroDivision::Reflect,
+        _ => return Err(invalid("unknown zero division mode")),
+    };
+    let options = Options {
+        width,
+        overflow,
+        zero_division,
+        ..Options::default()
+    };
+    let count = u32_at(bytecode, 9)? as usize;
+    if count == 0 {
+        return Err(invalid("no blocks"));
+    }
+    let code = bytecode
+        .get(HEADER + count * 8..)
+        .ok_or(invalid("truncated block table"))?;
+    let mut blocks = Vec::with_capacity(count);
+    for i in 0..count {
+        let offset = u32_at(bytecode, HEADER + i * 8)? as usize;
+        let registers = u32_at(bytecode, HEADER + i * 8 + 4)? as usize;
+        // Every register is assigned by an instruction of at least two bytes.
+        if registers > code.len() {
+            return Err(invalid("too many registers"));
+        }
+        let mut reader = Reader {
+            code: code.get(offset..).ok_or(invalid("block out of range"))?,
+            defined: vec![false; registers],
+            labels: count,
+        };
+        blocks.push(reader.block()?);
+    }
+    Ok((blocks, options))
+}
+
+fn u32_at(bytecode: &[u8], at: usize) -> Result<u32, Error> {
+    let bytes = bytecode
+        .get(at..at + 4)
+        .ok_or(Error::InvalidBytecode("truncated header"))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+struct Reader<'a> {
+    code: &'a [u8],
+    /// Whether each register of the block being read is assigned so far.
+    defined: Vec<bool>,
+    /// Number of blocks.
+    labels: usize,
+}
+
+impl Reader<'_> {
+    fn block(&mut self) -> Result<Block, Error> {
+        let mut instructions = vec![];
+        loop {
+            let instruction = match self.byte()? {
+                POP => Instruction::Pop {
+                    dst: self.dst()?,
+                    storage: self.storage()?,
+                },
+                PUSH => Instruction::Push {
+                    storage: self.storage()?,
+                    src: self.src()?,
+                },
+                PUSH_FRONT => Instruction::PushFront {
+                    storage: match self.storage()? {
+                        QUEUE => QUEUE,
+                        _ => return Err(Error::InvalidBytecode("pushing to the front of a stack")),
+                    },
+                    src: self.src()?,
+                },
+                CONST => Instruction::Const {
+                    dst: self.dst()?,
+                    value: self.signed()?,
+                },
+                BINARY => Instruction::Binary {
+                    op: *OPS
+                        .get(self.byte()? as usize)
+                        .ok_or(Error::InvalidBytecode("unknown operation"))?,
+                    dst: self.dst()?,
+                    lhs: self.src()?,
+                    rhs: self.src()?,
+                    cell: (self.unsigned()?, self.unsigned()?),
+                },
+                PRINT_DECIMAL => Instruction::PrintDecimal(self.src()?),
+                PRINT_UNICODE => Instruction::PrintUnicode(self.src()?),
+                SCAN_DECIMAL => Instruction::ScanDecimal(self.dst()?),
+                SCAN_UNICODE => Instruction::ScanUnicode(self.dst()?),
+                opcode => {
+                    let terminator = self.terminator(opcode)?;
+                    return Ok(Block {
+                        instructions,
+                        terminator,
+                    });
+                }
+            };
+            instructions.push(instruction);
+        }
+    }
+
+    fn terminator(&mut self, opcode: u8) -> Result<Terminator, Error> {
+        let terminator = match opcode {
+            GOTO => Terminator::Goto(self.label()?),
+            JSL => Terminator::Jsl {
+                storage: self.storage()?,
+                size: self.unsigned()?,
+                less: self.label()?,
+                otherwise: self.label()?,
+            },
+            JNZ => Terminator::Jnz {
+                storage: self.storage()?,
+                nonzero: self.label()?,
+                zero: self.label()?,
+            },
+            JZ => Terminator::Jz {
+                storage: self.storage()?,
+                zero: self.label()?,
+                otherwise: self.label()?,
+            },
+            HALT => Terminator::Halt {
+                storage: self.storage()?,
+            },
+            _ => return Err(Error::InvalidBytecode("unknown opcode")),
+        };
+        Ok(terminator)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let (&byte, rest) = self
+            .code
+            .split_first()
+            .ok_or(Error::InvalidBytecode("truncated opcode stream"))?;
+        self.code = rest;
+        Ok(byte)
+    }
+
+    fn unsigned(&mut self) -> Result<usize, Error> {
+        let mut v = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            v |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(Error::InvalidBytecode("operand too large"))
+    }
+
+    fn signed(&mut self) -> Result<Integer, Error> {
+        let mut v: Integer = 0;
+        for shift in (0..Integer::BITS).step_by(7) {
+            let byte = self.byte()?;
+            v |= ((byte & 0x7f) as Integer) << shift;
+            if byte & 0x80 == 0 {
+                // Sign-extend from the last byte.
+                let used = shift + 7;
+                return Ok(if used < Integer::BITS {
+                    v << (Integer::BITS - used) >> (Integer::BITS - used)
+                } else {
+                    v
+                });
+            }
+        }
+        Err(Error::InvalidBytecode("constant too large"))
+    }
+
+    fn dst(&mut self) -> Result<usize, Error> {
+        let register = self.unsigned()?;
+        let defined = self
+            .defined
+            .get_mut(register)
+            .ok_or(Error::InvalidBytecode("register out of range"))?;
+        *defined = true;
+        Ok(register)
+    }
+
+    // Values are only read from registers assigned before in the same block.
+    fn src(&mut self) -> Result<usize, Error> {
+        let register = self.unsigned()?;
+        if self.defined.get(register) == Some(&true) {
+            Ok(register)
+        } else {
+            Err(Error::InvalidBytecode("register read before assignment"))
+        }
+    }
+
+    fn storage(&mut self) -> Result<usize, Error> {
+        let storage = self.unsigned()?;
+        if storage < 28 {
+            Ok(storage)
+        } else {
+            Err(Error::InvalidBytecode("storage out of range"))
+        }
+    }
+
+    fn label(&mut self) -> Result<usize, Error> {
+        let label = self.unsigned()?;
+        if label < self.labels {
+            Ok(label)
+        } else {
+            Err(Error::InvalidBytecode("label out of range"))
+        }
+    }
+}
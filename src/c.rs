@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{Integer, Options, Overflow, StorageKind, ZeroDivision, STREAM};
+
+pub(crate) fn emit(blocks: &[Block], options: &Options) -> String {
+    // The big integer runtime is built on 64-bit words.
+    let bits = if options.bigint {
+        64
+    } else {
+        options.width.bits()
+    };
+    let mut output = format!("#define INTEGER_BITS {bits}\n");
+    output.push_str(include_str!("header.c"));
+    if options.bigint {
+        output.push_str(include_str!("bigint.c"));
+    }
+    output.push_str(include_str!("entry.c"));
+    let passed = passed(blocks);
+    let locals: Vec<_> = (0..28)
+        .flat_map(|storage| {
+            let depth = passed.iter().map(|p| p[storage]).max().unwrap_or(0);
+            (0..depth).map(move |i| format!("a{storage}_{i}"))
+        })
+        .collect();
+    if !locals.is_empty() {
+        writeln!(output, "    integer {};", locals.join(",")).ok();
+    }
+    for (label, block) in blocks.iter().enumerate() {
+        emit_block(&mut output, label, block, &passed, options);
+    }
+    output.push_str(include_str!("footer.c"));
+    output
+}
+
+// Number of values on top of each stack that a block receives in the locals `a{storage}_{i}`
+// instead of memory, `i` counting from the top. These are the values the block pops before
+// pushing to the stack, which every `goto` to the block pushes right before it.
+//
+// Only stacks and `goto` edges pass values this way. The queue and the ㅎ storage always go
+// through memory, and so do the values of a block entered by a branch, which enters at `B{label}`
+// and loads them into the locals before falling through to `R{label}`.
+fn passed(blocks: &[Block]) -> Vec<[usize; 28]> {
+    let mut passed = vec![None; blocks.len()];
+    for block in blocks {
+        if let Terminator::Goto(target) = block.terminator {
+            let pushed = pushed(block);
+            let passed = passed[target].get_or_insert_with(|| popped(&blocks[target]));
+            for (passed, pushed) in passed.iter_mut().zip(pushed) {
+                *passed = pushed.min(*passed);
+            }
+        }
+    }
+    passed.into_iter().map(Option::unwrap_or_default).collect()
+}
+
+// Number of values the block pops from each stack before pushing to it.
+fn popped(block: &Block) -> [usize; 28] {
+    let mut popped = [0; 28];
+    let mut pushed = [false; 28];
+    for instruction in &block.instructions {
+        match *instruction {
+            Instruction::Pop { storage, .. } if !pushed[storage] => popped[storage] += 1,
+            Instruction::Push { storage, .. } => pushed[storage] = true,
+            _ => {}
+        }
+    }
+    for (storage, popped) in popped.iter_mut().enumerate() {
+        if StorageKind::from(storage) != StorageKind::Stack {
+            *popped = 0;
+        }
+    }
+    popped
+}
+
+// Number of values the block pushes to each storage.
+fn pushed(block: &Block) -> [usize; 28] {
+    let mut pushed = [0; 28];
+    for instruction in &block.instructions {
+        if let Instruction::Push { storage, .. } = *instruction {
+            pushed[storage] += 1;
+        }
+    }
+    pushed
+}
+
+fn emit_block(
+    output: &mut String,
+    label: usize,
+    block: &Block,
+    passed: &[[usize; 28]],
+    options: &Options,
+) {
+    // Values to pass to the target of a `goto`, and how many of them are left to push first.
+    let mut pass = [0; 28];
+    let mut skip = pushed(block);
+    if let Terminator::Goto(target) = block.terminator {
+        pass = passed[target];
+        for (skip, pass) in skip.iter_mut().zip(pass) {
+            *skip -= pass;
+        }
+    }
+    let mut popped = [0; 28];
+    let mut body = String::new();
+    // Big integers are reference counted. A register owns its value until its first push, and
+    // every other push takes a new reference.
+    let mut pushes = HashMap::new();
+    let def_init = "    integer ";
+    let mut def = String::from(def_init);
+    let effect_init = "    ";
+    let mut effect = String::from(effect_init);
+    for instruction in &block.instructions {
+        match instruction {
+            Instruction::Pop { .. }
+            | Instruction::Const { .. }
+            | Instruction::Binary { .. }
+            | Instruction::ScanDecimal(..)
+            | Instruction::ScanUnicode(..) => flush(&mut body, &mut effect, effect_init),
+            Instruction::Push { .. }
+            | Instruction::PushFront { .. }
+            | Instruction::PrintDecimal(..)
+            | Instruction::PrintUnicode(..) => flush(&mut body, &mut def, def_init),
+        }
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                if popped[storage] < passed[label][storage] {
+                    write!(def, "v{dst}=a{storage}_{},", popped[storage]).ok();
+                } else {
+                    write!(def, "v{dst}=POP{}({storage}),", kind(storage)).ok();
+                }
+                popped[storage] += 1;
+            }
+            Instruction::Push { storage, src } => {
+                let count = pushes.entry(src).or_insert(0);
+                *count += 1;
+                if options.bigint && *count > 1 {
+                    write!(effect, "RETAIN({src}),").ok();
+                }
+                if skip[storage] > 0 {
+                    skip[storage] -= 1;
+                    write!(effect, "PUSH{}({storage},{src}),", kind(storage)).ok();
+                } else {
+                    pass[storage] -= 1;
+                    write!(effect, "a{storage}_{}=v{src},", pass[storage]).ok();
+                }
+            }
+            Instruction::PushFront { storage, src } => {
+                let count = pushes.entry(src).or_insert(0);
+                *count += 1;
+                if options.bigint && *count > 1 {
+                    write!(effect, "RETAIN({src}),").ok();
+                }
+                write!(effect, "PUSHF{}({storage},{src}),", kind(storage)).ok();
+            }
+            Instruction::Const { dst, value } => {
+                write!(def, "v{dst}={},", literal(value, options)).ok();
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                write!(def, "v{dst}={},", binary(op, lhs, rhs, cell, options)).ok();
+            }
+            Instruction::PrintDecimal(src) => {
+                write!(effect, "PRINTD({src}),").ok();
+            }
+            Instruction::PrintUnicode(src) => {
+                write!(effect, "PRINTU({src}),").ok();
+            }
+            Instruction::ScanDecimal(dst) => {
+                write!(def, "v{dst}=SCAND,").ok();
+            }
+            Instruction::ScanUnicode(dst) => {
+                write!(def, "v{dst}=SCANU,").ok();
+            }
+        }
+    }
+    flush(&mut body, &mut def, def_init);
+    if options.bigint {
+        for dst in block.instructions.iter().filter_map(Instruction::defined) {
+            if !pushes.contains_key(&dst) {
+                write!(effect, "RELEASE({dst}),").ok();
+            }
+        }
+    }
+    flush(&mut body, &mut effect, effect_init);
+    let mut entry = format!("B{label}");
+    if passed[label].iter().any(|&n| n > 0) {
+        // The other edges come with the values in memory, and load them first.
+        let loads: Vec<_> = (0..28)
+            .flat_map(|storage| {
+                (0..passed[label][storage]).map(move |i| format!("a{storage}_{i}=POPS({storage})"))
+            })
+            .collect();
+        writeln!(output, "B{label}:\n    {};", loads.join(",")).ok();
+        entry = format!("R{label}");
+    }
+    if body.is_empty() {
+        writeln!(output, "{entry}:").ok();
+    } else {
+        writeln!(output, "{entry}:{{\n{body}}}").ok();
+    }
+    match block.terminator {
+        Terminator::Goto(target) if passed[target].iter().any(|&n| n > 0) => {
+            writeln!(output, "    goto R{target};").ok();
+        }
+        Terminator::Goto(target) => {
+            writeln!(output, "    goto B{target};").ok();
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            // Only the ㅎ storage needs its own macro, as the size of the others is in `size`.
+            let suffix = if storage == STREAM { "E" } else { "" };
+            writeln!(
+                output,
+                "    JSL{suffix}({storage}, {size}, {less}, {otherwise});"
+            )
+            .ok();
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            writeln!(
+                output,
+                "    JNZ{}({storage}, {nonzero}, {zero})",
+                kind(storage)
+            )
+            .ok();
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            writeln!(
+                output,
+                "    JZ{}({storage}, {zero}, {otherwise})",
+                kind(storage)
+            )
+            .ok();
+        }
+        Terminator::Halt { storage } => {
+            writeln!(output, "    HALT{}({storage});", kind(storage)).ok();
+        }
+    }
+}
+
+fn binary(
+    op: BinaryOp,
+    lhs: Register,
+    rhs: Register,
+    (r, c): (usize, usize),
+    options: &Options,
+) -> String {
+    let (r, c) = (r + 1, c + 1);
+    let expr = if options.bigint {
+        let op = match op {
+            BinaryOp::Add => "ADD",
+            BinaryOp::Subtract => "SUB",
+            BinaryOp::Multiply => "MUL",
+            BinaryOp::Divide => "DIV",
+            BinaryOp::Remainder => "REM",
+            BinaryOp::Compare => "GE",
+        };
+        format!("{op}({lhs},{rhs})")
+    } else {
+        let (plain, wrap, trap) = match op {
+            BinaryOp::Add => ("+", "WADD", "CADD"),
+            BinaryOp::Subtract => ("-", "WSUB", "CSUB"),
+            BinaryOp::Multiply => ("*", "WMUL", "CMUL"),
+            BinaryOp::Divide => ("/", "WDIV", "CDIV"),
+            BinaryOp::Remainder => ("%", "WREM", "CREM"),
+            // Comparison never overflows.
+            BinaryOp::Compare => return format!("v{lhs}>=v{rhs}"),
+        };
+        match options.overflow {
+            Overflow::Undefined => format!("v{lhs}{plain}v{rhs}"),
+            Overflow::Wrap => format!("{wrap}({lhs},{rhs})"),
+            Overflow::Trap => format!("{trap}({lhs},{rhs},{r},{c})"),
+        }
+    };
+    if !matches!(op, BinaryOp::Divide | BinaryOp::Remainder) {
+        return expr;
+    }
+    match options.zero_division {
+        // Reflection happens before the division, in a separate block.
+        ZeroDivision::Undefined | ZeroDivision::Reflect => expr,
+        ZeroDivision::Trap => format!("(CHECKZ({rhs},{r},{c}),{expr})"),
+        ZeroDivision::Zero => format!("v{rhs}?{expr}:0"),
+    }
+}
+
+// C expression of the constant `value`, which C has no literal for when it is below `INT64_MIN`
+// or does not fit in 64 bits.
+fn literal(value: Integer, options: &Options) -> String {
+    let small = (-(1 << 62)..1 << 62).contains(&value);
+    match i64::try_from(value) {
+        Ok(_) if options.bigint && small => format!("INT({value})"),
+        Ok(i64::MIN) if options.bigint => "int_from(INT64_MIN)".to_string(),
+        Ok(_) if options.bigint => format!("int_from({value})"),
+        Ok(i64::MIN) => "INT64_MIN".to_string(),
+        Ok(_) => value.to_string(),
+        Err(_) => format!(
+            "(integer)((uinteger){}ull<<64|{}ull)",
+            (value >> 64) as u64,
+            value as u64
+        ),
+    }
+}
+
+// Suffix of the macros accessing `storage`.
+fn kind(storage: usize) -> &'static str {
+    match StorageKind::from(storage) {
+        StorageKind::Stack => "S",
+        StorageKind::Queue => "Q",
+        StorageKind::Stream => "E",
+    }
+}
+
+// Terminates a comma-separated C statement in `line` and moves it to `body`.
+fn flush(body: &mut String, line: &mut String, init: &str) {
+    if line.len() > init.len() {
+        line.pop();
+        line.push_str(";\n");
+        body.push_str(line);
+        line.truncate(0);
+        line.push_str(init);
+    }
+}
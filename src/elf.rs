@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use crate::x86::{Inst, Operand, Program, Reg, Size, Symbol};
+
+// Address the executable is loaded at.
+const BASE: u64 = 0x400000;
+const PAGE: u64 = 0x1000;
+// Sizes of the ELF header and of a program header.
+const EHDR: u64 = 64;
+const PHDR: u64 = 56;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Section {
+    Text,
+    Rodata,
+    Bss,
+}
+
+// Reference to `symbol` + `addend` as a 32-bit displacement at `at`, relative to `next`.
+struct Fixup {
+    at: usize,
+    next: usize,
+    symbol: Symbol,
+    addend: i32,
+}
+
+// Encodes `program` into a static executable for x86-64 Linux, entered at `_start`.
+//
+// Jumps are never relaxed, and symbols are only referred to relative to `%rip`.
+pub(crate) fn assemble(program: &Program) -> Vec<u8> {
+    let mut code = vec![];
+    let mut symbols = HashMap::new();
+    let mut fixups = vec![];
+    for inst in &program.text {
+        if let Inst::Label(symbol) = *inst {
+            symbols.insert(symbol, (Section::Text, code.len()));
+            continue;
+        }
+        let mut encoder = Encoder::default();
+        encoder.instruction(inst);
+        if let Some((at, symbol, addend)) = encoder.fixup {
+            fixups.push(Fixup {
+                at: code.len() + at,
+                next: code.len() + encoder.bytes.len(),
+                symbol,
+                addend,
+            });
+        }
+        code.extend(encoder.bytes);
+    }
+    let mut rodata: Vec<u8> = vec![];
+    for (symbol, bytes) in &program.rodata {
+        symbols.insert(*symbol, (Section::Rodata, rodata.len()));
+        rodata.extend(bytes);
+    }
+    let mut bss = 0;
+    for &(symbol, size) in &program.bss {
+        symbols.insert(symbol, (Section::Bss, bss));
+        bss += size;
+    }
+
+    let text_address = BASE + EHDR + 2 * PHDR;
+    let rodata_address = (text_address + code.len() as u64).next_multiple_of(16);
+    let file_size = rodata_address - BASE + rodata.len() as u64;
+    let bss_address = (BASE + file_size).next_multiple_of(PAGE);
+    let address = |symbol: Symbol| {
+        let (section, offset) = symbols[&symbol];
+        offset as u64
+            + match section {
+                Section::Text => text_address,
+                Section::Rodata => rodata_address,
+                Section::Bss => bss_address,
+            }
+    };
+    for fixup in &fixups {
+        let next = text_address + fixup.next as u64;
+        let displacement =
+            (address(fixup.symbol) as i64 + fixup.addend as i64 - next as i64) as i32;
+        code[fixup.at..fixup.at + 4].copy_from_slice(&displacement.to_le_bytes());
+    }
+
+    let mut output = vec![];
+    output.extend(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    output.extend(2u16.to_le_bytes());
+    output.extend(0x3eu16.to_le_bytes());
+    output.extend(1u32.to_le_bytes());
+    output.extend(address(Symbol::Named("_start")).to_le_bytes());
+    output.extend(EHDR.to_le_bytes());
+    output.extend(0u64.to_le_bytes());
+    output.extend(0u32.to_le_bytes());
+    for half in [EHDR, PHDR, 2, 64, 0, 0] {
+        output.extend((half as u16).to_le_bytes());
+    }
+    // Code and read-only data, then the zeroed storages and buffers.
+    program_header(&mut output, 5, 0, BASE, file_size, file_size);
+    program_header(&mut output, 6, 0, bss_address, 0, bss as u64);
+    output.extend(code);
+    output.resize((rodata_address - BASE) as usize, 0);
+    output.extend(rodata);
+    output
+}
+
+fn program_header(
+    output: &mut Vec<u8>,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+) {
+    output.extend(1u32.to_le_bytes());
+    output.extend(flags.to_le_bytes());
+    for field in [offset, address, address, file_size, memory_size, PAGE] {
+        output.extend(field.to_le_bytes());
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    /// Offset of a 32-bit displacement to `symbol` + addend, relative to the next instruction.
+    fixup: Option<(usize, Symbol, i32)>,
+}
+
+impl Encoder {
+    fn instruction(&mut self, inst: &Inst) {
+        match *inst {
+            Inst::Label(_) => {}
+            Inst::Syscall => self.bytes.extend([0x0f, 0x05]),
+            Inst::Ret => self.bytes.push(0xc3),
+            Inst::Cltd => self.bytes.push(0x99),
+            Inst::Cqto => self.bytes.extend([0x48, 0x99]),
+            Inst::Call(symbol) => self.relative(&[0xe8], symbol),
+            Inst::Jmp(symbol) => self.relative(&[0xe9], symbol),
+            Inst::J(cond, symbol) => self.relative(&[0x0f, 0x80 | cond.encoding().1], symbol),
+            Inst::Movabs(value, dst) => {
+                self.bytes.push(0x48 | dst.number() >> 3);
+                self.bytes.push(0xb8 | dst.number() & 7);
+                self.bytes.extend(value.to_le_bytes());
+            }
+            Inst::Movzbl(src, dst) => {
+                self.encode(Size::L, &[0x0f, 0xb6], dst.number(), src, low_byte(src));
+            }
+            Inst::Movsbq(src, dst) => {
+                self.encode(Size::Q, &[0x0f, 0xbe], dst.number(), src, low_byte(src));
+            }
+            Inst::Movslq(src, dst) => self.modrm(Size::Q, &[0x63], dst, src),
+            Inst::Setge(dst) => self.extension(Size::B, &[0x0f, 0x9d], 0, dst.into()),
+            Inst::Push(reg) | Inst::Pop(reg) => {
+                if reg.number() >= 8 {
+                    self.bytes.push(0x41);
+                }
+                let opcode = if let Inst::Push(_) = inst { 0x50 } else { 0x58 };
+                self.bytes.push(opcode | reg.number() & 7);
+            }
+            Inst::Mov(Size::L, Operand::Imm(value), Operand::Reg(dst)) => {
+                if dst.number() >= 8 {
+                    self.bytes.push(0x41);
+                }
+                self.bytes.push(0xb8 | dst.number() & 7);
+                self.bytes.extend((value as i32).to_le_bytes());
+            }
+            Inst::Mov(size, Operand::Imm(value), dst) => {
+                let opcode = if size == Size::B { 0xc6 } else { 0xc7 };
+                self.extension(size, &[opcode], 0, dst);
+                self.immediate(size, value);
+            }
+            Inst::Mov(size, src, dst) => self.binary(size, 0x88, 0x8a, src, dst),
+            Inst::Lea(src, dst) => self.modrm(Size::Q, &[0x8d], dst, src),
+            Inst::Alu(op, size, src, dst) => {
+                let op = op.encoding().1;
+                match src {
+                    Operand::Imm(value) if size == Size::B => {
+                        self.extension(size, &[0x80], op, dst);
+                        self.immediate(size, value);
+                    }
+                    Operand::Imm(value) if i8::try_from(value).is_ok() => {
+                        self.extension(size, &[0x83], op, dst);
+                        self.immediate(Size::B, value);
+                    }
+                    Operand::Imm(value) => {
+                        self.extension(size, &[0x81], op, dst);
+                        self.immediate(size, value);
+                    }
+                    _ => self.binary(size, op * 8, op * 8 + 2, src, dst),
+                }
+            }
+            Inst::Test(size, Operand::Imm(value), dst) => {
+                let opcode = if size == Size::B { 0xf6 } else { 0xf7 };
+                self.extension(size, &[opcode], 0, dst);
+                self.immediate(size, value);
+            }
+            Inst::Test(size, src, dst) => self.binary(size, 0x84, 0x84, src, dst),
+            Inst::Unary(op, size, dst) => {
+                let (_, opcode, op) = op.encoding();
+                let opcode = if size == Size::B { opcode } else { opcode + 1 };
+                self.extension(size, &[opcode], op, dst);
+            }
+            Inst::Shift(op, size, count, dst) => {
+                self.extension(size, &[0xc1], op.encoding().1, dst);
+                self.bytes.push(count);
+            }
+            Inst::Imul(size, src, dst) => self.modrm(size, &[0x0f, 0xaf], dst, src),
+            Inst::ImulImm(size, value, src, dst) => {
+                if i8::try_from(value).is_ok() {
+                    self.modrm(size, &[0x6b], dst, src);
+                    self.immediate(Size::B, value);
+                } else {
+                    self.modrm(size, &[0x69], dst, src);
+                    self.immediate(size, value);
+                }
+            }
+        }
+    }
+
+    // Encodes `opcode` with a 32-bit displacement to `symbol`.
+    fn relative(&mut self, opcode: &[u8], symbol: Symbol) {
+        self.bytes.extend(opcode);
+        self.fixup = Some((self.bytes.len(), symbol, 0));
+        self.bytes.extend([0; 4]);
+    }
+
+    // Encodes an instruction between registers or memory, with the register-to-memory `store` or
+    // the memory-to-register `load` opcode for bytes, and the next one for wider operands.
+    fn binary(&mut self, size: Size, store: u8, load: u8, src: Operand, dst: Operand) {
+        let wide = (size != Size::B) as u8;
+        match (src, dst) {
+            (Operand::Reg(src), _) => self.modrm(size, &[store + wide], src, dst),
+            (_, Operand::Reg(dst)) => self.modrm(size, &[load + wide], dst, src),
+            _ => unreachable!("no x86 instruction takes two memory operands"),
+        }
+    }
+
+    // Encodes an immediate of `size`, or 32 bits sign-extended for 64-bit operands.
+    fn immediate(&mut self, size: Size, value: i64) {
+        let length = match size {
+            Size::B => 1,
+            Size::L | Size::Q => 4,
+        };
+        self.bytes.extend(&value.to_le_bytes()[..length]);
+    }
+
+    // Encodes `opcode` with the register `reg` in the ModRM byte.
+    fn modrm(&mut self, size: Size, opcode: &[u8], reg: Reg, rm: Operand) {
+        let byte = size == Size::B && (low_byte(reg.into()) || low_byte(rm));
+        self.encode(size, opcode, reg.number(), rm, byte);
+    }
+
+    // Encodes `opcode` with the extension `op` in the ModRM byte.
+    fn extension(&mut self, size: Size, opcode: &[u8], op: u8, rm: Operand) {
+        let byte = size == Size::B && low_byte(rm);
+        self.encode(size, opcode, op, rm, byte);
+    }
+
+    // Encodes `opcode` with `reg` in the ModRM byte, and a REX prefix if needed or if `byte`.
+    fn encode(&mut self, size: Size, opcode: &[u8], reg: u8, rm: Operand, byte: bool) {
+        let (b, x) = match rm {
+            Operand::Reg(r) => (r.number() >> 3, 0),
+            Operand::Mem { base, index, .. } => (
+                base.map_or(0, |base| base.number() >> 3),
+                index.map_or(0, |(index, _)| index.number() >> 3),
+            ),
+            Operand::Rip(..) => (0, 0),
+            Operand::Imm(_) => unreachable!("immediates are encoded apart from the ModRM byte"),
+        };
+        let rex = ((size == Size::Q) as u8) << 3 | (reg >> 3) << 2 | x << 1 | b;
+        if rex != 0 || byte {
+            self.bytes.push(0x40 | rex);
+        }
+        self.bytes.extend(opcode);
+        let reg = (reg & 7) << 3;
+        let (base, index, disp) = match rm {
+            Operand::Reg(r) => return self.bytes.push(0xc0 | reg | r.number() & 7),
+            Operand::Rip(symbol, addend) => {
+                self.bytes.push(reg | 0x05);
+                self.fixup = Some((self.bytes.len(), symbol, addend));
+                return self.bytes.extend([0; 4]);
+            }
+            Operand::Mem { base, index, disp } => (base, index, disp),
+            Operand::Imm(_) => unreachable!(),
+        };
+        let Some(base) = base.map(Reg::number) else {
+            // Only an index, which needs a 32-bit displacement.
+            let (index, scale) = index.expect("absolute addresses are not supported");
+            self.bytes.push(reg | 0x04);
+            self.bytes
+                .push(scale_bits(scale) | (index.number() & 7) << 3 | 0x05);
+            return self.bytes.extend(disp.to_le_bytes());
+        };
+        // `%rbp` and `%r13` as a base always need a displacement.
+        let mode = match disp {
+            0 if base & 7 != 5 => 0x00,
+            -128..=127 => 0x40,
+            _ => 0x80,
+        };
+        match index {
+            Some((index, scale)) => {
+                self.bytes.push(mode | reg | 0x04);
+                self.bytes
+                    .push(scale_bits(scale) | (index.number() & 7) << 3 | base & 7);
+            }
+            // `%rsp` and `%r12` as a base need an SIB byte.
+            None if base & 7 == 4 => self.bytes.extend([mode | reg | 0x04, 0x24]),
+            None => self.bytes.push(mode | reg | base & 7),
+        }
+        match mode {
+            0x40 => self.bytes.push(disp as u8),
+            0x80 => self.bytes.extend(disp.to_le_bytes()),
+            _ => {}
+        }
+    }
+}
+
+// Whether `operand` is `%spl`, `%bpl`, `%sil` or `%dil` as a byte, which needs a REX prefix to be
+// told from `%ah` and others.
+fn low_byte(operand: Operand) -> bool {
+    matches!(operand, Operand::Reg(r) if (4..8).contains(&r.number()))
+}
+
+fn scale_bits(scale: u8) -> u8 {
+    match scale {
+        1 => 0x00,
+        2 => 0x40,
+        4 => 0x80,
+        8 => 0xc0,
+        _ => unreachable!("invalid scale {scale}"),
+    }
+}
@@ -0,0 +1,10 @@
+int main() {
+    IO input, output;
+    new_stdin(&input);
+    new_stdout(&output);
+    union Storage storage[28];
+    for (int i = 0; i < 28; ++i) {
+        if (i == 21) new_queue(&storage[i].queue);
+        else new_stack(&storage[i].stack);
+    }
+    int size[28] = {};
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io;
+
+use crate::ir::BinaryOp;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The code has no cells at all.
+    EmptyProgram,
+    /// The code has cells, but none of them is an aheui instruction.
+    NoCode,
+    /// The interpreter or the JIT divided by zero at the given 0-based cell.
+    DivisionByZero {
+        r: usize,
+        c: usize,
+    },
+    /// The interpreter or the JIT overflowed in `op` at the given 0-based cell.
+    Overflow {
+        op: BinaryOp,
+        r: usize,
+        c: usize,
+    },
+    /// The bytecode is malformed, or was written by another version of the format.
+    InvalidBytecode(&'static str),
+    /// The backend cannot generate code with `option`.
+    Unsupported {
+        option: &'static str,
+        backend: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::EmptyProgram => write!(f, "program is empty"),
+            Self::NoCode => write!(f, "program contains no Hangul instructions"),
+            Self::DivisionByZero { r, c } => {
+                write!(f, "division by zero at line {}, column {}", r + 1, c + 1)
+            }
+            Self::Overflow { op, r, c } => {
+                write!(f, "overflow in {op} at line {}, column {}", r + 1, c + 1)
+            }
+            Self::InvalidBytecode(reason) => write!(f, "invalid bytecode: {reason}"),
+            Self::Unsupported { option, backend } => {
+                write!(f, "{backend} backend does not support {option}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
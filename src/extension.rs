@@ -0,0 +1,42 @@
+use crate::Integer;
+
+/// Host side of the ㅎ storage, which aheui reserves for extensions.
+///
+/// Every push and pop on the storage is forwarded to the extension as the program executes it.
+/// The default extension, `Vec<Integer>`, makes the storage behave like a stack.
+pub trait Extension {
+    /// Number of values the program can pop now.
+    fn len(&self) -> usize;
+
+    /// Receives a value from the program.
+    fn push(&mut self, v: Integer);
+
+    /// Sends a value to the program. Only called when `len` is nonzero.
+    fn pop(&mut self) -> Integer;
+
+    /// Returns the value `pop` would send without removing it. Only called when `len` is
+    /// nonzero.
+    fn peek(&self) -> Integer;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Extension for Vec<Integer> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn push(&mut self, v: Integer) {
+        Vec::push(self, v);
+    }
+
+    fn pop(&mut self) -> Integer {
+        Vec::pop(self).expect("pop on an empty extension")
+    }
+
+    fn peek(&self) -> Integer {
+        self.last().copied().expect("peek on an empty extension")
+    }
+}
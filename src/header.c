@@ -1,9 +1,24 @@
+#include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
 #include <unistd.h>
 #include <stdint.h>
 
+#ifndef INTEGER_BITS
+#define INTEGER_BITS 64
+#endif
+#if INTEGER_BITS == 32
+typedef int32_t integer;
+typedef uint32_t uinteger;
+#elif INTEGER_BITS == 128
+typedef __int128 integer;
+typedef unsigned __int128 uinteger;
+#else
 typedef int64_t integer;
+typedef uint64_t uinteger;
+#endif
+// Enough for every digit of the widest value and its sign.
+#define DECIMAL_LEN (INTEGER_BITS * 3 / 10 + 2)
 
 #define DEF_CAPACITY (1 << 16)
 typedef struct {
@@ -45,6 +60,26 @@ void push_stack(Stack* st, int current_size, integer v) {
     }
 }
 
+// The ㅎ storage is a channel to the host. Link definitions of the functions below to replace
+// these defaults, which make it behave like a stack. `aheui_ext_pop` and `aheui_ext_peek` are
+// only called when `aheui_ext_size` is positive.
+static Stack ext_stack;
+static int ext_size;
+
+__attribute__((weak)) int aheui_ext_size(void) {
+    return ext_size;
+}
+__attribute__((weak)) void aheui_ext_push(integer v) {
+    if (!ext_stack.memory) new_stack(&ext_stack);
+    push_stack(&ext_stack, ext_size++, v);
+}
+__attribute__((weak)) integer aheui_ext_pop(void) {
+    return ext_stack.memory[--ext_size];
+}
+__attribute__((weak)) integer aheui_ext_peek(void) {
+    return ext_stack.memory[ext_size - 1];
+}
+
 void new_queue(Queue* q) {
     q->front = 0;
     q->back = 0;
@@ -72,7 +107,8 @@ void push_queue(Queue* q, integer v, int newsize) {
     q->memory[q->back++] = v;
     if (q->back == q->capacity) q->back = 0;
 }
-void push_queue_front(Queue* q, integer v) {
+void push_queue_front(Queue* q, integer v, int newsize) {
+    if (newsize == q->capacity) extend_queue(q, newsize - 1);
     if (q->front == 0) q->front = q->capacity;
     q->memory[--q->front] = v;
 }
@@ -92,16 +128,16 @@ void flush(IO* io) {
 }
 
 void print_decimal(IO* io, integer v) {
-    uint64_t w = v;
-    char temp[24];
-    int off = 24;
+    uinteger w = v;
+    char temp[DECIMAL_LEN];
+    int off = DECIMAL_LEN;
     int sign = v < 0;
     if (sign) w = -w;
     do {
 	temp[--off] = w % 10 + '0';
     } while (w /= 10);
     if (sign) temp[--off] = '-';
-    int len = 24 - off;
+    int len = DECIMAL_LEN - off;
     if (io->off + len > DEF_BUFSIZ) flush(io);
     memcpy(io->buffer + io->off, temp + off, len);
     io->off += len;
@@ -150,7 +186,7 @@ void consume(IO* io) {
 }
 
 integer scan_decimal(IO* io) {
-    uint64_t v = 0;
+    uinteger v = 0;
     signed char c;
     while (1) {
         c = peek_or_refill(io);
@@ -206,27 +242,77 @@ integer scan_utf8(IO* io) {
     return v;
 }
 
+void overflow(IO* io, const char* op, int r, int c) {
+    flush(io);
+    fprintf(stderr, "overflow in %s at line %d, column %d\n", op, r, c);
+    abort();
+}
+
+integer division_by_zero(IO* io, int r, int c) {
+    flush(io);
+    fprintf(stderr, "division by zero at line %d, column %d\n", r, c);
+    abort();
+}
+
+integer checked_add(IO* io, integer a, integer b, int r, int c) {
+    integer v;
+    if (__builtin_add_overflow(a, b, &v)) overflow(io, "addition", r, c);
+    return v;
+}
+
+integer checked_sub(IO* io, integer a, integer b, int r, int c) {
+    integer v;
+    if (__builtin_sub_overflow(a, b, &v)) overflow(io, "subtraction", r, c);
+    return v;
+}
+
+integer checked_mul(IO* io, integer a, integer b, int r, int c) {
+    integer v;
+    if (__builtin_mul_overflow(a, b, &v)) overflow(io, "multiplication", r, c);
+    return v;
+}
+
+integer checked_div(IO* io, integer a, integer b, int r, int c) {
+    integer v;
+    if (b == -1 && __builtin_sub_overflow((integer)0, a, &v)) overflow(io, "division", r, c);
+    return a / b;
+}
+
+integer checked_rem(integer a, integer b) {
+    return b == -1 ? 0 : a % b;
+}
+
 #define PUSHS(i, j) push_stack(&storage[i].stack, size[i]++, v ## j)
 #define POPS(i) storage[i].stack.memory[--size[i]]
 #define PUSHQ(i, j) push_queue(&storage[i].queue, v ## j, ++size[i])
 #define POPQ(i) (size[i]--, pop_queue(&storage[i].queue))
+#define PUSHFQ(i, j) push_queue_front(&storage[i].queue, v ## j, ++size[i])
 #define PRINTD(i) print_decimal(&output, v ## i)
 #define PRINTU(i) print_utf8(&output, v ## i)
 #define SCAND scan_decimal(&input)
 #define SCANU scan_utf8(&input)
 #define HALTS(i) return (flush(&output), size[i] ? storage[i].stack.memory[--size[i]] : 0)
 #define HALTQ(i) return (flush(&output), size[i] ? pop_queue(&storage[i].queue) : 0)
+#define PUSHE(i, j) aheui_ext_push(v ## j)
+#define POPE(i) aheui_ext_pop()
+#define HALTE(i) return (flush(&output), aheui_ext_size() ? aheui_ext_pop() : 0)
+#define JSLE(i, n, j, k) if (aheui_ext_size() < n) goto B ## j; else goto B ## k;
+#define JNZE(i, j, k) if (POPE(i)) goto B ## j; else goto B ## k;
+#define JZE(i, j, k) if (aheui_ext_peek()) goto B ## k; else goto B ## j;
 #define JSL(i, n, j, k) if (size[i] < n) goto B ## j; else goto B ## k;
 #define JNZQ(i, j, k) if (POPQ(i)) goto B ## j; else goto B ## k;
 #define JNZS(i, j, k) if (POPS(i)) goto B ## j; else goto B ## k;
+#define WADD(i, j) (integer)((uinteger)v ## i + (uinteger)v ## j)
+#define WSUB(i, j) (integer)((uinteger)v ## i - (uinteger)v ## j)
+#define WMUL(i, j) (integer)((uinteger)v ## i * (uinteger)v ## j)
+#define WDIV(i, j) (v ## j == -1 ? (integer)-(uinteger)v ## i : v ## i / v ## j)
+#define WREM(i, j) (v ## j == -1 ? 0 : v ## i % v ## j)
+#define JZS(i, j, k) if (storage[i].stack.memory[size[i] - 1]) goto B ## k; else goto B ## j;
+#define JZQ(i, j, k) if (storage[i].queue.memory[storage[i].queue.front]) goto B ## k; else goto B ## j;
+#define CHECKZ(j, r, c) (v ## j ? 0 : division_by_zero(&output, r, c))
+#define CADD(i, j, r, c) checked_add(&output, v ## i, v ## j, r, c)
+#define CSUB(i, j, r, c) checked_sub(&output, v ## i, v ## j, r, c)
+#define CMUL(i, j, r, c) checked_mul(&output, v ## i, v ## j, r, c)
+#define CDIV(i, j, r, c) checked_div(&output, v ## i, v ## j, r, c)
+#define CREM(i, j, r, c) checked_rem(v ## i, v ## j)
 
-int main() {
-    IO input, output;
-    new_stdin(&input);
-    new_stdout(&output);
-    union Storage storage[28];
-    for (int i = 0; i < 28; ++i) {
-        if (i == 21) new_queue(&storage[i].queue);
-        else new_stack(&storage[i].stack);
-    }
-    int size[28] = {};
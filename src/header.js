@@ -0,0 +1,208 @@
+// Values are `BigInt`s, wrapped to `BITS` bits unless it is 0.
+const wrap = (v) => (BITS ? BigInt.asIntN(BITS, v) : v);
+
+// Exit code of a program halting with `v`, as `int` in C.
+const exitCode = (v) => Number(BigInt.asIntN(32, v));
+
+class Queue {
+  constructor() {
+    this.items = [];
+    this.front = 0;
+  }
+
+  get length() {
+    return this.items.length - this.front;
+  }
+
+  push(v) {
+    this.items.push(v);
+  }
+
+  unshift(v) {
+    if (this.front > 0) {
+      this.items[--this.front] = v;
+    } else {
+      this.items.unshift(v);
+    }
+  }
+
+  shift() {
+    const v = this.items[this.front++];
+    // Drops the popped items once they are the majority, to keep popping in amortized O(1).
+    if (this.front >= 1024 && this.front * 2 >= this.items.length) {
+      this.items = this.items.slice(this.front);
+      this.front = 0;
+    }
+    return v;
+  }
+
+  peek() {
+    return this.items[this.front];
+  }
+}
+
+// Calls `put` with the UTF-8 bytes of the code point `c`. Surrogates are encoded as-is, so the
+// encoding is done here instead of by `TextEncoder`.
+function encodeUtf8(c, put) {
+  if (c < 0x80) {
+    put(c);
+  } else if (c < 0x800) {
+    put(0xc0 | (c >> 6));
+    put(0x80 | (c & 0x3f));
+  } else if (c < 0x10000) {
+    put(0xe0 | (c >> 12));
+    put(0x80 | ((c >> 6) & 0x3f));
+    put(0x80 | (c & 0x3f));
+  } else {
+    put(0xf0 | (c >> 18));
+    put(0x80 | ((c >> 12) & 0x3f));
+    put(0x80 | ((c >> 6) & 0x3f));
+    put(0x80 | (c & 0x3f));
+  }
+}
+
+// Byte callbacks for `Io` that pass strings to `read` and `write` instead. Lone surrogates are
+// encoded and decoded like any other code point rather than replaced with U+FFFD, so that they
+// survive a round trip through the program. A high surrogate ending a chunk of the input is
+// held back until the next chunk, which may start with its pair.
+function stringCallbacks(read, write) {
+  let high = "";
+  const readBytes = () => {
+    for (;;) {
+      const chunk = read();
+      let text = high + (chunk ?? "");
+      high = "";
+      if (!chunk) {
+        // An empty chunk ends the input, as for bytes.
+        if (!text) return null;
+      } else if (/[\ud800-\udbff]$/.test(text)) {
+        high = text.slice(-1);
+        text = text.slice(0, -1);
+        if (!text) continue;
+      }
+      const bytes = [];
+      for (const c of text) encodeUtf8(c.codePointAt(0), (b) => bytes.push(b));
+      return Uint8Array.from(bytes);
+    }
+  };
+  // `Io` never splits a character between chunks, so each chunk decodes on its own.
+  const writeBytes = (bytes) => {
+    let text = "";
+    for (let i = 0; i < bytes.length; ) {
+      const b = bytes[i++];
+      const trailing = b < 0x80 ? 0 : b < 0xe0 ? 1 : b < 0xf0 ? 2 : 3;
+      let c = b & [0x7f, 0x1f, 0x0f, 0x07][trailing];
+      for (let k = 0; k < trailing; k++) c = (c << 6) | (bytes[i++] & 0x3f);
+      text += String.fromCodePoint(c);
+    }
+    write(text);
+  };
+  return [readBytes, writeBytes];
+}
+
+// Input is read and output is written as bytes, in which characters are UTF-8.
+class Io {
+  constructor(read, write) {
+    this.read = read;
+    this.write = write;
+    this.input = new Uint8Array(0);
+    this.off = 0;
+    this.eof = false;
+    this.output = new Uint8Array(4096);
+    this.outputLength = 0;
+  }
+
+  flush() {
+    if (this.outputLength) {
+      this.write(this.output.slice(0, this.outputLength));
+      this.outputLength = 0;
+    }
+  }
+
+  put(b) {
+    if (this.outputLength === this.output.length) this.flush();
+    this.output[this.outputLength++] = b;
+  }
+
+  printDecimal(v) {
+    for (const c of String(v)) this.put(c.charCodeAt(0));
+  }
+
+  printUnicode(v) {
+    if (v < 0n || v > 0x10ffffn) return;
+    // Keeps the bytes of a character in one chunk of the output.
+    if (this.outputLength + 4 > this.output.length) this.flush();
+    encodeUtf8(Number(v), (b) => this.put(b));
+  }
+
+  // Returns the next byte as a `signed char`, or -1 at the end of the input.
+  peek() {
+    if (this.off >= this.input.length) {
+      if (this.eof) return -1;
+      const chunk = this.read();
+      if (chunk == null || chunk.length === 0) {
+        this.eof = true;
+        return -1;
+      }
+      this.input = chunk;
+      this.off = 0;
+    }
+    return (this.input[this.off] << 24) >> 24;
+  }
+
+  consume() {
+    this.off++;
+  }
+
+  // Bytes other than ASCII are negative, so they are skipped before the number.
+  scanDecimal() {
+    let c;
+    for (;;) {
+      c = this.peek();
+      if (c === -1) return -1n;
+      if (c > 32) break;
+      this.consume();
+    }
+    const sign = c === 45;
+    if (sign) {
+      this.consume();
+      c = this.peek();
+    }
+    let v = 0n;
+    while (c >= 48 && c <= 57) {
+      v = v * 10n + BigInt(c - 48);
+      this.consume();
+      c = this.peek();
+    }
+    return wrap(sign ? -v : v);
+  }
+
+  // Decodes without validation, taking the low 6 bits of -1 for bytes past the end of the input.
+  scanUnicode() {
+    const c = this.peek();
+    this.consume();
+    if (c === -1) return -1n;
+    let v = c & 0xff;
+    const trailing = !(v & 0x80) ? 0 : !(v & 0x20) ? 1 : !(v & 0x10) ? 2 : 3;
+    v &= [0x7f, 0x1f, 0x0f, 0x07][trailing];
+    for (let i = 0; i < trailing; i++) {
+      v = (v << 6) | (this.peek() & 0x3f);
+      this.consume();
+    }
+    return BigInt(v);
+  }
+
+  trap(message) {
+    this.flush();
+    throw new Error(message);
+  }
+
+  checked(v, op, r, c) {
+    if (BigInt.asIntN(BITS, v) !== v) this.trap(`overflow in ${op} at line ${r}, column ${c}`);
+    return v;
+  }
+
+  checkZero(b, r, c) {
+    if (b === 0n) this.trap(`division by zero at line ${r}, column ${c}`);
+  }
+}
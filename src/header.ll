@@ -0,0 +1,584 @@
+; `<int>` stands for the integer type of the chosen width. It is not valid IR, so it can not
+; appear in the header for any other reason.
+
+; memory, capacity, front, length. The capacity is zero or a power of two, and values are kept
+; in a ring so that stacks and queues share the same layout.
+%Storage = type { ptr, i64, i64, i64 }
+
+@storage = internal global [28 x %Storage] zeroinitializer
+@ext = internal global %Storage zeroinitializer
+@output = internal global [131072 x i8] zeroinitializer
+@output_len = internal global i64 0
+@input = internal global [131072 x i8] zeroinitializer
+@input_off = internal global i64 0
+@input_len = internal global i64 0
+@input_eof = internal global i1 false
+
+@overflow_message = private constant [38 x i8] c"overflow in %s at line %d, column %d\0A\00"
+@division_by_zero_message = private constant [40 x i8] c"division by zero at line %d, column %d\0A\00"
+@addition = private constant [9 x i8] c"addition\00"
+@subtraction = private constant [12 x i8] c"subtraction\00"
+@multiplication = private constant [15 x i8] c"multiplication\00"
+@division = private constant [9 x i8] c"division\00"
+
+declare ptr @malloc(i64)
+declare void @free(ptr)
+declare i64 @read(i32, ptr, i64)
+declare i64 @write(i32, ptr, i64)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @abort() noreturn
+declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)
+declare { <int>, i1 } @llvm.sadd.with.overflow.<int>(<int>, <int>)
+declare { <int>, i1 } @llvm.ssub.with.overflow.<int>(<int>, <int>)
+declare { <int>, i1 } @llvm.smul.with.overflow.<int>(<int>, <int>)
+
+define internal void @grow(ptr %s) {
+entry:
+  %memory.p = getelementptr %Storage, ptr %s, i32 0, i32 0
+  %capacity.p = getelementptr %Storage, ptr %s, i32 0, i32 1
+  %front.p = getelementptr %Storage, ptr %s, i32 0, i32 2
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %memory = load ptr, ptr %memory.p
+  %capacity = load i64, ptr %capacity.p
+  %front = load i64, ptr %front.p
+  %length = load i64, ptr %length.p
+  %empty = icmp eq i64 %capacity, 0
+  %double = shl i64 %capacity, 1
+  %new.capacity = select i1 %empty, i64 65536, i64 %double
+  %bytes = mul i64 %new.capacity, ptrtoint (ptr getelementptr (<int>, ptr null, i64 1) to i64)
+  %new = call ptr @malloc(i64 %bytes)
+  %mask = sub i64 %capacity, 1
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %copy ]
+  %done = icmp eq i64 %i, %length
+  br i1 %done, label %end, label %copy
+copy:
+  %j.unmasked = add i64 %front, %i
+  %j = and i64 %j.unmasked, %mask
+  %from = getelementptr <int>, ptr %memory, i64 %j
+  %v = load <int>, ptr %from
+  %to = getelementptr <int>, ptr %new, i64 %i
+  store <int> %v, ptr %to
+  %i.next = add i64 %i, 1
+  br label %loop
+end:
+  call void @free(ptr %memory)
+  store ptr %new, ptr %memory.p
+  store i64 %new.capacity, ptr %capacity.p
+  store i64 0, ptr %front.p
+  ret void
+}
+
+; Returns the address of the `i`th value from the front.
+define internal ptr @at(ptr %s, i64 %i) {
+  %memory.p = getelementptr %Storage, ptr %s, i32 0, i32 0
+  %capacity.p = getelementptr %Storage, ptr %s, i32 0, i32 1
+  %front.p = getelementptr %Storage, ptr %s, i32 0, i32 2
+  %memory = load ptr, ptr %memory.p
+  %capacity = load i64, ptr %capacity.p
+  %front = load i64, ptr %front.p
+  %mask = sub i64 %capacity, 1
+  %j.unmasked = add i64 %front, %i
+  %j = and i64 %j.unmasked, %mask
+  %p = getelementptr <int>, ptr %memory, i64 %j
+  ret ptr %p
+}
+
+define internal i64 @size(ptr %s) {
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %length = load i64, ptr %length.p
+  ret i64 %length
+}
+
+define internal void @push_back(ptr %s, <int> %v) {
+entry:
+  %capacity.p = getelementptr %Storage, ptr %s, i32 0, i32 1
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %capacity = load i64, ptr %capacity.p
+  %length = load i64, ptr %length.p
+  %full = icmp eq i64 %length, %capacity
+  br i1 %full, label %grow, label %store
+grow:
+  call void @grow(ptr %s)
+  br label %store
+store:
+  %p = call ptr @at(ptr %s, i64 %length)
+  store <int> %v, ptr %p
+  %new.length = add i64 %length, 1
+  store i64 %new.length, ptr %length.p
+  ret void
+}
+
+define internal void @push_front(ptr %s, <int> %v) {
+entry:
+  %capacity.p = getelementptr %Storage, ptr %s, i32 0, i32 1
+  %front.p = getelementptr %Storage, ptr %s, i32 0, i32 2
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %length = load i64, ptr %length.p
+  %old.capacity = load i64, ptr %capacity.p
+  %full = icmp eq i64 %length, %old.capacity
+  br i1 %full, label %grow, label %store
+grow:
+  call void @grow(ptr %s)
+  br label %store
+store:
+  %capacity = load i64, ptr %capacity.p
+  %front = load i64, ptr %front.p
+  %mask = sub i64 %capacity, 1
+  %front.unmasked = add i64 %front, %mask
+  %new.front = and i64 %front.unmasked, %mask
+  store i64 %new.front, ptr %front.p
+  %p = call ptr @at(ptr %s, i64 0)
+  store <int> %v, ptr %p
+  %new.length = add i64 %length, 1
+  store i64 %new.length, ptr %length.p
+  ret void
+}
+
+define internal <int> @peek_back(ptr %s) {
+  %length = call i64 @size(ptr %s)
+  %i = sub i64 %length, 1
+  %p = call ptr @at(ptr %s, i64 %i)
+  %v = load <int>, ptr %p
+  ret <int> %v
+}
+
+define internal <int> @pop_back(ptr %s) {
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %length = load i64, ptr %length.p
+  %new.length = sub i64 %length, 1
+  %p = call ptr @at(ptr %s, i64 %new.length)
+  %v = load <int>, ptr %p
+  store i64 %new.length, ptr %length.p
+  ret <int> %v
+}
+
+define internal <int> @peek_front(ptr %s) {
+  %p = call ptr @at(ptr %s, i64 0)
+  %v = load <int>, ptr %p
+  ret <int> %v
+}
+
+define internal <int> @pop_front(ptr %s) {
+  %capacity.p = getelementptr %Storage, ptr %s, i32 0, i32 1
+  %front.p = getelementptr %Storage, ptr %s, i32 0, i32 2
+  %length.p = getelementptr %Storage, ptr %s, i32 0, i32 3
+  %p = call ptr @at(ptr %s, i64 0)
+  %v = load <int>, ptr %p
+  %capacity = load i64, ptr %capacity.p
+  %front = load i64, ptr %front.p
+  %length = load i64, ptr %length.p
+  %mask = sub i64 %capacity, 1
+  %front.unmasked = add i64 %front, 1
+  %new.front = and i64 %front.unmasked, %mask
+  %new.length = sub i64 %length, 1
+  store i64 %new.front, ptr %front.p
+  store i64 %new.length, ptr %length.p
+  ret <int> %v
+}
+
+; The ㅎ storage is a channel to the host. Link definitions of the functions below to replace
+; these defaults, which make it behave like a stack. `aheui_ext_pop` and `aheui_ext_peek` are
+; only called when `aheui_ext_size` is positive.
+define weak i32 @aheui_ext_size() {
+  %length = call i64 @size(ptr @ext)
+  %v = trunc i64 %length to i32
+  ret i32 %v
+}
+
+define weak void @aheui_ext_push(<int> %v) {
+  call void @push_back(ptr @ext, <int> %v)
+  ret void
+}
+
+define weak <int> @aheui_ext_pop() {
+  %v = call <int> @pop_back(ptr @ext)
+  ret <int> %v
+}
+
+define weak <int> @aheui_ext_peek() {
+  %v = call <int> @peek_back(ptr @ext)
+  ret <int> %v
+}
+
+define internal void @flush() {
+  %length = load i64, ptr @output_len
+  %written = call i64 @write(i32 1, ptr @output, i64 %length)
+  store i64 0, ptr @output_len
+  ret void
+}
+
+define internal void @write_bytes(ptr %p, i64 %n) {
+entry:
+  %length = load i64, ptr @output_len
+  %end = add i64 %length, %n
+  %full = icmp ugt i64 %end, 131072
+  br i1 %full, label %flush, label %copy
+flush:
+  call void @flush()
+  br label %copy
+copy:
+  %offset = load i64, ptr @output_len
+  %to = getelementptr i8, ptr @output, i64 %offset
+  call void @llvm.memcpy.p0.p0.i64(ptr %to, ptr %p, i64 %n, i1 false)
+  %new.length = add i64 %offset, %n
+  store i64 %new.length, ptr @output_len
+  ret void
+}
+
+define internal void @print_decimal(<int> %v) {
+entry:
+  %buffer = alloca [48 x i8]
+  %sign = icmp slt <int> %v, 0
+  %negated = sub <int> 0, %v
+  %magnitude = select i1 %sign, <int> %negated, <int> %v
+  br label %loop
+loop:
+  %w = phi <int> [ %magnitude, %entry ], [ %quotient, %loop ]
+  %offset = phi i64 [ 48, %entry ], [ %digit.offset, %loop ]
+  %quotient = udiv <int> %w, 10
+  %remainder = urem <int> %w, 10
+  %remainder.byte = trunc <int> %remainder to i8
+  %digit = add i8 %remainder.byte, 48
+  %digit.offset = sub i64 %offset, 1
+  %digit.p = getelementptr i8, ptr %buffer, i64 %digit.offset
+  store i8 %digit, ptr %digit.p
+  %more = icmp ne <int> %quotient, 0
+  br i1 %more, label %loop, label %digits
+digits:
+  br i1 %sign, label %minus, label %done
+minus:
+  %minus.offset = sub i64 %digit.offset, 1
+  %minus.p = getelementptr i8, ptr %buffer, i64 %minus.offset
+  store i8 45, ptr %minus.p
+  br label %done
+done:
+  %start = phi i64 [ %digit.offset, %digits ], [ %minus.offset, %minus ]
+  %start.p = getelementptr i8, ptr %buffer, i64 %start
+  %length = sub i64 48, %start
+  call void @write_bytes(ptr %start.p, i64 %length)
+  ret void
+}
+
+; Sets the `i`th byte of `buffer` to `prefix | ((v >> shift) & mask)`.
+define internal void @put_byte(ptr %buffer, i64 %i, <int> %v, <int> %shift, <int> %mask, <int> %prefix) {
+  %shifted = lshr <int> %v, %shift
+  %masked = and <int> %shifted, %mask
+  %byte.wide = or <int> %masked, %prefix
+  %byte = trunc <int> %byte.wide to i8
+  %p = getelementptr i8, ptr %buffer, i64 %i
+  store i8 %byte, ptr %p
+  ret void
+}
+
+define internal void @print_utf8(<int> %v) {
+entry:
+  %buffer = alloca [4 x i8]
+  %valid = icmp ule <int> %v, 1114111
+  br i1 %valid, label %encode, label %done
+encode:
+  %one = icmp ult <int> %v, 128
+  br i1 %one, label %encode.one, label %not.one
+encode.one:
+  call void @put_byte(ptr %buffer, i64 0, <int> %v, <int> 0, <int> 127, <int> 0)
+  br label %write
+not.one:
+  %two = icmp ult <int> %v, 2048
+  br i1 %two, label %encode.two, label %not.two
+encode.two:
+  call void @put_byte(ptr %buffer, i64 0, <int> %v, <int> 6, <int> 31, <int> 192)
+  call void @put_byte(ptr %buffer, i64 1, <int> %v, <int> 0, <int> 63, <int> 128)
+  br label %write
+not.two:
+  %three = icmp ult <int> %v, 65536
+  br i1 %three, label %encode.three, label %encode.four
+encode.three:
+  call void @put_byte(ptr %buffer, i64 0, <int> %v, <int> 12, <int> 15, <int> 224)
+  call void @put_byte(ptr %buffer, i64 1, <int> %v, <int> 6, <int> 63, <int> 128)
+  call void @put_byte(ptr %buffer, i64 2, <int> %v, <int> 0, <int> 63, <int> 128)
+  br label %write
+encode.four:
+  call void @put_byte(ptr %buffer, i64 0, <int> %v, <int> 18, <int> 7, <int> 240)
+  call void @put_byte(ptr %buffer, i64 1, <int> %v, <int> 12, <int> 63, <int> 128)
+  call void @put_byte(ptr %buffer, i64 2, <int> %v, <int> 6, <int> 63, <int> 128)
+  call void @put_byte(ptr %buffer, i64 3, <int> %v, <int> 0, <int> 63, <int> 128)
+  br label %write
+write:
+  %length = phi i64 [ 1, %encode.one ], [ 2, %encode.two ], [ 3, %encode.three ], [ 4, %encode.four ]
+  call void @write_bytes(ptr %buffer, i64 %length)
+  br label %done
+done:
+  ret void
+}
+
+; Returns the next input byte as a `signed char`, or -1 at the end of the input.
+define internal i16 @peek_byte() {
+entry:
+  %offset = load i64, ptr @input_off
+  %length = load i64, ptr @input_len
+  %available = icmp ult i64 %offset, %length
+  br i1 %available, label %byte, label %refill
+refill:
+  %eof = load i1, ptr @input_eof
+  br i1 %eof, label %end, label %read
+read:
+  %read.length = call i64 @read(i32 0, ptr @input, i64 131072)
+  %filled = icmp sgt i64 %read.length, 0
+  br i1 %filled, label %reset, label %closed
+reset:
+  store i64 %read.length, ptr @input_len
+  store i64 0, ptr @input_off
+  br label %byte
+closed:
+  store i1 true, ptr @input_eof
+  br label %end
+byte:
+  %at = phi i64 [ %offset, %entry ], [ 0, %reset ]
+  %p = getelementptr i8, ptr @input, i64 %at
+  %v = load i8, ptr %p
+  %signed = sext i8 %v to i16
+  ret i16 %signed
+end:
+  ret i16 -1
+}
+
+define internal void @consume() {
+  %offset = load i64, ptr @input_off
+  %next = add i64 %offset, 1
+  store i64 %next, ptr @input_off
+  ret void
+}
+
+define internal <int> @scan_decimal() {
+entry:
+  br label %skip
+skip:
+  %c = call i16 @peek_byte()
+  %eof = icmp eq i16 %c, -1
+  br i1 %eof, label %end, label %check
+check:
+  %visible = icmp sgt i16 %c, 32
+  br i1 %visible, label %start, label %blank
+blank:
+  call void @consume()
+  br label %skip
+end:
+  ret <int> -1
+start:
+  %minus = icmp eq i16 %c, 45
+  br i1 %minus, label %sign, label %digits.entry
+sign:
+  call void @consume()
+  %after.sign = call i16 @peek_byte()
+  br label %digits.entry
+digits.entry:
+  %first = phi i16 [ %c, %start ], [ %after.sign, %sign ]
+  br label %digits
+digits:
+  %d = phi i16 [ %first, %digits.entry ], [ %next, %digit ]
+  %v = phi <int> [ 0, %digits.entry ], [ %v.next, %digit ]
+  %d.low = icmp sge i16 %d, 48
+  %d.high = icmp sle i16 %d, 57
+  %is.digit = and i1 %d.low, %d.high
+  br i1 %is.digit, label %digit, label %finish
+digit:
+  %d.value = sub i16 %d, 48
+  %d.wide = sext i16 %d.value to <int>
+  %v.times = mul <int> %v, 10
+  %v.next = add <int> %v.times, %d.wide
+  call void @consume()
+  %next = call i16 @peek_byte()
+  br label %digits
+finish:
+  %negated = sub <int> 0, %v
+  %result = select i1 %minus, <int> %negated, <int> %v
+  ret <int> %result
+}
+
+; Appends the low 6 bits of the next byte, or of 0xFF at the end of the input, to `v`.
+define internal <int> @continue_utf8(<int> %v) {
+  %c = call i16 @peek_byte()
+  call void @consume()
+  %c.wide = sext i16 %c to <int>
+  %bits = and <int> %c.wide, 63
+  %shifted = shl <int> %v, 6
+  %result = or <int> %shifted, %bits
+  ret <int> %result
+}
+
+define internal <int> @scan_utf8() {
+entry:
+  %c = call i16 @peek_byte()
+  call void @consume()
+  %eof = icmp eq i16 %c, -1
+  br i1 %eof, label %end, label %lead
+end:
+  ret <int> -1
+lead:
+  %byte = trunc i16 %c to i8
+  %v = zext i8 %byte to <int>
+  %ascii = icmp ult <int> %v, 128
+  br i1 %ascii, label %done, label %not.ascii
+not.ascii:
+  %bit5 = and <int> %v, 32
+  %two = icmp eq <int> %bit5, 0
+  br i1 %two, label %decode.two, label %not.two
+decode.two:
+  %v2 = and <int> %v, 31
+  %v2.1 = call <int> @continue_utf8(<int> %v2)
+  br label %done
+not.two:
+  %bit4 = and <int> %v, 16
+  %three = icmp eq <int> %bit4, 0
+  br i1 %three, label %decode.three, label %decode.four
+decode.three:
+  %v3 = and <int> %v, 15
+  %v3.1 = call <int> @continue_utf8(<int> %v3)
+  %v3.2 = call <int> @continue_utf8(<int> %v3.1)
+  br label %done
+decode.four:
+  %v4 = and <int> %v, 7
+  %v4.1 = call <int> @continue_utf8(<int> %v4)
+  %v4.2 = call <int> @continue_utf8(<int> %v4.1)
+  %v4.3 = call <int> @continue_utf8(<int> %v4.2)
+  br label %done
+done:
+  %result = phi <int> [ %v, %lead ], [ %v2.1, %decode.two ], [ %v3.2, %decode.three ], [ %v4.3, %decode.four ]
+  ret <int> %result
+}
+
+define internal <int> @halt_back(ptr %s) {
+entry:
+  call void @flush()
+  %length = call i64 @size(ptr %s)
+  %empty = icmp eq i64 %length, 0
+  br i1 %empty, label %zero, label %pop
+zero:
+  ret <int> 0
+pop:
+  %v = call <int> @pop_back(ptr %s)
+  ret <int> %v
+}
+
+define internal <int> @halt_front(ptr %s) {
+entry:
+  call void @flush()
+  %length = call i64 @size(ptr %s)
+  %empty = icmp eq i64 %length, 0
+  br i1 %empty, label %zero, label %pop
+zero:
+  ret <int> 0
+pop:
+  %v = call <int> @pop_front(ptr %s)
+  ret <int> %v
+}
+
+define internal <int> @halt_ext() {
+entry:
+  call void @flush()
+  %length = call i32 @aheui_ext_size()
+  %empty = icmp eq i32 %length, 0
+  br i1 %empty, label %zero, label %pop
+zero:
+  ret <int> 0
+pop:
+  %v = call <int> @aheui_ext_pop()
+  ret <int> %v
+}
+
+define internal void @overflow(ptr %op, i32 %r, i32 %c) noreturn {
+  call void @flush()
+  %printed = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @overflow_message, ptr %op, i32 %r, i32 %c)
+  call void @abort()
+  unreachable
+}
+
+define internal void @division_by_zero(i32 %r, i32 %c) noreturn {
+  call void @flush()
+  %printed = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @division_by_zero_message, i32 %r, i32 %c)
+  call void @abort()
+  unreachable
+}
+
+define internal void @check_zero(<int> %b, i32 %r, i32 %c) {
+entry:
+  %zero = icmp eq <int> %b, 0
+  br i1 %zero, label %trap, label %ok
+trap:
+  call void @division_by_zero(i32 %r, i32 %c)
+  unreachable
+ok:
+  ret void
+}
+
+define internal <int> @checked_add(<int> %a, <int> %b, i32 %r, i32 %c) {
+entry:
+  %result = call { <int>, i1 } @llvm.sadd.with.overflow.<int>(<int> %a, <int> %b)
+  %overflow = extractvalue { <int>, i1 } %result, 1
+  br i1 %overflow, label %trap, label %ok
+trap:
+  call void @overflow(ptr @addition, i32 %r, i32 %c)
+  unreachable
+ok:
+  %v = extractvalue { <int>, i1 } %result, 0
+  ret <int> %v
+}
+
+define internal <int> @checked_sub(<int> %a, <int> %b, i32 %r, i32 %c) {
+entry:
+  %result = call { <int>, i1 } @llvm.ssub.with.overflow.<int>(<int> %a, <int> %b)
+  %overflow = extractvalue { <int>, i1 } %result, 1
+  br i1 %overflow, label %trap, label %ok
+trap:
+  call void @overflow(ptr @subtraction, i32 %r, i32 %c)
+  unreachable
+ok:
+  %v = extractvalue { <int>, i1 } %result, 0
+  ret <int> %v
+}
+
+define internal <int> @checked_mul(<int> %a, <int> %b, i32 %r, i32 %c) {
+entry:
+  %result = call { <int>, i1 } @llvm.smul.with.overflow.<int>(<int> %a, <int> %b)
+  %overflow = extractvalue { <int>, i1 } %result, 1
+  br i1 %overflow, label %trap, label %ok
+trap:
+  call void @overflow(ptr @multiplication, i32 %r, i32 %c)
+  unreachable
+ok:
+  %v = extractvalue { <int>, i1 } %result, 0
+  ret <int> %v
+}
+
+define internal <int> @checked_div(<int> %a, <int> %b, i32 %r, i32 %c) {
+entry:
+  %negated = call { <int>, i1 } @llvm.ssub.with.overflow.<int>(<int> 0, <int> %a)
+  %minimum = extractvalue { <int>, i1 } %negated, 1
+  %minus.one = icmp eq <int> %b, -1
+  %overflow = and i1 %minimum, %minus.one
+  br i1 %overflow, label %trap, label %ok
+trap:
+  call void @overflow(ptr @division, i32 %r, i32 %c)
+  unreachable
+ok:
+  %v = sdiv <int> %a, %b
+  ret <int> %v
+}
+
+define internal <int> @wrapping_div(<int> %a, <int> %b) {
+  %minus.one = icmp eq <int> %b, -1
+  %divisor = select i1 %minus.one, <int> 1, <int> %b
+  %quotient = sdiv <int> %a, %divisor
+  %negated = sub <int> 0, %a
+  %v = select i1 %minus.one, <int> %negated, <int> %quotient
+  ret <int> %v
+}
+
+define internal <int> @wrapping_rem(<int> %a, <int> %b) {
+  %minus.one = icmp eq <int> %b, -1
+  %divisor = select i1 %minus.one, <int> 1, <int> %b
+  %remainder = srem <int> %a, %divisor
+  %v = select i1 %minus.one, <int> 0, <int> %remainder
+  ret <int> %v
+}
@@ -0,0 +1,123 @@
+import sys
+from collections import deque
+
+# Values are wrapped to `BITS` bits unless it is 0.
+HALF = 1 << BITS >> 1
+MASK = (1 << BITS) - 1
+
+
+def wrap(v):
+    return ((v + HALF) & MASK) - HALF if BITS else v
+
+
+# Exit code of a program halting with `v`, as `int` in C.
+def exit_code(v):
+    return ((v + (1 << 31)) & 0xFFFFFFFF) - (1 << 31)
+
+
+# Division and remainder truncate toward zero, as in C.
+def div(a, b):
+    q = a // b
+    if q < 0 and q * b != a:
+        q += 1
+    return q
+
+
+def rem(a, b):
+    return a - b * div(a, b)
+
+
+class Io:
+    def __init__(self, input, output):
+        self.read = getattr(input, "read1", input.read)
+        self.output = output
+        self.buffer = bytearray()
+        self.input = b""
+        self.off = 0
+        self.eof = False
+
+    def flush(self):
+        self.output.write(self.buffer)
+        self.output.flush()
+        self.buffer.clear()
+
+    def print(self, b):
+        self.buffer += b
+        if len(self.buffer) >= 1 << 16:
+            self.flush()
+
+    def print_decimal(self, v):
+        self.print(str(v).encode())
+
+    # Code points are encoded even if they are surrogates, as in the C backend.
+    def print_utf8(self, v):
+        if 0 <= v <= 0x10FFFF:
+            self.print(chr(v).encode("utf-8", "surrogatepass"))
+
+    # Returns the next input byte as a `signed char`, or -1 at the end of the input.
+    def peek(self):
+        if self.off >= len(self.input):
+            if self.eof:
+                return -1
+            self.input = self.read(1 << 16)
+            self.off = 0
+            if not self.input:
+                self.eof = True
+                return -1
+        c = self.input[self.off]
+        return c - 256 if c >= 128 else c
+
+    def consume(self):
+        self.off += 1
+
+    def scan_decimal(self):
+        while True:
+            c = self.peek()
+            if c == -1:
+                return -1
+            if c > 32:
+                break
+            self.consume()
+        sign = c == 45
+        if sign:
+            self.consume()
+            c = self.peek()
+        v = 0
+        while 48 <= c <= 57:
+            v = v * 10 + c - 48
+            self.consume()
+            c = self.peek()
+        return wrap(-v if sign else v)
+
+    def scan_utf8(self):
+        c = self.peek()
+        self.consume()
+        if c == -1:
+            return -1
+        v = c & 0xFF
+        if v & 0x80 == 0:
+            continuation = 0
+        elif v & 0x20 == 0:
+            v, continuation = v & 0x1F, 1
+        elif v & 0x10 == 0:
+            v, continuation = v & 0x0F, 2
+        else:
+            v, continuation = v & 0x07, 3
+        for _ in range(continuation):
+            v = v << 6 | self.peek() & 0x3F
+            self.consume()
+        return v
+
+    # Exits with status 1 after printing `message` to the standard error.
+    def trap(self, message):
+        self.flush()
+        sys.exit(message)
+
+    def checked(self, v, op, r, c):
+        if wrap(v) != v:
+            self.trap(f"overflow in {op} at line {r}, column {c}")
+        return v
+
+    def check_zero(self, b, r, c):
+        if b == 0:
+            self.trap(f"division by zero at line {r}, column {c}")
@@ -0,0 +1,175 @@
+// A divisor may be a constant zero where division by zero is undefined, or in code that never
+// runs.
+#![allow(unused, unconditional_panic)]
+
+use std::collections::VecDeque;
+use std::io::{self, BufWriter, Read, Write};
+
+// `Integer` is defined after this file, as inner attributes have to come first.
+
+fn main() {
+    std::process::exit(run(io::stdin().lock(), io::stdout().lock()));
+}
+
+struct Io<R: Read, W: Write> {
+    input: R,
+    output: BufWriter<W>,
+    buffer: Box<[u8]>,
+    off: usize,
+    len: usize,
+    eof: bool,
+}
+
+impl<R: Read, W: Write> Io<R, W> {
+    fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output: BufWriter::with_capacity(1 << 16, output),
+            buffer: vec![0; 1 << 16].into_boxed_slice(),
+            off: 0,
+            len: 0,
+            eof: false,
+        }
+    }
+
+    fn flush(&mut self) {
+        self.output.flush().ok();
+    }
+
+    fn print_decimal(&mut self, v: Integer) {
+        write!(self.output, "{v}").ok();
+    }
+
+    // Code points are encoded even if they are surrogates, as in the C backend.
+    fn print_utf8(&mut self, v: Integer) {
+        if !(0..=0x10ffff).contains(&v) {
+            return;
+        }
+        let v = v as u32;
+        let continuation = |shift: u32| 0x80 | (v >> shift & 0x3f) as u8;
+        let result = match v {
+            0..=0x7f => self.output.write_all(&[v as u8]),
+            0x80..=0x7ff => self.output.write_all(&[0xc0 | (v >> 6) as u8, continuation(0)]),
+            0x800..=0xffff => self.output.write_all(&[
+                0xe0 | (v >> 12) as u8,
+                continuation(6),
+                continuation(0),
+            ]),
+            _ => self.output.write_all(&[
+                0xf0 | (v >> 18) as u8,
+                continuation(12),
+                continuation(6),
+                continuation(0),
+            ]),
+        };
+        result.ok();
+    }
+
+    // Returns the next input byte as a `signed char`, or -1 at the end of the input.
+    fn peek(&mut self) -> Integer {
+        if self.off >= self.len {
+            if self.eof {
+                return -1;
+            }
+            self.len = self.input.read(&mut self.buffer).unwrap_or(0);
+            self.off = 0;
+            if self.len == 0 {
+                self.eof = true;
+                return -1;
+            }
+        }
+        self.buffer[self.off] as i8 as Integer
+    }
+
+    fn consume(&mut self) {
+        self.off += 1;
+    }
+
+    fn scan_decimal(&mut self) -> Integer {
+        let mut c = loop {
+            let c = self.peek();
+            if c == -1 {
+                return -1;
+            }
+            if c > b' ' as Integer {
+                break c;
+            }
+            self.consume();
+        };
+        let sign = c == b'-' as Integer;
+        if sign {
+            self.consume();
+            c = self.peek();
+        }
+        let mut v: Integer = 0;
+        while (b'0' as Integer..=b'9' as Integer).contains(&c) {
+            v = v.wrapping_mul(10).wrapping_add(c - b'0' as Integer);
+            self.consume();
+            c = self.peek();
+        }
+        if sign {
+            v.wrapping_neg()
+        } else {
+            v
+        }
+    }
+
+    fn scan_utf8(&mut self) -> Integer {
+        let c = self.peek();
+        self.consume();
+        if c == -1 {
+            return -1;
+        }
+        let v = c & 0xff;
+        let (mut v, continuation) = if v & 0x80 == 0 {
+            (v, 0)
+        } else if v & 0x20 == 0 {
+            (v & 0x1f, 1)
+        } else if v & 0x10 == 0 {
+            (v & 0x0f, 2)
+        } else {
+            (v & 0x07, 3)
+        };
+        for _ in 0..continuation {
+            v = v << 6 | self.peek() & 0x3f;
+            self.consume();
+        }
+        v
+    }
+
+    fn overflow(&mut self, op: &str, r: u32, c: u32) -> ! {
+        self.flush();
+        eprintln!("overflow in {op} at line {r}, column {c}");
+        std::process::abort()
+    }
+
+    // Returns the divisor, which is not zero.
+    fn check_zero(&mut self, b: Integer, r: u32, c: u32) -> Integer {
+        if b == 0 {
+            self.flush();
+            eprintln!("division by zero at line {r}, column {c}");
+            std::process::abort()
+        }
+        b
+    }
+
+    fn checked_add(&mut self, a: Integer, b: Integer, r: u32, c: u32) -> Integer {
+        a.checked_add(b).unwrap_or_else(|| self.overflow("addition", r, c))
+    }
+
+    fn checked_sub(&mut self, a: Integer, b: Integer, r: u32, c: u32) -> Integer {
+        a.checked_sub(b).unwrap_or_else(|| self.overflow("subtraction", r, c))
+    }
+
+    fn checked_mul(&mut self, a: Integer, b: Integer, r: u32, c: u32) -> Integer {
+        a.checked_mul(b).unwrap_or_else(|| self.overflow("multiplication", r, c))
+    }
+
+    // Division by zero is left to `/`, as only `MIN / -1` overflows.
+    fn checked_div(&mut self, a: Integer, b: Integer, r: u32, c: u32) -> Integer {
+        if a == Integer::MIN && b == -1 {
+            self.overflow("division", r, c)
+        }
+        a / b
+    }
+}
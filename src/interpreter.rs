@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use crate::ir::BinaryOp;
+use crate::{
+    parse, Consonant, Direction, Error, Extension, Integer, Options, Overflow, Program, State,
+    StorageKind, ZeroDivision, STREAM,
+};
+
+/// Runs aheui `code` directly, reading from `input` and writing to `output`.
+///
+/// Behaves like the C code generated by [`crate::transpile`], and returns the value the program
+/// halts with, which the generated code uses as its exit code. Unlike the generated code, division
+/// by zero stops the program with [`Error::DivisionByZero`] unless [`interpret_with`] is told
+/// otherwise.
+pub fn interpret(code: &str, input: impl Read, output: impl Write) -> Result<Integer, Error> {
+    interpret_with(code, input, output, &Options::default())
+}
+
+/// Runs aheui `code` like [`interpret`], behaving like the code generated with `options`.
+pub fn interpret_with(
+    code: &str,
+    input: impl Read,
+    output: impl Write,
+    options: &Options,
+) -> Result<Integer, Error> {
+    interpret_with_extension(code, input, output, options, &mut Vec::new())
+}
+
+/// Runs aheui `code` like [`interpret_with`], forwarding the ㅎ storage to `extension`.
+pub fn interpret_with_extension(
+    code: &str,
+    input: impl Read,
+    output: impl Write,
+    options: &Options,
+    extension: &mut dyn Extension,
+) -> Result<Integer, Error> {
+    let width = options.width;
+    let program = parse(code);
+    program.validate()?;
+    let Program {
+        field,
+        syllables: code,
+    } = program;
+    let mut input = Input {
+        reader: BufReader::new(input),
+    };
+    let mut output = BufWriter::new(output);
+    let mut storage = storages(extension);
+    let mut state = State {
+        r: 0,
+        c: 0,
+        direction: Direction::Down,
+        speed: 1,
+        storage: 0,
+    };
+    loop {
+        let pos = state.r * field.w + state.c;
+        if let Some(vowel) = &code[pos].vowel {
+            state.turn(vowel);
+        }
+        if let Some(consonant) = code[pos].consonant {
+            let required = consonant.required();
+            if storage[state.storage].len() < required {
+                state = state.reverse_next(&field, options.wrap);
+                continue;
+            }
+            let current = &mut storage[state.storage];
+            match consonant {
+                Consonant::Halt => {
+                    output.flush()?;
+                    return Ok(current.pop().unwrap_or(0));
+                }
+                Consonant::Add
+                | Consonant::Subtract
+                | Consonant::Multiply
+                | Consonant::Divide
+                | Consonant::Remainder
+                | Consonant::Compare => {
+                    let op = match consonant {
+                        Consonant::Add => BinaryOp::Add,
+                        Consonant::Subtract => BinaryOp::Subtract,
+                        Consonant::Multiply => BinaryOp::Multiply,
+                        Consonant::Divide => BinaryOp::Divide,
+                        Consonant::Remainder => BinaryOp::Remainder,
+                        _ => BinaryOp::Compare,
+                    };
+                    let divides = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+                    if divides && current.peek() == 0 {
+                        match options.zero_division {
+                            ZeroDivision::Undefined | ZeroDivision::Trap => {
+                                output.flush()?;
+                                return Err(Error::DivisionByZero {
+                                    r: state.r,
+                                    c: state.c,
+                                });
+                            }
+                            ZeroDivision::Zero => {}
+                            ZeroDivision::Reflect => {
+                                state = state.reverse_next(&field, options.wrap);
+                                continue;
+                            }
+                        }
+                    }
+                    let (a, b) = current.pop_two();
+                    if divides && a == 0 {
+                        current.push(0);
+                    } else if options.overflow == Overflow::Trap {
+                        match op.checked(b, a).filter(|&v| width.wrap(v) == v) {
+                            Some(v) => current.push(v),
+                            None => {
+                                output.flush()?;
+                                return Err(Error::Overflow {
+                                    op,
+                                    r: state.r,
+                                    c: state.c,
+                                });
+                            }
+                        }
+                    } else {
+                        current.push(width.wrap(op.wrapping(b, a)));
+                    }
+                }
+                Consonant::PrintDecimal => {
+                    let a = current.pop().unwrap();
+                    write!(output, "{a}")?;
+                }
+                Consonant::PrintUnicode => {
+                    let a = current.pop().unwrap();
+                    print_utf8(&mut output, a)?;
+                }
+                Consonant::Pop => {
+                    current.pop();
+                }
+                Consonant::ScanDecimal => current.push(width.wrap(input.scan_decimal()?)),
+                Consonant::ScanUnicode => current.push(input.scan_utf8()?),
+                Consonant::Push(v) => current.push(v),
+                Consonant::Duplicate => current.duplicate(),
+                Consonant::Exchange => current.exchange(),
+                Consonant::Select(s) => state.storage = s as usize,
+                Consonant::Move(s) => {
+                    let a = current.pop().unwrap();
+                    storage[s as usize].push(a);
+                }
+                Consonant::Branch => {
+                    if current.pop() == Some(0) {
+                        state = state.reverse_next(&field, options.wrap);
+                        continue;
+                    }
+                }
+            }
+        }
+        (state.r, state.c) = field.next_pos(&state, options.wrap);
+    }
+}
+
+pub(crate) struct Storage<'a> {
+    memory: VecDeque<Integer>,
+    kind: StorageKind,
+    // Set for the ㅎ storage, which forwards everything to the host.
+    extension: Option<&'a mut dyn Extension>,
+}
+
+// Creates the 28 storages of a program, forwarding the ㅎ storage to `extension`.
+pub(crate) fn storages(extension: &mut dyn Extension) -> Vec<Storage<'_>> {
+    let mut storage: Vec<_> = (0..28).map(Storage::new).collect();
+    storage[STREAM].extension = Some(extension);
+    storage
+}
+
+impl Storage<'_> {
+    pub(crate) fn new(index: usize) -> Self {
+        Self {
+            memory: VecDeque::new(),
+            kind: StorageKind::from(index),
+            extension: None,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match &self.extension {
+            Some(extension) => extension.len(),
+            None => self.memory.len(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, v: Integer) {
+        match &mut self.extension {
+            Some(extension) => extension.push(v),
+            None => self.memory.push_back(v),
+        }
+    }
+
+    // Only used on the queue, which has no extension.
+    pub(crate) fn push_front(&mut self, v: Integer) {
+        self.memory.push_front(v);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Integer> {
+        match &mut self.extension {
+            Some(extension) if extension.is_empty() => None,
+            Some(extension) => Some(extension.pop()),
+            None if self.kind == StorageKind::Queue => self.memory.pop_front(),
+            None => self.memory.pop_back(),
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Integer {
+        match &self.extension {
+            Some(extension) => extension.peek(),
+            None if self.kind == StorageKind::Queue => self.memory[0],
+            None => self.memory[self.len() - 1],
+        }
+    }
+
+    fn pop_two(&mut self) -> (Integer, Integer) {
+        let a = self.pop().unwrap();
+        let b = self.pop().unwrap();
+        (a, b)
+    }
+
+    fn duplicate(&mut self) {
+        if self.extension.is_some() {
+            let a = self.pop().unwrap();
+            self.push(a);
+            self.push(a);
+        } else if self.kind == StorageKind::Queue {
+            self.memory.push_front(self.memory[0]);
+        } else {
+            self.memory.push_back(self.memory[self.len() - 1]);
+        }
+    }
+
+    fn exchange(&mut self) {
+        if self.extension.is_some() {
+            let (a, b) = self.pop_two();
+            self.push(a);
+            self.push(b);
+        } else if self.kind == StorageKind::Queue {
+            self.memory.swap(0, 1);
+        } else {
+            let len = self.len();
+            self.memory.swap(len - 1, len - 2);
+        }
+    }
+}
+
+pub(crate) fn print_utf8(output: &mut impl Write, codepoint: Integer) -> io::Result<()> {
+    if !(0..=0x10ffff).contains(&codepoint) {
+        return Ok(());
+    }
+    // Surrogates are encoded as-is, so `char` can not be used here.
+    let c = codepoint as u32;
+    if c < 0x80 {
+        output.write_all(&[c as u8])
+    } else if c < 0x800 {
+        output.write_all(&[0xC0 | (c >> 6) as u8, 0x80 | (c & 0x3F) as u8])
+    } else if c < 0x10000 {
+        output.write_all(&[
+            0xE0 | (c >> 12) as u8,
+            0x80 | ((c >> 6) & 0x3F) as u8,
+            0x80 | (c & 0x3F) as u8,
+        ])
+    } else {
+        output.write_all(&[
+            0xF0 | (c >> 18) as u8,
+            0x80 | ((c >> 12) & 0x3F) as u8,
+            0x80 | ((c >> 6) & 0x3F) as u8,
+            0x80 | (c & 0x3F) as u8,
+        ])
+    }
+}
+
+pub(crate) struct Input<R> {
+    pub(crate) reader: BufReader<R>,
+}
+
+impl<R: Read> Input<R> {
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self) {
+        if !self.reader.buffer().is_empty() {
+            self.reader.consume(1);
+        }
+    }
+
+    pub(crate) fn scan_decimal(&mut self) -> io::Result<Integer> {
+        let mut c = loop {
+            match self.peek()? {
+                None => return Ok(-1),
+                // Bytes are compared as `signed char`, so non-ASCII bytes are skipped as well.
+                Some(c) if c as i8 > b' ' as i8 => break Some(c),
+                Some(_) => self.consume(),
+            }
+        };
+        let sign = c == Some(b'-');
+        if sign {
+            self.consume();
+            c = self.peek()?;
+        }
+        let mut v: Integer = 0;
+        while let Some(d @ b'0'..=b'9') = c {
+            v = v.wrapping_mul(10).wrapping_add((d - b'0') as Integer);
+            self.consume();
+            c = self.peek()?;
+        }
+        Ok(if sign { v.wrapping_neg() } else { v })
+    }
+
+    pub(crate) fn scan_utf8(&mut self) -> io::Result<Integer> {
+        let c = self.peek()?;
+        self.consume();
+        let Some(c) = c else {
+            return Ok(-1);
+        };
+        let mut v = c as Integer;
+        let trailing = if v & 0x80 == 0 {
+            0
+        } else if v & 0x20 == 0 {
+            v &= 0x1F;
+            1
+        } else if v & 0x10 == 0 {
+            v &= 0x0F;
+            2
+        } else {
+            v &= 0x07;
+            3
+        };
+        for _ in 0..trailing {
+            v <<= 6;
+            v |= (self.peek()?.unwrap_or(0xFF) & 0x3F) as Integer;
+            self.consume();
+        }
+        Ok(v)
+    }
+}
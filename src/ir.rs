@@ -0,0 +1,156 @@
+//! Intermediate representation of linearized aheui code.
+//!
+//! A program is a list of [`Block`]s whose index is their [`Label`], starting from the entry
+//! block `0`. Values popped or computed inside a block live in virtual [`Register`]s, which are
+//! numbered from `0` in every block and assigned exactly once.
+
+use std::fmt;
+
+use crate::Integer;
+
+/// Virtual register holding a value inside a block.
+pub type Register = usize;
+
+/// Index of a block in the linearized program.
+pub type Label = usize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    /// `1` if the left operand is greater than or equal to the right one, `0` otherwise.
+    Compare,
+}
+
+impl BinaryOp {
+    /// Evaluates the operation in two's complement. Panics on division by zero.
+    pub fn wrapping(self, lhs: Integer, rhs: Integer) -> Integer {
+        match self {
+            Self::Add => lhs.wrapping_add(rhs),
+            Self::Subtract => lhs.wrapping_sub(rhs),
+            Self::Multiply => lhs.wrapping_mul(rhs),
+            Self::Divide => lhs.wrapping_div(rhs),
+            Self::Remainder => lhs.wrapping_rem(rhs),
+            Self::Compare => (lhs >= rhs) as Integer,
+        }
+    }
+
+    /// Evaluates the operation, or returns `None` if the result overflows or the divisor is zero.
+    pub fn checked(self, lhs: Integer, rhs: Integer) -> Option<Integer> {
+        match self {
+            Self::Add => lhs.checked_add(rhs),
+            Self::Subtract => lhs.checked_sub(rhs),
+            Self::Multiply => lhs.checked_mul(rhs),
+            Self::Divide => lhs.checked_div(rhs),
+            // The remainder of `MIN / -1` is `0`, even though the quotient overflows.
+            Self::Remainder if rhs == -1 => Some(0),
+            Self::Remainder => lhs.checked_rem(rhs),
+            Self::Compare => Some((lhs >= rhs) as Integer),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Add => "addition",
+            Self::Subtract => "subtraction",
+            Self::Multiply => "multiplication",
+            Self::Divide => "division",
+            Self::Remainder => "remainder",
+            Self::Compare => "comparison",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Instruction {
+    /// Pops a value from `storage`, which is known to have one.
+    Pop {
+        dst: Register,
+        storage: usize,
+    },
+    /// Pushes `src` to `storage`.
+    Push {
+        storage: usize,
+        src: Register,
+    },
+    /// Puts `src` back to the front of `storage`, which is the queue. This restores values the
+    /// block took from the front, or duplicated there, but did not use.
+    PushFront {
+        storage: usize,
+        src: Register,
+    },
+    Const {
+        dst: Register,
+        value: Integer,
+    },
+    Binary {
+        dst: Register,
+        op: BinaryOp,
+        lhs: Register,
+        rhs: Register,
+        /// 0-based row and column of the consonant in the grid.
+        cell: (usize, usize),
+    },
+    PrintDecimal(Register),
+    PrintUnicode(Register),
+    ScanDecimal(Register),
+    ScanUnicode(Register),
+}
+
+impl Instruction {
+    /// Returns the register the instruction assigns, if any.
+    pub fn defined(&self) -> Option<Register> {
+        match *self {
+            Self::Pop { dst, .. }
+            | Self::Const { dst, .. }
+            | Self::Binary { dst, .. }
+            | Self::ScanDecimal(dst)
+            | Self::ScanUnicode(dst) => Some(dst),
+            Self::Push { .. }
+            | Self::PushFront { .. }
+            | Self::PrintDecimal(..)
+            | Self::PrintUnicode(..) => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Terminator {
+    Goto(Label),
+    /// Jumps to `less` if `storage` has less than `size` values, and to `otherwise` if not.
+    Jsl {
+        storage: usize,
+        size: usize,
+        less: Label,
+        otherwise: Label,
+    },
+    /// Pops a value from `storage`, which is known to have one, and jumps to `nonzero` or `zero`.
+    Jnz {
+        storage: usize,
+        nonzero: Label,
+        zero: Label,
+    },
+    /// Jumps to `zero` if the next value of `storage`, which is known to exist, is zero, and to
+    /// `otherwise` if not. The value is left on the storage.
+    Jz {
+        storage: usize,
+        zero: Label,
+        otherwise: Label,
+    },
+    /// Exits with a value popped from `storage`, or `0` if it is empty.
+    Halt {
+        storage: usize,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
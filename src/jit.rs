@@ -0,0 +1,553 @@
+//! Just-in-time compilation of aheui code to native code with Cranelift.
+//!
+//! Every block becomes a block of one native function, with its registers kept in machine
+//! registers. The storages and input and output stay on the Rust side and are reached through
+//! calls, so the program behaves exactly like [`crate::interpret_with`].
+
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block as NativeBlock, FuncRef, InstBuilder, StackSlotData, StackSlotKind,
+    Type, UserFuncName, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+use crate::interpreter::{print_utf8, storages, Input, Storage};
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{
+    linearize_with, parse, Error, Extension, Integer, Options, Overflow, Width, ZeroDivision,
+};
+
+/// Compiles aheui `code` to native code and runs it, reading from `input` and writing to
+/// `output`.
+///
+/// Behaves like [`crate::interpret_with`] with the same `options`, including the errors for
+/// division by zero and overflow, and returns the value the program halts with. Big integers and
+/// 128-bit integers are not supported. The ㅎ storage is a plain stack.
+pub fn run(
+    code: &str,
+    input: impl Read,
+    output: impl Write,
+    options: &Options,
+) -> Result<Integer, Error> {
+    run_with_extension(code, input, output, options, &mut Vec::new())
+}
+
+/// Runs aheui `code` like [`run`], forwarding the ㅎ storage to `extension` like
+/// [`crate::interpret_with_extension`].
+pub fn run_with_extension(
+    code: &str,
+    mut input: impl Read,
+    mut output: impl Write,
+    options: &Options,
+    extension: &mut dyn Extension,
+) -> Result<Integer, Error> {
+    let option = if options.bigint {
+        Some("bigint")
+    } else if options.width == Width::W128 {
+        Some("128-bit integers")
+    } else {
+        None
+    };
+    if let Some(option) = option {
+        return Err(Error::Unsupported {
+            option,
+            backend: "JIT",
+        });
+    }
+    let blocks = linearize_with(&parse(code), options)?;
+    let isa = cranelift_native::builder().map_err(|_| Error::Unsupported {
+        option: "this host",
+        backend: "JIT",
+    })?;
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").unwrap();
+    let isa = isa.finish(settings::Flags::new(flags)).unwrap();
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    for (name, _, _, address) in callbacks() {
+        builder.symbol(name, address);
+    }
+    let mut module = JITModule::new(builder);
+
+    let mut traps = vec![];
+    let mut context = module.make_context();
+    let pointer = module.target_config().pointer_type();
+    context.func.signature.params.push(AbiParam::new(pointer));
+    let id = module
+        .declare_function("main", Linkage::Export, &context.func.signature)
+        .unwrap();
+    context.func.name = UserFuncName::user(0, id.as_u32());
+    let mut function_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let callbacks = callbacks().map(|(name, params, returns, _)| {
+        let mut signature = module.make_signature();
+        signature.params.push(AbiParam::new(pointer));
+        signature
+            .params
+            .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        signature
+            .returns
+            .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        let id = module
+            .declare_function(name, Linkage::Import, &signature)
+            .unwrap();
+        module.declare_func_in_func(id, builder.func)
+    });
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let runtime = builder.block_params(entry)[0];
+    let compiler = Compiler {
+        labels: blocks.iter().map(|_| builder.create_block()).collect(),
+        exit: builder.create_block(),
+        builder,
+        options,
+        ty: if options.width == Width::W32 {
+            types::I32
+        } else {
+            types::I64
+        },
+        pointer,
+        runtime,
+        callbacks,
+        traps: &mut traps,
+        values: HashMap::new(),
+    };
+    compiler.compile(&blocks);
+    module.define_function(id, &mut context).unwrap();
+    module.clear_context(&mut context);
+    module.finalize_definitions().unwrap();
+
+    let mut runtime = Runtime {
+        storage: storages(extension),
+        input: Input {
+            reader: BufReader::new(&mut input),
+        },
+        output: BufWriter::new(&mut output),
+        traps,
+        result: None,
+    };
+    // SAFETY: The function was compiled with the signature of `main`, and only calls back into
+    // the functions of `callbacks` with the runtime it is given.
+    let main: extern "C" fn(&mut Runtime) =
+        unsafe { mem::transmute(module.get_finalized_function(id)) };
+    main(&mut runtime);
+    // SAFETY: The compiled code is not used anymore.
+    unsafe { module.free_memory() };
+    runtime.result.expect("program stopped without a result")
+}
+
+struct Runtime<'a> {
+    storage: Vec<Storage<'a>>,
+    input: Input<&'a mut dyn Read>,
+    output: BufWriter<&'a mut dyn Write>,
+    /// Errors of the checks in the compiled code, by their index.
+    traps: Vec<Error>,
+    /// Set before the compiled code returns.
+    result: Option<Result<Integer, Error>>,
+}
+
+impl Runtime<'_> {
+    // Stops the program with `error`, after flushing the output.
+    fn fail(&mut self, error: Error) {
+        let flushed = self.output.flush();
+        self.result = Some(Err(flushed.map_or_else(Error::from, |_| error)));
+    }
+}
+
+// Function the compiled code calls, with its name, the types of its arguments after the
+// runtime, the types of its results, and its address.
+type Callback = (&'static str, &'static [Type], &'static [Type], *const u8);
+
+// Functions returning `I8` report whether the program has to stop.
+fn callbacks() -> [Callback; 11] {
+    [
+        ("push", &[types::I64, types::I64], &[], push as *const u8),
+        ("pop", &[types::I64], &[types::I64], pop as *const u8),
+        ("peek", &[types::I64], &[types::I64], peek as *const u8),
+        ("len", &[types::I64], &[types::I64], len as *const u8),
+        (
+            "print_decimal",
+            &[types::I64],
+            &[types::I8],
+            print_decimal as *const u8,
+        ),
+        (
+            "print_unicode",
+            &[types::I64],
+            &[types::I8],
+            print_unicode as *const u8,
+        ),
+        (
+            "scan_decimal",
+            &[types::I64],
+            &[types::I8],
+            scan_decimal as *const u8,
+        ),
+        (
+            "scan_unicode",
+            &[types::I64],
+            &[types::I8],
+            scan_unicode as *const u8,
+        ),
+        ("halt", &[types::I64], &[], halt as *const u8),
+        ("trap", &[types::I64], &[], trap as *const u8),
+        (
+            "push_front",
+            &[types::I64, types::I64],
+            &[],
+            push_front as *const u8,
+        ),
+    ]
+}
+
+// Indices into `callbacks`.
+const PUSH: usize = 0;
+const POP: usize = 1;
+const PEEK: usize = 2;
+const LEN: usize = 3;
+const PRINT_DECIMAL: usize = 4;
+const PRINT_UNICODE: usize = 5;
+const SCAN_DECIMAL: usize = 6;
+const SCAN_UNICODE: usize = 7;
+const HALT: usize = 8;
+const TRAP: usize = 9;
+const PUSH_FRONT: usize = 10;
+
+extern "C" fn push(runtime: &mut Runtime, storage: usize, v: i64) {
+    runtime.storage[storage].push(v as Integer);
+}
+
+extern "C" fn push_front(runtime: &mut Runtime, storage: usize, v: i64) {
+    runtime.storage[storage].push_front(v as Integer);
+}
+
+extern "C" fn pop(runtime: &mut Runtime, storage: usize) -> i64 {
+    runtime.storage[storage].pop().unwrap() as i64
+}
+
+extern "C" fn peek(runtime: &mut Runtime, storage: usize) -> i64 {
+    runtime.storage[storage].peek() as i64
+}
+
+extern "C" fn len(runtime: &mut Runtime, storage: usize) -> usize {
+    runtime.storage[storage].len()
+}
+
+extern "C" fn print_decimal(runtime: &mut Runtime, v: i64) -> bool {
+    let result = write!(runtime.output, "{v}");
+    result.map_err(|e| runtime.fail(e.into())).is_err()
+}
+
+extern "C" fn print_unicode(runtime: &mut Runtime, v: i64) -> bool {
+    let result = print_utf8(&mut runtime.output, v as Integer);
+    result.map_err(|e| runtime.fail(e.into())).is_err()
+}
+
+extern "C" fn scan_decimal(runtime: &mut Runtime, v: &mut i64) -> bool {
+    match runtime.input.scan_decimal() {
+        Ok(value) => {
+            *v = value as i64;
+            false
+        }
+        Err(e) => {
+            runtime.fail(e.into());
+            true
+        }
+    }
+}
+
+extern "C" fn scan_unicode(runtime: &mut Runtime, v: &mut i64) -> bool {
+    match runtime.input.scan_utf8() {
+        Ok(value) => {
+            *v = value as i64;
+            false
+        }
+        Err(e) => {
+            runtime.fail(e.into());
+            true
+        }
+    }
+}
+
+extern "C" fn halt(runtime: &mut Runtime, storage: usize) {
+    let v = runtime.storage[storage].pop().unwrap_or(0);
+    runtime.result = Some(runtime.output.flush().map(|_| v).map_err(Error::from));
+}
+
+extern "C" fn trap(runtime: &mut Runtime, index: usize) {
+    // The program stops here, so the order of the other errors does not matter.
+    let error = runtime.traps.swap_remove(index);
+    runtime.fail(error);
+}
+
+struct Compiler<'a> {
+    builder: FunctionBuilder<'a>,
+    options: &'a Options,
+    /// Type of the values, which are wrapped to the width of the options.
+    ty: Type,
+    pointer: Type,
+    runtime: Value,
+    callbacks: [FuncRef; 11],
+    traps: &'a mut Vec<Error>,
+    labels: Vec<NativeBlock>,
+    /// Returns to the runtime, whose result is already set.
+    exit: NativeBlock,
+    values: HashMap<Register, Value>,
+}
+
+impl Compiler<'_> {
+    fn compile(mut self, blocks: &[Block]) {
+        self.builder.ins().jump(self.labels[0], &[]);
+        for (label, block) in blocks.iter().enumerate() {
+            self.builder.switch_to_block(self.labels[label]);
+            self.values.clear();
+            for instruction in &block.instructions {
+                self.instruction(instruction);
+            }
+            self.terminator(&block.terminator);
+        }
+        self.builder.switch_to_block(self.exit);
+        self.builder.ins().return_(&[]);
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                let v = self.call(POP, &[storage as i64]).unwrap();
+                let v = self.narrow(v);
+                self.values.insert(dst, v);
+            }
+            Instruction::Push { storage, src } => {
+                let v = self.widen(self.values[&src]);
+                let storage = self.builder.ins().iconst(self.pointer, storage as i64);
+                self.call_values(PUSH, &[storage, v]);
+            }
+            Instruction::PushFront { storage, src } => {
+                let v = self.widen(self.values[&src]);
+                let storage = self.builder.ins().iconst(self.pointer, storage as i64);
+                self.call_values(PUSH_FRONT, &[storage, v]);
+            }
+            Instruction::Const { dst, value } => {
+                let value = self.options.width.wrap(value) as i64;
+                let v = self.builder.ins().iconst(self.ty, value);
+                self.values.insert(dst, v);
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                let v = self.binary(op, self.values[&lhs], self.values[&rhs], cell);
+                self.values.insert(dst, v);
+            }
+            Instruction::PrintDecimal(src) | Instruction::PrintUnicode(src) => {
+                let callback = if matches!(instruction, Instruction::PrintDecimal(..)) {
+                    PRINT_DECIMAL
+                } else {
+                    PRINT_UNICODE
+                };
+                let v = self.widen(self.values[&src]);
+                let stop = self.call_values(callback, &[v]).unwrap();
+                self.exit_if(stop);
+            }
+            Instruction::ScanDecimal(dst) | Instruction::ScanUnicode(dst) => {
+                let callback = if matches!(instruction, Instruction::ScanDecimal(..)) {
+                    SCAN_DECIMAL
+                } else {
+                    SCAN_UNICODE
+                };
+                let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+                    StackSlotKind::ExplicitSlot,
+                    8,
+                    3,
+                ));
+                let address = self.builder.ins().stack_addr(self.pointer, slot, 0);
+                let stop = self.call_values(callback, &[address]).unwrap();
+                self.exit_if(stop);
+                let v = self.builder.ins().stack_load(types::I64, slot, 0);
+                let v = self.narrow(v);
+                self.values.insert(dst, v);
+            }
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        match *terminator {
+            Terminator::Goto(target) => {
+                self.builder.ins().jump(self.labels[target], &[]);
+            }
+            Terminator::Jsl {
+                storage,
+                size,
+                less,
+                otherwise,
+            } => {
+                let len = self.call(LEN, &[storage as i64]).unwrap();
+                let less_than =
+                    self.builder
+                        .ins()
+                        .icmp_imm(IntCC::UnsignedLessThan, len, size as i64);
+                self.builder.ins().brif(
+                    less_than,
+                    self.labels[less],
+                    &[],
+                    self.labels[otherwise],
+                    &[],
+                );
+            }
+            Terminator::Jnz {
+                storage,
+                nonzero,
+                zero,
+            } => {
+                let v = self.call(POP, &[storage as i64]).unwrap();
+                let v = self.narrow(v);
+                self.builder
+                    .ins()
+                    .brif(v, self.labels[nonzero], &[], self.labels[zero], &[]);
+            }
+            Terminator::Jz {
+                storage,
+                zero,
+                otherwise,
+            } => {
+                let v = self.call(PEEK, &[storage as i64]).unwrap();
+                let v = self.narrow(v);
+                self.builder
+                    .ins()
+                    .brif(v, self.labels[otherwise], &[], self.labels[zero], &[]);
+            }
+            Terminator::Halt { storage } => {
+                self.call(HALT, &[storage as i64]);
+                self.builder.ins().jump(self.exit, &[]);
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: Value, rhs: Value, (r, c): (usize, usize)) -> Value {
+        let trap = self.options.overflow == Overflow::Trap;
+        let ins = self.builder.ins();
+        let (v, overflow) = match op {
+            BinaryOp::Add if trap => ins.sadd_overflow(lhs, rhs),
+            BinaryOp::Subtract if trap => ins.ssub_overflow(lhs, rhs),
+            BinaryOp::Multiply if trap => ins.smul_overflow(lhs, rhs),
+            BinaryOp::Add => return ins.iadd(lhs, rhs),
+            BinaryOp::Subtract => return ins.isub(lhs, rhs),
+            BinaryOp::Multiply => return ins.imul(lhs, rhs),
+            BinaryOp::Compare => {
+                let v = ins.icmp(IntCC::SignedGreaterThanOrEqual, lhs, rhs);
+                return self.builder.ins().uextend(self.ty, v);
+            }
+            BinaryOp::Divide | BinaryOp::Remainder => return self.divide(op, lhs, rhs, (r, c)),
+        };
+        self.trap_if(overflow, Error::Overflow { op, r, c });
+        v
+    }
+
+    // Native division faults on the divisors `0` and `-1`, so they are replaced with `1` and the
+    // result is fixed afterwards.
+    fn divide(&mut self, op: BinaryOp, lhs: Value, rhs: Value, (r, c): (usize, usize)) -> Value {
+        let zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        let zero_divides = self.options.zero_division == ZeroDivision::Zero;
+        if !zero_divides {
+            self.trap_if(zero, Error::DivisionByZero { r, c });
+        }
+        let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+        if op == BinaryOp::Divide && self.options.overflow == Overflow::Trap {
+            let min = if self.ty == types::I32 {
+                i32::MIN as i64
+            } else {
+                i64::MIN
+            };
+            let is_min = self.builder.ins().icmp_imm(IntCC::Equal, lhs, min);
+            let overflow = self.builder.ins().band(minus_one, is_min);
+            self.trap_if(overflow, Error::Overflow { op, r, c });
+        }
+        let faulting = if zero_divides {
+            self.builder.ins().bor(zero, minus_one)
+        } else {
+            minus_one
+        };
+        let one = self.builder.ins().iconst(self.ty, 1);
+        let divisor = self.builder.ins().select(faulting, one, rhs);
+        // The remainder of a division by `1` is already `0`.
+        if op == BinaryOp::Remainder {
+            return self.builder.ins().srem(lhs, divisor);
+        }
+        let quotient = self.builder.ins().sdiv(lhs, divisor);
+        let negated = self.builder.ins().ineg(lhs);
+        let v = self.builder.ins().select(minus_one, negated, quotient);
+        if zero_divides {
+            let zero_value = self.builder.ins().iconst(self.ty, 0);
+            self.builder.ins().select(zero, zero_value, v)
+        } else {
+            v
+        }
+    }
+
+    // Stops the program with `error` if `condition` is nonzero.
+    fn trap_if(&mut self, condition: Value, error: Error) {
+        let index = self.traps.len();
+        self.traps.push(error);
+        let trap = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.set_cold_block(trap);
+        self.builder.ins().brif(condition, trap, &[], next, &[]);
+        self.builder.switch_to_block(trap);
+        self.call(TRAP, &[index as i64]);
+        self.builder.ins().jump(self.exit, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    fn exit_if(&mut self, condition: Value) {
+        let next = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(condition, self.exit, &[], next, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    // Calls a callback with constant arguments, and returns its result if it has one.
+    fn call(&mut self, callback: usize, args: &[i64]) -> Option<Value> {
+        let args: Vec<_> = args
+            .iter()
+            .map(|&arg| self.builder.ins().iconst(types::I64, arg))
+            .collect();
+        self.call_values(callback, &args)
+    }
+
+    fn call_values(&mut self, callback: usize, args: &[Value]) -> Option<Value> {
+        let mut values = vec![self.runtime];
+        values.extend(args);
+        let call = self.builder.ins().call(self.callbacks[callback], &values);
+        self.builder.inst_results(call).first().copied()
+    }
+
+    // Converts a value from the storages to the type of the values.
+    fn narrow(&mut self, v: Value) -> Value {
+        if self.ty == types::I64 {
+            v
+        } else {
+            self.builder.ins().ireduce(self.ty, v)
+        }
+    }
+
+    fn widen(&mut self, v: Value) -> Value {
+        if self.ty == types::I64 {
+            v
+        } else {
+            self.builder.ins().sextend(types::I64, v)
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{Options, Overflow, StorageKind, ZeroDivision};
+
+pub(crate) fn emit(blocks: &[Block], options: &Options) -> String {
+    let bits = if options.bigint {
+        0
+    } else {
+        options.width.bits()
+    };
+    let mut output = format!("const BITS = {bits};\n\n");
+    output.push_str(include_str!("header.js"));
+    output.push_str(
+        "
+// `read` returns the next chunk of the input as a string, or `null` at the end of it, and `write`
+// takes a chunk of the output as one. Returns the exit code.
+export function run(read = () => null, write = () => {}) {
+  return runBytes(...stringCallbacks(read, write));
+}
+
+// Like `run`, with the chunks as `Uint8Array`s of UTF-8.
+export function runBytes(read = () => null, write = () => {}) {
+  const io = new Io(read, write);
+  // The ㅎ storage is a plain stack.
+  const s = Array.from({ length: 28 }, (_, i) => (i === 21 ? new Queue() : []));
+  let label = 0;
+  for (;;) {
+    switch (label) {
+",
+    );
+    for (label, block) in blocks.iter().enumerate() {
+        emit_block(&mut output, label, block, options);
+    }
+    output.push_str("    }\n  }\n}\n");
+    output
+}
+
+fn emit_block(output: &mut String, label: usize, block: &Block, options: &Options) {
+    writeln!(output, "      case {label}: {{").ok();
+    for instruction in &block.instructions {
+        output.push_str("        ");
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                writeln!(output, "const v{dst} = {};", pop(storage)).ok();
+            }
+            Instruction::Push { storage, src } => {
+                writeln!(output, "s[{storage}].push(v{src});").ok();
+            }
+            Instruction::PushFront { storage, src } => {
+                writeln!(output, "s[{storage}].unshift(v{src});").ok();
+            }
+            Instruction::Const { dst, value } => {
+                writeln!(output, "const v{dst} = {value}n;").ok();
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                writeln!(
+                    output,
+                    "const v{dst} = {};",
+                    binary(op, lhs, rhs, cell, options)
+                )
+                .ok();
+            }
+            Instruction::PrintDecimal(src) => {
+                writeln!(output, "io.printDecimal(v{src});").ok();
+            }
+            Instruction::PrintUnicode(src) => {
+                writeln!(output, "io.printUnicode(v{src});").ok();
+            }
+            Instruction::ScanDecimal(dst) => {
+                writeln!(output, "const v{dst} = io.scanDecimal();").ok();
+            }
+            Instruction::ScanUnicode(dst) => {
+                writeln!(output, "const v{dst} = io.scanUnicode();").ok();
+            }
+        }
+    }
+    output.push_str("        ");
+    match block.terminator {
+        Terminator::Goto(target) => {
+            writeln!(output, "label = {target};").ok();
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            writeln!(
+                output,
+                "label = s[{storage}].length < {size} ? {less} : {otherwise};"
+            )
+            .ok();
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            writeln!(
+                output,
+                "label = {} !== 0n ? {nonzero} : {zero};",
+                pop(storage)
+            )
+            .ok();
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            let peek = if StorageKind::from(storage) == StorageKind::Queue {
+                format!("s[{storage}].peek()")
+            } else {
+                format!("s[{storage}].at(-1)")
+            };
+            writeln!(output, "label = {peek} === 0n ? {zero} : {otherwise};").ok();
+        }
+        Terminator::Halt { storage } => {
+            writeln!(
+                output,
+                "io.flush();\n        return s[{storage}].length ? exitCode({}) : 0;",
+                pop(storage)
+            )
+            .ok();
+            output.push_str("      }\n");
+            return;
+        }
+    }
+    output.push_str("        break;\n      }\n");
+}
+
+fn binary(
+    op: BinaryOp,
+    lhs: Register,
+    rhs: Register,
+    (r, c): (usize, usize),
+    options: &Options,
+) -> String {
+    let (r, c) = (r + 1, c + 1);
+    let plain = match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Remainder => "%",
+        BinaryOp::Compare => return format!("v{lhs} >= v{rhs} ? 1n : 0n"),
+    };
+    let expr = format!("v{lhs} {plain} v{rhs}");
+    // `BigInt` arithmetic is exact, and a remainder is never larger than its operands.
+    let expr = if options.bigint || op == BinaryOp::Remainder {
+        expr
+    } else if options.overflow == Overflow::Trap {
+        format!("io.checked({expr}, \"{op}\", {r}, {c})")
+    } else {
+        format!("wrap({expr})")
+    };
+    if !matches!(op, BinaryOp::Divide | BinaryOp::Remainder) {
+        return expr;
+    }
+    match options.zero_division {
+        // Reflection happens before the division, in a separate block.
+        ZeroDivision::Undefined | ZeroDivision::Reflect => expr,
+        ZeroDivision::Trap => format!("(io.checkZero(v{rhs}, {r}, {c}), {expr})"),
+        ZeroDivision::Zero => format!("v{rhs} === 0n ? 0n : {expr}"),
+    }
+}
+
+fn pop(storage: usize) -> String {
+    if StorageKind::from(storage) == StorageKind::Queue {
+        format!("s[{storage}].shift()")
+    } else {
+        format!("s[{storage}].pop()")
+    }
+}
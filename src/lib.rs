@@ -1,10 +1,39 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
-// Integer type used throughout aheui runtime.
-type Integer = i64;
+use ir::{BinaryOp, Block, Instruction, Register, Terminator};
 
-#[derive(Clone, Copy, Debug)]
-enum Consonant {
+mod asm;
+pub mod bytecode;
+mod c;
+mod elf;
+mod error;
+mod extension;
+mod interpreter;
+pub mod ir;
+#[cfg(feature = "jit")]
+pub mod jit;
+mod js;
+mod linux;
+mod llvm;
+mod python;
+mod rust;
+pub mod threaded;
+mod vm;
+mod wasi;
+mod wasm;
+mod wat;
+mod x86;
+
+pub use error::Error;
+pub use extension::Extension;
+pub use interpreter::{interpret, interpret_with, interpret_with_extension};
+
+/// Integer type used throughout aheui runtime, wide enough for every [`Width`].
+pub type Integer = i128;
+
+/// Instruction encoded in the leading jamo of a syllable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Consonant {
     Halt,
     Add,
     Multiply,
@@ -19,14 +48,39 @@ enum Consonant {
     Push(Integer),
     Duplicate,
     Exchange,
+    /// Selects the storage with the given index.
     Select(u32),
+    /// Moves a value to the storage with the given index.
     Move(u32),
     Compare,
     Branch,
 }
 
-#[derive(Clone, Debug)]
-enum Vowel {
+impl Consonant {
+    // Number of values the instruction needs on the current storage.
+    fn required(self) -> usize {
+        match self {
+            Consonant::Add
+            | Consonant::Subtract
+            | Consonant::Multiply
+            | Consonant::Divide
+            | Consonant::Remainder
+            | Consonant::Compare
+            | Consonant::Exchange => 2,
+            Consonant::PrintDecimal
+            | Consonant::PrintUnicode
+            | Consonant::Pop
+            | Consonant::Duplicate
+            | Consonant::Move(..)
+            | Consonant::Branch => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Cursor movement encoded in the medial jamo of a syllable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Vowel {
     Up,
     Down,
     Left,
@@ -48,22 +102,31 @@ enum Direction {
     Right,
 }
 
-#[derive(Clone, Default, Debug)]
-struct Syllable {
-    consonant: Option<Consonant>,
-    vowel: Option<Vowel>,
+/// Decoded code cell. Non-Hangul cells and jamo without meaning are `None`.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Syllable {
+    pub consonant: Option<Consonant>,
+    pub vowel: Option<Vowel>,
 }
 
-#[derive(Debug)]
-struct Field {
-    w: usize,
-    h: usize,
+/// Size of the smallest rectangle containing the code, and the extent of each row and column.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Field {
+    pub w: usize,
+    pub h: usize,
+    /// Number of cells in each row.
+    pub widths: Vec<usize>,
+    /// Number of rows up to the last one reaching each column.
+    pub heights: Vec<usize>,
 }
 
 impl Field {
-    fn next_pos(&self, state: &State) -> (usize, usize) {
+    fn next_pos(&self, state: &State, wrap: WrapMode) -> (usize, usize) {
         assert!(state.r < self.h, "`state.r` must be in range 0..{}", self.h);
         assert!(state.c < self.w, "`state.c` must be in range 0..{}", self.w);
+        if wrap == WrapMode::Spec {
+            return self.next_pos_spec(state);
+        }
         match state.direction {
             Direction::Up => {
                 if let Some(next) = state.r.checked_sub(state.speed) {
@@ -95,14 +158,301 @@ impl Field {
             }
         }
     }
+
+    // The cursor is always within the extent of its column, as every move keeps it there, but may
+    // be past the end of its row after moving vertically.
+    fn next_pos_spec(&self, state: &State) -> (usize, usize) {
+        let speed = state.speed;
+        let backward = |pos: usize, len: usize| {
+            let len = len.max(1);
+            pos.checked_sub(speed)
+                .unwrap_or((pos + len * speed - speed) % len)
+        };
+        let forward = |pos: usize, len: usize| (pos + speed) % len.max(1);
+        match state.direction {
+            Direction::Up => (backward(state.r, self.heights[state.c]), state.c),
+            Direction::Down => (forward(state.r, self.heights[state.c]), state.c),
+            Direction::Left => (state.r, backward(state.c, self.widths[state.r])),
+            Direction::Right => (state.r, forward(state.c, self.widths[state.r])),
+        }
+    }
+}
+
+/// Aheui code decoded into a grid of [`Syllable`]s.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub field: Field,
+    /// Cells in row-major order, `field.w * field.h` in total.
+    pub syllables: Vec<Syllable>,
+}
+
+impl Program {
+    /// Returns the cell at row `r` and column `c`, or `None` if it is out of the field.
+    pub fn get(&self, r: usize, c: usize) -> Option<&Syllable> {
+        if r < self.field.h && c < self.field.w {
+            self.syllables.get(r * self.field.w + c)
+        } else {
+            None
+        }
+    }
+
+    // Rejects code which would loop forever without doing anything.
+    fn validate(&self) -> Result<(), Error> {
+        if self.syllables.is_empty() {
+            Err(Error::EmptyProgram)
+        } else if self.syllables.iter().all(|s| *s == Syllable::default()) {
+            Err(Error::NoCode)
+        } else {
+            Ok(())
+        }
+    }
 }
 
-pub fn transpile(code: &str) -> String {
+/// Bit width of the fixed-size integers.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Width {
+    W32,
+    #[default]
+    W64,
+    W128,
+}
+
+impl Width {
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::W32 => 32,
+            Self::W64 => 64,
+            Self::W128 => 128,
+        }
+    }
+
+    /// Truncates `v` to this width in two's complement.
+    pub fn wrap(self, v: Integer) -> Integer {
+        match self {
+            Self::W32 => v as i32 as Integer,
+            Self::W64 => v as i64 as Integer,
+            Self::W128 => v,
+        }
+    }
+}
+
+/// Behaviour of arithmetic that overflows the integer width.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    /// Emit plain signed C arithmetic, leaving overflow undefined.
+    #[default]
+    Undefined,
+    /// Wrap around in two's complement, including `MIN / -1`.
+    Wrap,
+    /// Abort with the operation and the grid cell.
+    Trap,
+}
+
+/// Behaviour of division and remainder by zero.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ZeroDivision {
+    /// Emit plain C division, which usually crashes. The interpreter traps.
+    #[default]
+    Undefined,
+    /// Abort with the grid cell.
+    Trap,
+    /// Pop both operands and push `0`.
+    Zero,
+    /// Leave the operands and reverse the cursor, as if the storage had too few values.
+    Reflect,
+}
+
+/// How the cursor wraps around when it leaves the code.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    /// Jump to the opposite side of the smallest rectangle containing the code.
+    #[default]
+    Rectangle,
+    /// Keep moving from the opposite end of the current row or column, as in the specification.
+    Spec,
+}
+
+/// Options for the generated C code, also honoured by [`interpret_with`].
+#[derive(Clone, Default, Debug)]
+pub struct Options {
+    /// Use arbitrary-precision integers instead of 64-bit ones.
+    ///
+    /// Takes precedence over `width`. The interpreter does not support it and uses `width`.
+    pub bigint: bool,
+    /// Width of the integers on the storages.
+    pub width: Width,
+    /// What happens when arithmetic overflows `width`. The interpreter wraps unless it traps.
+    pub overflow: Overflow,
+    /// What happens when dividing by zero.
+    pub zero_division: ZeroDivision,
+    /// How the cursor wraps around the code.
+    pub wrap: WrapMode,
+}
+
+pub fn transpile(code: &str) -> Result<String, Error> {
+    transpile_with(code, &Options::default())
+}
+
+pub fn transpile_with(code: &str, options: &Options) -> Result<String, Error> {
+    Ok(c::emit(&linearize_with(&parse(code), options)?, options))
+}
+
+/// Transpiles `code` to textual LLVM IR, which can be compiled with `llc` or `clang`.
+///
+/// The IR uses opaque pointers. Big integers are not supported.
+pub fn transpile_llvm(code: &str, options: &Options) -> Result<String, Error> {
+    if options.bigint {
+        return Err(Error::Unsupported {
+            option: "bigint",
+            backend: "LLVM IR",
+        });
+    }
+    Ok(llvm::emit(&linearize_with(&parse(code), options)?, options))
+}
+
+/// Transpiles `code` to GNU assembly for x86-64 Linux, which can be built with `as` and `ld`.
+///
+/// The program makes system calls directly instead of linking libc. The ㅎ storage is a plain
+/// stack. Big integers and 128-bit integers are not supported.
+pub fn transpile_asm(code: &str, options: &Options) -> Result<String, Error> {
+    Ok(asm::emit(&x86_program(code, options)?))
+}
+
+/// Compiles `code` to a static x86-64 Linux executable, the assembled form of [`transpile_asm`].
+///
+/// No external assembler, linker or C compiler is needed.
+pub fn transpile_exe(code: &str, options: &Options) -> Result<Vec<u8>, Error> {
+    Ok(elf::assemble(&x86_program(code, options)?))
+}
+
+fn x86_program(code: &str, options: &Options) -> Result<x86::Program, Error> {
+    let option = if options.bigint {
+        "bigint"
+    } else if options.width == Width::W128 {
+        "128-bit integers"
+    } else {
+        return Ok(asm::program(
+            &linearize_with(&parse(code), options)?,
+            options,
+        ));
+    };
+    Err(Error::Unsupported {
+        option,
+        backend: "x86-64 assembly",
+    })
+}
+
+/// Transpiles `code` to a WebAssembly text module for WASI, exporting `_start` and `memory`.
+///
+/// The ㅎ storage is a plain stack. Big integers and 128-bit integers are not supported.
+pub fn transpile_wat(code: &str, options: &Options) -> Result<String, Error> {
+    Ok(wat::emit(&wasm_module(code, options)?))
+}
+
+/// Compiles `code` to a binary WebAssembly module, the encoded form of [`transpile_wat`].
+pub fn transpile_wasm(code: &str, options: &Options) -> Result<Vec<u8>, Error> {
+    Ok(wasm::encode(&wasm_module(code, options)?))
+}
+
+fn wasm_module(code: &str, options: &Options) -> Result<wasm::Module, Error> {
+    let option = if options.bigint {
+        "bigint"
+    } else if options.width == Width::W128 {
+        "128-bit integers"
+    } else {
+        return Ok(wasm::module(
+            &linearize_with(&parse(code), options)?,
+            options,
+        ));
+    };
+    Err(Error::Unsupported {
+        option,
+        backend: "WebAssembly",
+    })
+}
+
+/// Transpiles `code` to a standalone Rust program without dependencies or `unsafe`.
+///
+/// Besides `main`, the program has `pub fn run(input, output) -> i32`, so it can also be
+/// included as a module. The ㅎ storage is a plain stack. Big integers are not supported.
+pub fn transpile_rust(code: &str, options: &Options) -> Result<String, Error> {
+    if options.bigint {
+        return Err(Error::Unsupported {
+            option: "bigint",
+            backend: "Rust",
+        });
+    }
+    Ok(rust::emit(&linearize_with(&parse(code), options)?, options))
+}
+
+/// Transpiles `code` to a JavaScript ES module with values as `BigInt`s.
+///
+/// The module exports `run(read, write)`, which takes callbacks reading and writing the input and
+/// output as `Uint8Array`s, and returns the exit code. The ㅎ storage is a plain stack.
+pub fn transpile_js(code: &str, options: &Options) -> Result<String, Error> {
+    Ok(js::emit(&linearize_with(&parse(code), options)?, options))
+}
+
+/// Transpiles `code` to a Python 3 script, with values as Python integers.
+///
+/// Besides running as a script, the module has `run(input, output)`, which takes binary files and
+/// returns the exit code. The ㅎ storage is a plain stack.
+pub fn transpile_python(code: &str, options: &Options) -> Result<String, Error> {
+    Ok(python::emit(
+        &linearize_with(&parse(code), options)?,
+        options,
+    ))
+}
+
+/// Linearizes the control flow of `program` into [`ir::Block`]s, starting from label `0`.
+pub fn linearize(program: &Program) -> Result<Vec<Block>, Error> {
+    linearize_with(program, &Options::default())
+}
+
+/// Linearizes `program` like [`linearize`], for code generated with `options`.
+pub fn linearize_with(program: &Program, options: &Options) -> Result<Vec<Block>, Error> {
+    Ok(Linearizer::new(program, options)?.linearize())
+}
+
+// Number of strokes of each trailing jamo, pushed by ㅂ.
+const STROKES: [Integer; 28] = [
+    0, // None
+    2, // ㄱ
+    4, // ㄲ
+    4, // ㄳ
+    2, // ㄴ
+    5, // ㄵ
+    5, // ㄶ
+    3, // ㄷ
+    5, // ㄹ
+    7, // ㄺ
+    9, // ㄻ
+    9, // ㄼ
+    7, // ㄽ
+    9, // ㄾ
+    9, // ㄿ
+    8, // ㅀ
+    4, // ㅁ
+    4, // ㅂ
+    6, // ㅄ
+    2, // ㅅ
+    4, // ㅆ
+    1, // ㅇ, which scans a decimal instead
+    3, // ㅈ
+    4, // ㅊ
+    3, // ㅋ
+    4, // ㅌ
+    4, // ㅍ
+    3, // ㅎ, which scans a character instead
+];
+
+/// Decodes Hangul syllables of `code` into a [`Program`].
+pub fn parse(code: &str) -> Program {
     let (w, h) = code
         .lines()
         .fold((0, 0), |(w, h), line| (w.max(line.chars().count()), h + 1));
     let mut syllables = vec![Syllable::default(); w * h];
-    for (line, row) in code.lines().zip(syllables.chunks_mut(w)) {
+    for (line, row) in code.lines().zip(syllables.chunks_mut(w.max(1))) {
         for (c, cell) in line.chars().zip(row) {
             let syllable = 0xAC00..=0xD7AF;
             let mut i = c as u32;
@@ -159,63 +509,11 @@ pub fn transpile(code: &str) -> String {
                 }),
                 // ㅂ
                 7 => Some(match trail {
-                    // None
-                    0 => Consonant::Push(0),
-                    // ㄱ
-                    1 => Consonant::Push(2),
-                    // ㄲ
-                    2 => Consonant::Push(4),
-                    // ㄳ
-                    3 => Consonant::Push(4),
-                    // ㄴ
-                    4 => Consonant::Push(2),
-                    // ㄵ
-                    5 => Consonant::Push(5),
-                    // ㄶ
-                    6 => Consonant::Push(5),
-                    // ㄷ
-                    7 => Consonant::Push(3),
-                    // ㄹ
-                    8 => Consonant::Push(5),
-                    // ㄺ
-                    9 => Consonant::Push(7),
-                    // ㄻ
-                    10 => Consonant::Push(9),
-                    // ㄼ
-                    11 => Consonant::Push(9),
-                    // ㄽ
-                    12 => Consonant::Push(7),
-                    // ㄾ
-                    13 => Consonant::Push(9),
-                    // ㄿ
-                    14 => Consonant::Push(9),
-                    // ㅀ
-                    15 => Consonant::Push(8),
-                    // ㅁ
-                    16 => Consonant::Push(4),
-                    // ㅂ
-                    17 => Consonant::Push(4),
-                    // ㅄ
-                    18 => Consonant::Push(6),
-                    // ㅅ
-                    19 => Consonant::Push(2),
-                    // ㅆ
-                    20 => Consonant::Push(4),
                     // ㅇ
                     21 => Consonant::ScanDecimal,
-                    // ㅈ
-                    22 => Consonant::Push(3),
-                    // ㅊ
-                    23 => Consonant::Push(4),
-                    // ㅋ
-                    24 => Consonant::Push(3),
-                    // ㅌ
-                    25 => Consonant::Push(4),
-                    // ㅍ
-                    26 => Consonant::Push(4),
                     // ㅎ
                     27 => Consonant::ScanUnicode,
-                    _ => unreachable!("trailing jamo out of range: {}", trail),
+                    _ => Consonant::Push(STROKES[trail as usize]),
                 }),
                 // ㅃ
                 8 => Some(Consonant::Duplicate),
@@ -237,16 +535,26 @@ pub fn transpile(code: &str) -> String {
             };
         }
     }
-    let code = Linearizer::new(&Field { w, h }, &syllables).linearize();
-    let mut output = include_str!("header.c").to_owned();
-    for line in code.iter() {
-        output.push_str(line);
-        output.push('\n');
+    let widths: Vec<_> = code.lines().map(|line| line.chars().count()).collect();
+    let heights = (0..w)
+        .map(|c| widths.iter().rposition(|&len| len > c).map_or(0, |r| r + 1))
+        .collect();
+    Program {
+        field: Field {
+            w,
+            h,
+            widths,
+            heights,
+        },
+        syllables,
     }
-    output.push_str(include_str!("footer.c"));
-    output
 }
 
+// Index of the ㅇ storage, which is the only queue.
+const QUEUE: usize = 21;
+// Index of the ㅎ storage, which is forwarded to the host.
+const STREAM: usize = 27;
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 enum StorageKind {
     Stack,
@@ -257,13 +565,16 @@ enum StorageKind {
 impl From<usize> for StorageKind {
     fn from(value: usize) -> Self {
         match value {
-            21 => Self::Queue,
-            27 => Self::Stream,
+            QUEUE => Self::Queue,
+            STREAM => Self::Stream,
             _ => Self::Stack,
         }
     }
 }
 
+// Lower bounds of the number of values on each storage.
+type Sizes = [usize; 28];
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 struct State {
     r: usize,
@@ -274,7 +585,66 @@ struct State {
 }
 
 impl State {
-    fn reverse_next(&self, field: &Field) -> Self {
+    fn turn(&mut self, vowel: &Vowel) {
+        match vowel {
+            Vowel::Up => {
+                self.direction = Direction::Up;
+                self.speed = 1;
+            }
+            Vowel::Down => {
+                self.direction = Direction::Down;
+                self.speed = 1;
+            }
+            Vowel::Left => {
+                self.direction = Direction::Left;
+                self.speed = 1;
+            }
+            Vowel::Right => {
+                self.direction = Direction::Right;
+                self.speed = 1;
+            }
+            Vowel::UpTwo => {
+                self.direction = Direction::Up;
+                self.speed = 2;
+            }
+            Vowel::DownTwo => {
+                self.direction = Direction::Down;
+                self.speed = 2;
+            }
+            Vowel::LeftTwo => {
+                self.direction = Direction::Left;
+                self.speed = 2;
+            }
+            Vowel::RightTwo => {
+                self.direction = Direction::Right;
+                self.speed = 2;
+            }
+            Vowel::HorizontalFlip => {
+                self.direction = match self.direction {
+                    Direction::Left => Direction::Right,
+                    Direction::Right => Direction::Left,
+                    other => other,
+                };
+            }
+            Vowel::VerticalFlip => {
+                self.direction = match self.direction {
+                    Direction::Up => Direction::Down,
+                    Direction::Down => Direction::Up,
+                    other => other,
+                };
+            }
+            Vowel::Flip => {
+                self.direction = match self.direction {
+                    Direction::Up => Direction::Down,
+                    Direction::Down => Direction::Up,
+                    Direction::Left => Direction::Right,
+                    Direction::Right => Direction::Left,
+                };
+            }
+        }
+    }
+
+    fn reverse_next(&self, field: &Field, wrap: WrapMode) -> Self {
         let mut next = Self {
             direction: match self.direction {
                 Direction::Up => Direction::Down,
@@ -284,7 +654,7 @@ impl State {
             },
             ..*self
         };
-        (next.r, next.c) = field.next_pos(&next);
+        (next.r, next.c) = field.next_pos(&next, wrap);
         next
     }
 }
@@ -292,22 +662,26 @@ impl State {
 struct Linearizer<'a> {
     field: &'a Field,
     code: &'a [Syllable],
-    blocks: Vec<String>,
-    state_memo: HashMap<(State, usize), usize>,
+    options: &'a Options,
+    blocks: Vec<Block>,
+    // Blocks starting at each state, with the lower bound of the size of their first storage they
+    // assume.
+    state_memo: HashMap<(State, bool), Vec<(usize, usize)>>,
 }
 
 impl<'a> Linearizer<'a> {
-    fn new(field: &'a Field, code: &'a [Syllable]) -> Self {
-        assert!(!code.is_empty(), "`code` must not be empty");
-        Self {
-            field,
-            code,
+    fn new(program: &'a Program, options: &'a Options) -> Result<Self, Error> {
+        program.validate()?;
+        Ok(Self {
+            field: &program.field,
+            code: &program.syllables,
+            options,
             blocks: vec![],
             state_memo: HashMap::new(),
-        }
+        })
     }
 
-    fn linearize(mut self) -> Vec<String> {
+    fn linearize(mut self) -> Vec<Block> {
         self.linearize_recursive(
             State {
                 r: 0,
@@ -316,100 +690,81 @@ impl<'a> Linearizer<'a> {
                 speed: 1,
                 storage: 0,
             },
-            0,
+            [0; 28],
         );
         self.blocks
     }
 
-    fn linearize_recursive(&mut self, mut state: State, presize: usize) -> usize {
-        if let Some(&label) = self.state_memo.get(&(state.clone(), presize)) {
+    fn linearize_recursive(&mut self, state: State, sizes: Sizes) -> usize {
+        self.linearize_block(state, sizes, false, false)
+    }
+
+    // Like `linearize_recursive`, after checking that the storage has the values the first
+    // consonant requires.
+    fn linearize_checked(&mut self, state: State, sizes: Sizes) -> usize {
+        self.linearize_block(state, sizes, true, false)
+    }
+
+    // `sizes` are lower bounds of the storage sizes when the block is entered, of which the block
+    // only assumes the one on the storage it starts with. Assuming the others too would start a
+    // new copy of the block for every combination of bounds that reaches it. `checked` tells that
+    // the first consonant was just checked. `divisor_checked` tells that the first cell is a
+    // division whose divisor is known to be nonzero, so that reflecting division by zero does not
+    // check it again.
+    fn linearize_block(
+        &mut self,
+        mut state: State,
+        sizes: Sizes,
+        checked: bool,
+        divisor_checked: bool,
+    ) -> usize {
+        let pos = state.r * self.field.w + state.c;
+        let required = self.code[pos].consonant.map_or(0, Consonant::required);
+        let own = sizes[state.storage];
+        let key = (state.clone(), divisor_checked);
+        let memo = self.state_memo.entry(key).or_default();
+        // A block assuming fewer values is still correct, and reusing it keeps loops finite. After
+        // a check it must not check the first consonant again, though, or the passed check would
+        // jump back.
+        let reusable = (memo.iter())
+            .filter(|&&(assumed, _)| assumed <= own && (!checked || assumed >= own.min(required)))
+            .max();
+        if let Some(&(_, label)) = reusable {
             return label;
         }
+        let mut sizes = [0; 28];
+        sizes[state.storage] = own;
         let init_storage = state.storage;
         let entry = self.blocks.len();
-        self.state_memo.insert((state.clone(), presize), entry);
-        let mut size = [0usize; 28];
-        size[state.storage] = presize;
+        memo.push((own, entry));
+        // The terminator is replaced once the end of the block is found.
+        self.blocks.push(Block {
+            instructions: vec![],
+            terminator: Terminator::Halt { storage: 0 },
+        });
+        let mut size = sizes;
         let mut visited = HashSet::new();
+        // Consonants of the block, with the cells they were read from.
         let mut block = vec![];
         loop {
             if !visited.insert(state.clone()) {
-                self.optimize_block(entry, init_storage, presize, &block);
-                let i = self.blocks.len();
-                self.blocks.push("".into());
-                let j = self.linearize_recursive(state, 0);
-                self.blocks[i] = format!("    goto B{j};");
+                self.optimize_block(entry, init_storage, &sizes, &block);
+                let j = self.linearize_recursive(state, carried(&size));
+                self.blocks[entry].terminator = Terminator::Goto(j);
                 break;
             }
             let prev = state.clone();
             let pos = state.r * self.field.w + state.c;
-            match self.code[pos].vowel {
-                None => {}
-                Some(Vowel::Up) => {
-                    state.direction = Direction::Up;
-                    state.speed = 1;
-                }
-                Some(Vowel::Down) => {
-                    state.direction = Direction::Down;
-                    state.speed = 1;
-                }
-                Some(Vowel::Left) => {
-                    state.direction = Direction::Left;
-                    state.speed = 1;
-                }
-                Some(Vowel::Right) => {
-                    state.direction = Direction::Right;
-                    state.speed = 1;
-                }
-                Some(Vowel::UpTwo) => {
-                    state.direction = Direction::Up;
-                    state.speed = 2;
-                }
-                Some(Vowel::DownTwo) => {
-                    state.direction = Direction::Down;
-                    state.speed = 2;
-                }
-                Some(Vowel::LeftTwo) => {
-                    state.direction = Direction::Left;
-                    state.speed = 2;
-                }
-                Some(Vowel::RightTwo) => {
-                    state.direction = Direction::Right;
-                    state.speed = 2;
-                }
-                Some(Vowel::HorizontalFlip) => {
-                    state.direction = match state.direction {
-                        Direction::Left => Direction::Right,
-                        Direction::Right => Direction::Left,
-                        other => other,
-                    };
-                }
-                Some(Vowel::VerticalFlip) => {
-                    state.direction = match state.direction {
-                        Direction::Up => Direction::Down,
-                        Direction::Down => Direction::Up,
-                        other => other,
-                    };
-                }
-                Some(Vowel::Flip) => {
-                    state.direction = match state.direction {
-                        Direction::Up => Direction::Down,
-                        Direction::Down => Direction::Up,
-                        Direction::Left => Direction::Right,
-                        Direction::Right => Direction::Left,
-                    };
-                }
+            if let Some(vowel) = &self.code[pos].vowel {
+                state.turn(vowel);
             }
             match self.code[pos].consonant {
                 None => {}
                 Some(Consonant::Halt) => {
-                    self.optimize_block(entry, init_storage, presize, &block);
-                    let storage = state.storage;
-                    if StorageKind::from(storage) == StorageKind::Queue {
-                        self.blocks.push(format!("    HALTQ({storage});"));
-                    } else {
-                        self.blocks.push(format!("    HALTS({storage});"));
-                    }
+                    self.optimize_block(entry, init_storage, &sizes, &block);
+                    self.blocks[entry].terminator = Terminator::Halt {
+                        storage: state.storage,
+                    };
                     break;
                 }
                 Some(
@@ -422,117 +777,153 @@ impl<'a> Linearizer<'a> {
                 ) => {
                     if size[state.storage] < 2 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 2);
-                        self.blocks[i] = format!("    JSL({storage}, 2, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 2));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 2,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    block.push(c);
+                    if matches!(c, Consonant::Divide | Consonant::Remainder)
+                        && self.options.zero_division == ZeroDivision::Reflect
+                        && !(divisor_checked && visited.len() == 1)
+                    {
+                        let storage = state.storage;
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_block(prev, at_least(&size, storage, 2), true, true);
+                        self.blocks[entry].terminator = Terminator::Jz {
+                            storage,
+                            zero: j,
+                            otherwise: k,
+                        };
+                        break;
+                    }
+                    block.push((c, (state.r, state.c)));
                     size[state.storage] = size[state.storage].saturating_sub(1);
                 }
                 Some(c @ (Consonant::PrintDecimal | Consonant::PrintUnicode | Consonant::Pop)) => {
                     if size[state.storage] < 1 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 1);
-                        self.blocks[i] = format!("    JSL({storage}, 1, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 1));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 1,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                     size[state.storage] = size[state.storage].saturating_sub(1);
                 }
                 Some(
                     c @ (Consonant::ScanDecimal | Consonant::ScanUnicode | Consonant::Push(..)),
                 ) => {
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                     size[state.storage] += 1;
                 }
                 Some(c @ Consonant::Duplicate) => {
                     if size[state.storage] < 1 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 1);
-                        self.blocks[i] = format!("    JSL({storage}, 1, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 1));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 1,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                     size[state.storage] += 1;
                 }
                 Some(c @ Consonant::Exchange) => {
                     if size[state.storage] < 2 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 2);
-                        self.blocks[i] = format!("    JSL({storage}, 2, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 2));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 2,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                 }
                 Some(c @ Consonant::Select(s)) => {
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                     state.storage = s as usize;
                 }
                 Some(c @ Consonant::Move(s)) => {
                     if size[state.storage] < 1 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 1);
-                        self.blocks[i] = format!("    JSL({storage}, 1, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 1));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 1,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    block.push(c);
+                    block.push((c, (state.r, state.c)));
                     size[state.storage] = size[state.storage].saturating_sub(1);
                     size[s as usize] += 1;
                 }
                 Some(Consonant::Branch) => {
                     if size[state.storage] < 1 {
                         let storage = state.storage;
-                        self.optimize_block(entry, init_storage, presize, &block);
-                        let i = self.blocks.len();
-                        self.blocks.push("".into());
-                        let reverse_state = state.reverse_next(self.field);
-                        let j = self.linearize_recursive(reverse_state, 0);
-                        let k = self.linearize_recursive(prev, 1);
-                        self.blocks[i] = format!("    JSL({storage}, 1, {j}, {k});");
+                        self.optimize_block(entry, init_storage, &sizes, &block);
+                        let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                        let j = self.linearize_recursive(reverse_state, carried(&size));
+                        let k = self.linearize_checked(prev, at_least(&size, storage, 1));
+                        self.blocks[entry].terminator = Terminator::Jsl {
+                            storage,
+                            size: 1,
+                            less: j,
+                            otherwise: k,
+                        };
                         break;
                     }
-                    self.optimize_block(entry, init_storage, presize, &block);
-                    let i = self.blocks.len();
+                    self.optimize_block(entry, init_storage, &sizes, &block);
                     let storage = state.storage;
-                    self.blocks.push("".into());
-                    let reverse_state = state.reverse_next(self.field);
-                    let j = self.linearize_recursive(reverse_state, 0);
-                    (state.r, state.c) = self.field.next_pos(&state);
-                    let k = self.linearize_recursive(state, 0);
-                    if StorageKind::from(storage) == StorageKind::Queue {
-                        self.blocks[i] = format!("    JNZQ({storage}, {k}, {j})");
-                    } else {
-                        self.blocks[i] = format!("    JNZS({storage}, {k}, {j})");
-                    }
+                    // The branch pops the value it tests.
+                    size[storage] -= 1;
+                    let reverse_state = state.reverse_next(self.field, self.options.wrap);
+                    let j = self.linearize_recursive(reverse_state, carried(&size));
+                    (state.r, state.c) = self.field.next_pos(&state, self.options.wrap);
+                    let k = self.linearize_recursive(state, carried(&size));
+                    self.blocks[entry].terminator = Terminator::Jnz {
+                        storage,
+                        nonzero: k,
+                        zero: j,
+                    };
                     break;
                 }
             }
-            (state.r, state.c) = self.field.next_pos(&state);
+            // The host may change the ㅎ storage at any time, so its size is never known.
+            size[STREAM] = 0;
+            (state.r, state.c) = self.field.next_pos(&state, self.options.wrap);
         }
         entry
     }
@@ -541,218 +932,97 @@ impl<'a> Linearizer<'a> {
         &mut self,
         label: usize,
         init_storage: usize,
-        presize: usize,
-        block: &[Consonant],
+        sizes: &Sizes,
+        block: &[(Consonant, (usize, usize))],
     ) {
-        use std::fmt::Write;
-        let mut output = String::new();
+        let mut instructions = vec![];
         let mut id = 0;
         let mut var = vec![VecDeque::new(); 28];
+        // Values of the registers known at transpile time.
+        let mut known = HashMap::new();
         let mut storage = init_storage;
-        let def_init = "    integer ";
-        let mut def = String::from(def_init);
-        for _ in 0..presize {
-            if StorageKind::from(storage) == StorageKind::Queue {
-                write!(def, "v{id}=POPQ({storage}),").ok();
-                var[storage].push_back(id);
-            } else {
-                write!(def, "v{id}=POPS({storage}),",).ok();
-                var[storage].push_front(id);
-            }
-            id += 1;
-        }
-        let effect_init = "    ";
-        let mut effect = String::from(effect_init);
-        for code in block {
-            match code {
-                Consonant::Halt | Consonant::Branch => {}
-                Consonant::Add => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
+        // Values taken from the front of the queue, which `var[QUEUE]` pushes go after. Only
+        // `in_memory` values are known to be in memory before those pushes.
+        let mut front = VecDeque::new();
+        let mut in_memory = sizes[QUEUE];
+        for &(code, cell) in block {
+            // Values are popped right before their first use.
+            if storage == QUEUE {
+                while front.len() < code.required() {
+                    if in_memory == 0 {
+                        // The memory may hold more values, which come before the pushed ones.
+                        for src in var[QUEUE].drain(..) {
+                            instructions.push(Instruction::Push { storage, src });
+                            in_memory += 1;
+                        }
                     }
-                    write!(def, "v{id}=v{b}+v{a},").ok();
-                    var[storage].push_back(id);
-                    id += 1;
-                }
-                Consonant::Multiply => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=v{b}*v{a},").ok();
-                    var[storage].push_back(id);
-                    id += 1;
-                }
-                Consonant::Subtract => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=v{b}-v{a},").ok();
-                    var[storage].push_back(id);
+                    instructions.push(Instruction::Pop { dst: id, storage });
+                    front.push_back(id);
+                    in_memory -= 1;
                     id += 1;
                 }
-                Consonant::Divide => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=v{b}/v{a},").ok();
-                    var[storage].push_back(id);
+            } else {
+                while var[storage].len() < code.required() {
+                    instructions.push(Instruction::Pop { dst: id, storage });
+                    var[storage].push_front(id);
                     id += 1;
                 }
-                Consonant::Remainder => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
+            }
+            match code {
+                Consonant::Halt | Consonant::Branch => {}
+                Consonant::Add
+                | Consonant::Multiply
+                | Consonant::Subtract
+                | Consonant::Divide
+                | Consonant::Remainder => {
+                    let a = pop_var(&mut var, &mut front, storage);
+                    let b = pop_var(&mut var, &mut front, storage);
+                    let op = match code {
+                        Consonant::Add => BinaryOp::Add,
+                        Consonant::Multiply => BinaryOp::Multiply,
+                        Consonant::Subtract => BinaryOp::Subtract,
+                        Consonant::Divide => BinaryOp::Divide,
+                        _ => BinaryOp::Remainder,
                     };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=v{b}%v{a},").ok();
+                    instructions.push(self.binary(&mut known, id, op, b, a, cell));
                     var[storage].push_back(id);
                     id += 1;
                 }
                 Consonant::PrintDecimal => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if def.len() > def_init.len() {
-                        def.pop();
-                        def.push_str(";\n");
-                        output.push_str(&def);
-                        def = def_init.into();
-                    }
-                    write!(effect, "PRINTD({a}),").ok();
+                    let a = pop_var(&mut var, &mut front, storage);
+                    instructions.push(Instruction::PrintDecimal(a));
                 }
                 Consonant::PrintUnicode => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if def.len() > def_init.len() {
-                        def.pop();
-                        def.push_str(";\n");
-                        output.push_str(&def);
-                        def = def_init.into();
-                    }
-                    write!(effect, "PRINTU({a}),").ok();
+                    let a = pop_var(&mut var, &mut front, storage);
+                    instructions.push(Instruction::PrintUnicode(a));
                 }
                 Consonant::ScanDecimal => {
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=SCAND,").ok();
+                    instructions.push(Instruction::ScanDecimal(id));
                     var[storage].push_back(id);
                     id += 1;
                 }
                 Consonant::ScanUnicode => {
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}=SCANU,").ok();
+                    instructions.push(Instruction::ScanUnicode(id));
                     var[storage].push_back(id);
                     id += 1;
                 }
-                Consonant::Select(s) => storage = *s as usize,
+                Consonant::Select(s) => storage = s as usize,
                 Consonant::Compare => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    let b = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
+                    let a = pop_var(&mut var, &mut front, storage);
+                    let b = pop_var(&mut var, &mut front, storage);
                     if a == b {
-                        write!(def, "v{id}=1,").ok();
+                        known.insert(id, 1);
+                        instructions.push(Instruction::Const { dst: id, value: 1 });
                     } else {
-                        write!(def, "v{id}=v{b}>=v{a},").ok();
+                        let compare = self.binary(&mut known, id, BinaryOp::Compare, b, a, cell);
+                        instructions.push(compare);
                     }
                     var[storage].push_back(id);
                     id += 1;
                 }
                 Consonant::Exchange => {
-                    if StorageKind::from(storage) == StorageKind::Queue {
-                        let a = var[storage].pop_front().unwrap();
-                        let b = var[storage].pop_front().unwrap();
-                        var[storage].push_front(a);
-                        var[storage].push_front(b);
+                    if storage == QUEUE {
+                        front.swap(0, 1);
                     } else {
                         let a = var[storage].pop_back().unwrap();
                         let b = var[storage].pop_back().unwrap();
@@ -761,10 +1031,8 @@ impl<'a> Linearizer<'a> {
                     }
                 }
                 Consonant::Duplicate => {
-                    if StorageKind::from(storage) == StorageKind::Queue {
-                        let a = var[storage].pop_front().unwrap();
-                        var[storage].push_front(a);
-                        var[storage].push_front(a);
+                    if storage == QUEUE {
+                        front.push_front(front[0]);
                     } else {
                         let a = var[storage].pop_back().unwrap();
                         var[storage].push_back(a);
@@ -772,58 +1040,160 @@ impl<'a> Linearizer<'a> {
                     };
                 }
                 Consonant::Pop => {
-                    if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
+                    pop_var(&mut var, &mut front, storage);
                 }
                 Consonant::Push(v) => {
-                    if effect.len() > effect_init.len() {
-                        effect.pop();
-                        effect.push_str(";\n");
-                        output.push_str(&effect);
-                        effect = effect_init.into();
-                    }
-                    write!(def, "v{id}={v},").ok();
+                    known.insert(id, v);
+                    instructions.push(Instruction::Const { dst: id, value: v });
                     var[storage].push_back(id);
                     id += 1;
                 }
                 Consonant::Move(s) => {
-                    let a = if StorageKind::from(storage) == StorageKind::Queue {
-                        var[storage].pop_front().unwrap()
-                    } else {
-                        var[storage].pop_back().unwrap()
-                    };
-                    var[*s as usize].push_back(a);
+                    let a = pop_var(&mut var, &mut front, storage);
+                    var[s as usize].push_back(a);
                 }
             }
-        }
-        if def.len() > def_init.len() {
-            def.pop();
-            def.push_str(";\n");
-            output.push_str(&def);
+            // Pushes to the ㅎ storage are not delayed either, so the host sees them in order.
+            for src in var[STREAM].drain(..) {
+                instructions.push(Instruction::Push {
+                    storage: STREAM,
+                    src,
+                });
+            }
         }
         for (i, storage) in var.into_iter().enumerate() {
-            if StorageKind::from(i) == StorageKind::Queue {
-                for id in storage {
-                    write!(effect, "PUSHQ({i},{id}),").ok();
+            for src in storage {
+                instructions.push(Instruction::Push { storage: i, src });
+            }
+        }
+        for src in front.into_iter().rev() {
+            instructions.push(Instruction::PushFront {
+                storage: QUEUE,
+                src,
+            });
+        }
+        // Constants that were only operands of folded operations are not needed anymore.
+        let mut used = HashSet::new();
+        for instruction in &instructions {
+            match *instruction {
+                Instruction::Push { src, .. }
+                | Instruction::PushFront { src, .. }
+                | Instruction::PrintDecimal(src)
+                | Instruction::PrintUnicode(src) => {
+                    used.insert(src);
                 }
-            } else {
-                for id in storage {
-                    write!(effect, "PUSHS({i},{id}),").ok();
+                Instruction::Binary { lhs, rhs, .. } => {
+                    used.insert(lhs);
+                    used.insert(rhs);
                 }
+                _ => {}
             }
         }
-        if effect.len() > effect_init.len() {
-            effect.pop();
-            effect.push_str(";\n");
-            output.push_str(&effect);
+        instructions.retain(|instruction| match *instruction {
+            Instruction::Const { dst, .. } => used.contains(&dst),
+            _ => true,
+        });
+        // The remaining registers are numbered again from zero, in the order they are assigned.
+        let mut number = HashMap::new();
+        for instruction in &mut instructions {
+            match instruction {
+                Instruction::Push { src, .. }
+                | Instruction::PushFront { src, .. }
+                | Instruction::PrintDecimal(src)
+                | Instruction::PrintUnicode(src) => *src = number[src],
+                Instruction::Binary { dst, lhs, rhs, .. } => {
+                    *lhs = number[lhs];
+                    *rhs = number[rhs];
+                    let n = number.len();
+                    number.insert(*dst, n);
+                    *dst = n;
+                }
+                Instruction::Pop { dst, .. }
+                | Instruction::Const { dst, .. }
+                | Instruction::ScanDecimal(dst)
+                | Instruction::ScanUnicode(dst) => {
+                    let n = number.len();
+                    number.insert(*dst, n);
+                    *dst = n;
+                }
+            }
         }
-        if output.is_empty() {
-            self.blocks.push(format!("B{label}:"));
-        } else {
-            self.blocks.push(format!("B{label}:{{\n{output}}}"));
+        self.blocks[label].instructions = instructions;
+    }
+
+    // Computes `lhs op rhs` into `dst`, as a constant if both operands are known and the result
+    // does not depend on how the program runs.
+    fn binary(
+        &self,
+        known: &mut HashMap<Register, Integer>,
+        dst: Register,
+        op: BinaryOp,
+        lhs: Register,
+        rhs: Register,
+        cell: (usize, usize),
+    ) -> Instruction {
+        let value = match (known.get(&lhs), known.get(&rhs)) {
+            (Some(&a), Some(&b)) => self.fold(op, a, b),
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                known.insert(dst, value);
+                Instruction::Const { dst, value }
+            }
+            None => Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            },
         }
     }
+
+    // Evaluates `lhs op rhs` like the program would, or returns `None` if it would stop there.
+    fn fold(&self, op: BinaryOp, lhs: Integer, rhs: Integer) -> Option<Integer> {
+        let options = self.options;
+        if matches!(op, BinaryOp::Divide | BinaryOp::Remainder) && rhs == 0 {
+            return (options.zero_division == ZeroDivision::Zero).then_some(0);
+        }
+        if options.bigint {
+            // Any backend can write a 64-bit literal.
+            return op.checked(lhs, rhs).filter(|v| i64::try_from(*v).is_ok());
+        }
+        match options.overflow {
+            Overflow::Trap => op.checked(lhs, rhs).filter(|&v| options.width.wrap(v) == v),
+            Overflow::Undefined | Overflow::Wrap => Some(options.width.wrap(op.wrapping(lhs, rhs))),
+        }
+    }
+}
+
+// Lower bounds of the storage sizes to start the next block with, given the ones at the end of
+// this block. The host may change the ㅎ storage in between. No consonant requires more than 2
+// values, so larger bounds would only start more copies of the same block.
+fn carried(size: &Sizes) -> Sizes {
+    let mut size = size.map(|size| size.min(2));
+    size[STREAM] = 0;
+    size
+}
+
+// Like `carried`, when `storage` is also known to have at least `n` values.
+fn at_least(size: &Sizes, storage: usize, n: usize) -> Sizes {
+    let mut size = carried(size);
+    size[storage] = size[storage].max(n);
+    size
+}
+
+// Pops from the values of the queue taken from its front, or of a stack pushed or taken from its
+// top.
+fn pop_var(
+    var: &mut [VecDeque<Register>],
+    front: &mut VecDeque<Register>,
+    storage: usize,
+) -> Register {
+    if storage == QUEUE {
+        front.pop_front().unwrap()
+    } else {
+        var[storage].pop_back().unwrap()
+    }
 }
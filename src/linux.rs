@@ -0,0 +1,538 @@
+// Runtime for x86-64 Linux, using raw system calls instead of libc.
+//
+// Functions take arguments in %rdi and %rsi, return in %rax, and clobber only %rax, %rcx, %rdx,
+// %rsi, %rdi and %r11, so the generated code keeps its values in the other registers across calls.
+//
+// A storage is a ring buffer {buffer, head, length, capacity} of 8-byte slots. Stacks, including
+// the ㅎ storage, keep their head at 0 and are pushed and popped inline by the generated code.
+
+use crate::x86::Alu::*;
+use crate::x86::Inst::{self, *};
+use crate::x86::Reg::*;
+use crate::x86::Shift::*;
+use crate::x86::Size::*;
+use crate::x86::Unary::*;
+use crate::x86::{imm, indexed, mem, rip, Cond, Program, Reg, Symbol};
+
+// Size of the output and input buffers.
+const BUFFER: i64 = 65536;
+
+const READ: i64 = 0;
+const WRITE: i64 = 1;
+const MMAP: i64 = 9;
+const MUNMAP: i64 = 11;
+const GETPID: i64 = 39;
+const KILL: i64 = 62;
+const EXIT_GROUP: i64 = 231;
+
+// Appends the runtime functions and its zeroed data to `program`.
+pub(crate) fn runtime(program: &mut Program) {
+    program.bss.extend([
+        (Symbol::Named("storage"), 28 * 32),
+        (Symbol::Named("output"), BUFFER as usize),
+        (Symbol::Named("output_len"), 8),
+        (Symbol::Named("input"), BUFFER as usize),
+        (Symbol::Named("input_off"), 8),
+        (Symbol::Named("input_len"), 8),
+        (Symbol::Named("input_eof"), 8),
+    ]);
+    for function in [
+        alloc,
+        grow,
+        push_back,
+        push_front,
+        peek_front,
+        pop_front,
+        flush,
+        reserve,
+        print_decimal,
+        print_utf8,
+        peek_byte,
+        scan_decimal,
+        scan_utf8,
+        halt,
+        trap,
+    ] {
+        function(program);
+    }
+}
+
+fn label(name: &'static str) -> Inst {
+    Label(Symbol::Named(name))
+}
+
+fn call(name: &'static str) -> Inst {
+    Call(Symbol::Named(name))
+}
+
+fn syscall(number: i64) -> [Inst; 2] {
+    [Mov(L, imm(number), Rax.into()), Syscall]
+}
+
+// Zeroes the lower 32 bits, and with them the whole of `reg`.
+fn zero(reg: Reg) -> Inst {
+    Alu(Xor, L, reg.into(), reg.into())
+}
+
+// Maps %rdi bytes of zeroed memory.
+fn alloc(p: &mut Program) {
+    p.extend([
+        label("alloc"),
+        Push(R8),
+        Push(R9),
+        Push(R10),
+        Mov(Q, Rdi.into(), Rsi.into()),
+        zero(Rdi),
+        Mov(L, imm(3), Rdx.into()),
+        Mov(L, imm(0x22), R10.into()),
+        Mov(Q, imm(-1), R8.into()),
+        zero(R9),
+    ]);
+    p.extend(syscall(MMAP));
+    p.extend([Pop(R10), Pop(R9), Pop(R8), Ret]);
+}
+
+// Doubles the capacity of the storage at %rdi, moving its values to the front of a new buffer.
+fn grow(p: &mut Program) {
+    let [sized, copy, check, freed] = p.locals();
+    p.extend([
+        label("grow"),
+        Push(Rbx),
+        Push(R12),
+        Mov(Q, Rdi.into(), Rbx.into()),
+        Mov(Q, mem(Rbx, 24), R12.into()),
+        Shift(Shl, Q, 1, R12.into()),
+        J(Cond::Ne, sized),
+        Mov(L, imm(512), R12.into()),
+        Label(sized),
+        Lea(indexed(None, R12, 8, 0), Rdi),
+        call("alloc"),
+        Mov(Q, mem(Rbx, 0), Rsi.into()),
+        Mov(Q, mem(Rbx, 8), Rdx.into()),
+        zero(Rcx),
+        Jmp(check),
+        Label(copy),
+        Mov(Q, indexed(Some(Rsi), Rdx, 8, 0), Rdi.into()),
+        Mov(Q, Rdi.into(), indexed(Some(Rax), Rcx, 8, 0)),
+        Unary(Inc, Q, Rcx.into()),
+        Unary(Inc, Q, Rdx.into()),
+        Alu(Cmp, Q, mem(Rbx, 24), Rdx.into()),
+        J(Cond::B, check),
+        zero(Rdx),
+        Label(check),
+        Alu(Cmp, Q, mem(Rbx, 16), Rcx.into()),
+        J(Cond::B, copy),
+        Mov(Q, Rsi.into(), Rdi.into()),
+        Mov(Q, Rax.into(), mem(Rbx, 0)),
+        Mov(Q, imm(0), mem(Rbx, 8)),
+        Mov(Q, mem(Rbx, 24), Rsi.into()),
+        Mov(Q, R12.into(), mem(Rbx, 24)),
+        Test(Q, Rsi.into(), Rsi.into()),
+        J(Cond::E, freed),
+        Shift(Shl, Q, 3, Rsi.into()),
+    ]);
+    p.extend(syscall(MUNMAP));
+    p.extend([Label(freed), Pop(R12), Pop(Rbx), Ret]);
+}
+
+// Grows the storage at %rdi if it is full, keeping %rdi and %rsi, and goes on at `room`.
+fn make_room(room: Symbol) -> [Inst; 9] {
+    [
+        Mov(Q, mem(Rdi, 16), Rax.into()),
+        Alu(Cmp, Q, mem(Rdi, 24), Rax.into()),
+        J(Cond::B, room),
+        Push(Rsi),
+        Push(Rdi),
+        call("grow"),
+        Pop(Rdi),
+        Pop(Rsi),
+        Mov(Q, mem(Rdi, 16), Rax.into()),
+    ]
+}
+
+// Pushes %rsi to the back of the storage at %rdi.
+fn push_back(p: &mut Program) {
+    let [room, wrapped] = p.locals();
+    p.push(label("push_back"));
+    p.extend(make_room(room));
+    p.extend([
+        Label(room),
+        Alu(Add, Q, mem(Rdi, 8), Rax.into()),
+        Alu(Cmp, Q, mem(Rdi, 24), Rax.into()),
+        J(Cond::B, wrapped),
+        Alu(Sub, Q, mem(Rdi, 24), Rax.into()),
+        Label(wrapped),
+        Mov(Q, mem(Rdi, 0), Rcx.into()),
+        Mov(Q, Rsi.into(), indexed(Some(Rcx), Rax, 8, 0)),
+        Unary(Inc, Q, mem(Rdi, 16)),
+        Ret,
+    ]);
+}
+
+// Pushes %rsi to the front of the storage at %rdi.
+fn push_front(p: &mut Program) {
+    let [room, wrapped] = p.locals();
+    p.push(label("push_front"));
+    p.extend(make_room(room));
+    p.extend([
+        Label(room),
+        Mov(Q, mem(Rdi, 8), Rax.into()),
+        Test(Q, Rax.into(), Rax.into()),
+        J(Cond::Ne, wrapped),
+        Mov(Q, mem(Rdi, 24), Rax.into()),
+        Label(wrapped),
+        Unary(Dec, Q, Rax.into()),
+        Mov(Q, Rax.into(), mem(Rdi, 8)),
+        Mov(Q, mem(Rdi, 0), Rcx.into()),
+        Mov(Q, Rsi.into(), indexed(Some(Rcx), Rax, 8, 0)),
+        Unary(Inc, Q, mem(Rdi, 16)),
+        Ret,
+    ]);
+}
+
+// Loads the value at the head of the storage at %rdi to %rax, and the head to %rcx.
+fn front() -> [Inst; 3] {
+    [
+        Mov(Q, mem(Rdi, 8), Rcx.into()),
+        Mov(Q, mem(Rdi, 0), Rax.into()),
+        Mov(Q, indexed(Some(Rax), Rcx, 8, 0), Rax.into()),
+    ]
+}
+
+fn peek_front(p: &mut Program) {
+    p.push(label("peek_front"));
+    p.extend(front());
+    p.push(Ret);
+}
+
+fn pop_front(p: &mut Program) {
+    let [wrapped] = p.locals();
+    p.push(label("pop_front"));
+    p.extend(front());
+    p.extend([
+        Unary(Inc, Q, Rcx.into()),
+        Alu(Cmp, Q, mem(Rdi, 24), Rcx.into()),
+        J(Cond::B, wrapped),
+        zero(Rcx),
+        Label(wrapped),
+        Mov(Q, Rcx.into(), mem(Rdi, 8)),
+        Unary(Dec, Q, mem(Rdi, 16)),
+        Ret,
+    ]);
+}
+
+fn flush(p: &mut Program) {
+    let [write, done] = p.locals();
+    p.extend([
+        label("flush"),
+        Lea(rip("output", 0), Rsi),
+        Mov(Q, rip("output_len", 0), Rdx.into()),
+        Label(write),
+        Test(Q, Rdx.into(), Rdx.into()),
+        J(Cond::E, done),
+        Mov(L, imm(1), Rdi.into()),
+    ]);
+    p.extend(syscall(WRITE));
+    p.extend([
+        Test(Q, Rax.into(), Rax.into()),
+        J(Cond::Le, done),
+        Alu(Add, Q, Rax.into(), Rsi.into()),
+        Alu(Sub, Q, Rax.into(), Rdx.into()),
+        Jmp(write),
+        Label(done),
+        Mov(Q, imm(0), rip("output_len", 0)),
+        Ret,
+    ]);
+}
+
+// Makes room for %rdi bytes in the output buffer, and returns the current length in %rax.
+fn reserve(p: &mut Program) {
+    let [done] = p.locals();
+    p.extend([
+        label("reserve"),
+        Mov(Q, rip("output_len", 0), Rax.into()),
+        Alu(Add, Q, Rax.into(), Rdi.into()),
+        Alu(Cmp, Q, imm(BUFFER), Rdi.into()),
+        J(Cond::Be, done),
+        call("flush"),
+        zero(Rax),
+        Label(done),
+        Ret,
+    ]);
+}
+
+// Reserves `size` bytes in the output buffer keeping %rdi, and points %rsi to them.
+fn reserved(size: i64) -> [Inst; 6] {
+    [
+        Push(Rdi),
+        Mov(L, imm(size), Rdi.into()),
+        call("reserve"),
+        Pop(Rdi),
+        Lea(rip("output", 0), Rsi),
+        Alu(Add, Q, Rax.into(), Rsi.into()),
+    ]
+}
+
+fn print_decimal(p: &mut Program) {
+    let [positive, digit, copy] = p.locals();
+    p.push(label("print_decimal"));
+    p.extend(reserved(21));
+    p.extend([
+        Mov(Q, Rdi.into(), Rax.into()),
+        Test(Q, Rax.into(), Rax.into()),
+        J(Cond::Ns, positive),
+        Mov(B, imm(b'-' as i64), mem(Rsi, 0)),
+        Unary(Inc, Q, Rsi.into()),
+        Unary(Neg, Q, Rax.into()),
+        // The digits are written backwards on the stack, then copied.
+        Label(positive),
+        Alu(Sub, Q, imm(24), Rsp.into()),
+        Lea(mem(Rsp, 24), Rdi),
+        Mov(L, imm(10), Rcx.into()),
+        Label(digit),
+        zero(Rdx),
+        Unary(Div, Q, Rcx.into()),
+        Alu(Add, L, imm(b'0' as i64), Rdx.into()),
+        Unary(Dec, Q, Rdi.into()),
+        Mov(B, Rdx.into(), mem(Rdi, 0)),
+        Test(Q, Rax.into(), Rax.into()),
+        J(Cond::Ne, digit),
+        Lea(mem(Rsp, 24), Rdx),
+        Label(copy),
+        Mov(B, mem(Rdi, 0), Rax.into()),
+        Mov(B, Rax.into(), mem(Rsi, 0)),
+        Unary(Inc, Q, Rsi.into()),
+        Unary(Inc, Q, Rdi.into()),
+        Alu(Cmp, Q, Rdx.into(), Rdi.into()),
+        J(Cond::B, copy),
+        Alu(Add, Q, imm(24), Rsp.into()),
+        Lea(rip("output", 0), Rax),
+        Alu(Sub, Q, Rax.into(), Rsi.into()),
+        Mov(Q, Rsi.into(), rip("output_len", 0)),
+        Ret,
+    ]);
+}
+
+// Code points are encoded even if they are surrogates, as in the C backend.
+fn print_utf8(p: &mut Program) {
+    // Code points below each limit are encoded in as many bytes, starting with the prefix.
+    const LENGTHS: [(i64, i64); 4] = [(0x80, 0), (0x800, 0xc0), (0x10000, 0xe0), (0x110000, 0xf0)];
+    let lengths: [Symbol; 4] = p.locals();
+    let [done, invalid] = p.locals();
+    p.extend([
+        label("print_utf8"),
+        Alu(Cmp, Q, imm(LENGTHS[3].0 - 1), Rdi.into()),
+        J(Cond::A, invalid),
+    ]);
+    p.extend(reserved(4));
+    for (n, (limit, prefix)) in LENGTHS.into_iter().enumerate() {
+        p.push(Label(lengths[n]));
+        if n < 3 {
+            p.extend([
+                Alu(Cmp, L, imm(limit), Rdi.into()),
+                J(Cond::Ae, lengths[n + 1]),
+            ]);
+        }
+        for k in 0..=n {
+            let shift = 6 * (n - k) as u8;
+            p.push(Mov(L, Rdi.into(), Rcx.into()));
+            if shift > 0 {
+                p.push(Shift(Shr, L, shift, Rcx.into()));
+            }
+            if k > 0 {
+                p.extend([
+                    Alu(And, L, imm(0x3f), Rcx.into()),
+                    Alu(Or, L, imm(0x80), Rcx.into()),
+                ]);
+            } else if prefix != 0 {
+                p.push(Alu(Or, L, imm(prefix), Rcx.into()));
+            }
+            p.push(Mov(B, Rcx.into(), mem(Rsi, k as i32)));
+        }
+        p.extend([Alu(Add, Q, imm(n as i64 + 1), Rax.into()), Jmp(done)]);
+    }
+    p.extend([
+        Label(done),
+        Mov(Q, Rax.into(), rip("output_len", 0)),
+        Label(invalid),
+        Ret,
+    ]);
+}
+
+// Returns the next input byte as a `signed char`, or -1 at the end of the input.
+fn peek_byte(p: &mut Program) {
+    let [buffered, end, filled] = p.locals();
+    p.extend([
+        label("peek_byte"),
+        Mov(Q, rip("input_off", 0), Rax.into()),
+        Alu(Cmp, Q, rip("input_len", 0), Rax.into()),
+        J(Cond::B, buffered),
+        Alu(Cmp, B, imm(0), rip("input_eof", 0)),
+        J(Cond::Ne, end),
+        zero(Rdi),
+        Lea(rip("input", 0), Rsi),
+        Mov(L, imm(BUFFER), Rdx.into()),
+    ]);
+    p.extend(syscall(READ));
+    p.extend([
+        Test(Q, Rax.into(), Rax.into()),
+        J(Cond::G, filled),
+        Mov(B, imm(1), rip("input_eof", 0)),
+        Label(end),
+        Mov(Q, imm(-1), Rax.into()),
+        Ret,
+        Label(filled),
+        Mov(Q, Rax.into(), rip("input_len", 0)),
+        Mov(Q, imm(0), rip("input_off", 0)),
+        zero(Rax),
+        Label(buffered),
+        Lea(rip("input", 0), Rcx),
+        Movsbq(indexed(Some(Rcx), Rax, 1, 0), Rax),
+        Ret,
+    ]);
+}
+
+fn consume() -> Inst {
+    Unary(Inc, Q, rip("input_off", 0))
+}
+
+fn scan_decimal(p: &mut Program) {
+    let [skip, sign, start, digit, check, end, done] = p.locals();
+    p.extend([
+        label("scan_decimal"),
+        Push(Rbx),
+        Push(R12),
+        Label(skip),
+        call("peek_byte"),
+        Alu(Cmp, Q, imm(-1), Rax.into()),
+        J(Cond::E, done),
+        Alu(Cmp, Q, imm(b' ' as i64), Rax.into()),
+        J(Cond::G, sign),
+        consume(),
+        Jmp(skip),
+        Label(sign),
+        zero(R12),
+        Alu(Cmp, Q, imm(b'-' as i64), Rax.into()),
+        J(Cond::Ne, start),
+        Mov(L, imm(1), R12.into()),
+        consume(),
+        call("peek_byte"),
+        Label(start),
+        zero(Rbx),
+        Jmp(check),
+        Label(digit),
+        ImulImm(Q, 10, Rbx.into(), Rbx),
+        Lea(indexed(Some(Rbx), Rax, 1, -(b'0' as i32)), Rbx),
+        consume(),
+        call("peek_byte"),
+        Label(check),
+        Alu(Cmp, Q, imm(b'0' as i64), Rax.into()),
+        J(Cond::L, end),
+        Alu(Cmp, Q, imm(b'9' as i64), Rax.into()),
+        J(Cond::Le, digit),
+        Label(end),
+        Mov(Q, Rbx.into(), Rax.into()),
+        Test(L, R12.into(), R12.into()),
+        J(Cond::E, done),
+        Unary(Neg, Q, Rax.into()),
+        Label(done),
+        Pop(R12),
+        Pop(Rbx),
+        Ret,
+    ]);
+}
+
+fn scan_utf8(p: &mut Program) {
+    // Lead bytes without the bit are followed by as many continuation bytes, and keep the mask.
+    const LEADS: [(i64, i64); 3] = [(0x20, 0x1f), (0x10, 0x0f), (0, 0x07)];
+    let [continuation, check, done] = p.locals();
+    p.extend([
+        label("scan_utf8"),
+        Push(Rbx),
+        Push(R12),
+        call("peek_byte"),
+        consume(),
+        Alu(Cmp, Q, imm(-1), Rax.into()),
+        J(Cond::E, done),
+        Movzbl(Rax.into(), Rbx),
+        zero(R12),
+        Test(B, imm(0x80), Rbx.into()),
+        J(Cond::E, check),
+    ]);
+    for (count, (bit, mask)) in LEADS.into_iter().enumerate() {
+        let [next] = p.locals();
+        if bit != 0 {
+            p.extend([Test(B, imm(bit), Rbx.into()), J(Cond::Ne, next)]);
+        }
+        p.extend([
+            Alu(And, L, imm(mask), Rbx.into()),
+            Mov(L, imm(count as i64 + 1), R12.into()),
+            Jmp(check),
+        ]);
+        if bit != 0 {
+            p.push(Label(next));
+        }
+    }
+    p.extend([
+        Label(continuation),
+        call("peek_byte"),
+        consume(),
+        Alu(And, L, imm(0x3f), Rax.into()),
+        Shift(Shl, L, 6, Rbx.into()),
+        Alu(Or, L, Rax.into(), Rbx.into()),
+        Unary(Dec, L, R12.into()),
+        Label(check),
+        Test(L, R12.into(), R12.into()),
+        J(Cond::Ne, continuation),
+        Mov(Q, Rbx.into(), Rax.into()),
+        Label(done),
+        Pop(R12),
+        Pop(Rbx),
+        Ret,
+    ]);
+}
+
+// `halt_back` and `halt_front` exit with a value popped from the storage at %rdi, or 0 if it is
+// empty. `exit` exits with %eax after flushing the output.
+fn halt(p: &mut Program) {
+    let exit = Symbol::Named("exit");
+    p.extend([
+        label("halt_back"),
+        zero(Rax),
+        Mov(Q, mem(Rdi, 16), Rcx.into()),
+        Test(Q, Rcx.into(), Rcx.into()),
+        J(Cond::E, exit),
+        Mov(Q, mem(Rdi, 0), Rax.into()),
+        Mov(Q, indexed(Some(Rax), Rcx, 8, -8), Rax.into()),
+        Jmp(exit),
+        label("halt_front"),
+        zero(Rax),
+        Alu(Cmp, Q, imm(0), mem(Rdi, 16)),
+        J(Cond::E, exit),
+        call("pop_front"),
+        Label(exit),
+        Push(Rax),
+        call("flush"),
+        Pop(Rdi),
+    ]);
+    p.extend(syscall(EXIT_GROUP));
+}
+
+// Prints the %rdx bytes at %rsi to the standard error, and aborts.
+fn trap(p: &mut Program) {
+    p.extend([
+        label("trap"),
+        Push(Rsi),
+        Push(Rdx),
+        call("flush"),
+        Pop(Rdx),
+        Pop(Rsi),
+        Mov(L, imm(2), Rdi.into()),
+    ]);
+    p.extend(syscall(WRITE));
+    p.extend(syscall(GETPID));
+    p.extend([Mov(Q, Rax.into(), Rdi.into()), Mov(L, imm(6), Rsi.into())]);
+    p.extend(syscall(KILL));
+    p.push(Mov(L, imm(134), Rdi.into()));
+    p.extend(syscall(EXIT_GROUP));
+}
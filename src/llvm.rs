@@ -0,0 +1,251 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{Options, Overflow, StorageKind, ZeroDivision};
+
+pub(crate) fn emit(blocks: &[Block], options: &Options) -> String {
+    let bits = options.width.bits();
+    // The runtime is written against the placeholder type `<int>`.
+    let mut output = include_str!("header.ll").replace("<int>", &format!("i{bits}"));
+    output.push_str("\ndefine i32 @main() {\nentry:\n  br label %B0\n");
+    for (label, block) in blocks.iter().enumerate() {
+        emit_block(&mut output, label, block, options);
+    }
+    output.push_str("}\n");
+    output
+}
+
+fn emit_block(output: &mut String, label: usize, block: &Block, options: &Options) {
+    let int = format!("i{}", options.width.bits());
+    let v = |register: Register| format!("%b{label}v{register}");
+    writeln!(output, "B{label}:").ok();
+    for instruction in &block.instructions {
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                writeln!(output, "  {} = call {int} {}", v(dst), pop(storage)).ok();
+            }
+            Instruction::Push { storage, src } => {
+                if StorageKind::from(storage) == StorageKind::Stream {
+                    writeln!(output, "  call void @aheui_ext_push({int} {})", v(src)).ok();
+                } else {
+                    writeln!(
+                        output,
+                        "  call void @push_back(ptr {}, {int} {})",
+                        address(storage),
+                        v(src)
+                    )
+                    .ok();
+                }
+            }
+            Instruction::PushFront { storage, src } => {
+                writeln!(
+                    output,
+                    "  call void @push_front(ptr {}, {int} {})",
+                    address(storage),
+                    v(src)
+                )
+                .ok();
+            }
+            Instruction::Const { dst, value } => {
+                writeln!(output, "  {} = add {int} 0, {value}", v(dst)).ok();
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                binary(output, &int, &v(dst), op, &v(lhs), &v(rhs), cell, options);
+            }
+            Instruction::PrintDecimal(src) => {
+                writeln!(output, "  call void @print_decimal({int} {})", v(src)).ok();
+            }
+            Instruction::PrintUnicode(src) => {
+                writeln!(output, "  call void @print_utf8({int} {})", v(src)).ok();
+            }
+            Instruction::ScanDecimal(dst) => {
+                writeln!(output, "  {} = call {int} @scan_decimal()", v(dst)).ok();
+            }
+            Instruction::ScanUnicode(dst) => {
+                writeln!(output, "  {} = call {int} @scan_utf8()", v(dst)).ok();
+            }
+        }
+    }
+    match block.terminator {
+        Terminator::Goto(target) => {
+            writeln!(output, "  br label %B{target}").ok();
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            if StorageKind::from(storage) == StorageKind::Stream {
+                writeln!(output, "  %b{label}.size = call i32 @aheui_ext_size()").ok();
+                writeln!(
+                    output,
+                    "  %b{label}.less = icmp slt i32 %b{label}.size, {size}"
+                )
+                .ok();
+            } else {
+                writeln!(
+                    output,
+                    "  %b{label}.size = call i64 @size(ptr {})",
+                    address(storage)
+                )
+                .ok();
+                writeln!(
+                    output,
+                    "  %b{label}.less = icmp ult i64 %b{label}.size, {size}"
+                )
+                .ok();
+            }
+            writeln!(
+                output,
+                "  br i1 %b{label}.less, label %B{less}, label %B{otherwise}"
+            )
+            .ok();
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            writeln!(output, "  %b{label}.cond = call {int} {}", pop(storage)).ok();
+            writeln!(
+                output,
+                "  switch {int} %b{label}.cond, label %B{nonzero} [ {int} 0, label %B{zero} ]"
+            )
+            .ok();
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            let call = match StorageKind::from(storage) {
+                StorageKind::Stack => format!("@peek_back(ptr {})", address(storage)),
+                StorageKind::Queue => format!("@peek_front(ptr {})", address(storage)),
+                StorageKind::Stream => "@aheui_ext_peek()".to_string(),
+            };
+            writeln!(output, "  %b{label}.cond = call {int} {call}").ok();
+            writeln!(
+                output,
+                "  switch {int} %b{label}.cond, label %B{otherwise} [ {int} 0, label %B{zero} ]"
+            )
+            .ok();
+        }
+        Terminator::Halt { storage } => {
+            let call = match StorageKind::from(storage) {
+                StorageKind::Stack => format!("@halt_back(ptr {})", address(storage)),
+                StorageKind::Queue => format!("@halt_front(ptr {})", address(storage)),
+                StorageKind::Stream => "@halt_ext()".to_string(),
+            };
+            writeln!(output, "  %b{label}.exit = call {int} {call}").ok();
+            if options.width.bits() > 32 {
+                writeln!(
+                    output,
+                    "  %b{label}.code = trunc {int} %b{label}.exit to i32"
+                )
+                .ok();
+                writeln!(output, "  ret i32 %b{label}.code").ok();
+            } else {
+                writeln!(output, "  ret i32 %b{label}.exit").ok();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn binary(
+    output: &mut String,
+    int: &str,
+    dst: &str,
+    op: BinaryOp,
+    lhs: &str,
+    rhs: &str,
+    (r, c): (usize, usize),
+    options: &Options,
+) {
+    let (r, c) = (r + 1, c + 1);
+    if op == BinaryOp::Compare {
+        // Comparison never overflows.
+        writeln!(output, "  {dst}.ge = icmp sge {int} {lhs}, {rhs}").ok();
+        writeln!(output, "  {dst} = zext i1 {dst}.ge to {int}").ok();
+        return;
+    }
+    let division = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+    // With `ZeroDivision::Zero`, a zero divisor is replaced by `1` and the result by `0`.
+    let (divisor, result) = match options.zero_division {
+        ZeroDivision::Trap if division => {
+            writeln!(
+                output,
+                "  call void @check_zero({int} {rhs}, i32 {r}, i32 {c})"
+            )
+            .ok();
+            (rhs.to_string(), dst.to_string())
+        }
+        ZeroDivision::Zero if division => {
+            writeln!(output, "  {dst}.zero = icmp eq {int} {rhs}, 0").ok();
+            writeln!(
+                output,
+                "  {dst}.divisor = select i1 {dst}.zero, {int} 1, {int} {rhs}"
+            )
+            .ok();
+            (format!("{dst}.divisor"), format!("{dst}.result"))
+        }
+        // Reflection happens before the division, in a separate block.
+        _ => (rhs.to_string(), dst.to_string()),
+    };
+    let expr = match (options.overflow, op) {
+        (Overflow::Undefined, BinaryOp::Add) => format!("add nsw {int} {lhs}, {divisor}"),
+        (Overflow::Undefined, BinaryOp::Subtract) => format!("sub nsw {int} {lhs}, {divisor}"),
+        (Overflow::Undefined, BinaryOp::Multiply) => format!("mul nsw {int} {lhs}, {divisor}"),
+        (Overflow::Undefined, BinaryOp::Divide) => format!("sdiv {int} {lhs}, {divisor}"),
+        (Overflow::Undefined, BinaryOp::Remainder) => format!("srem {int} {lhs}, {divisor}"),
+        (Overflow::Wrap, BinaryOp::Add) => format!("add {int} {lhs}, {divisor}"),
+        (Overflow::Wrap, BinaryOp::Subtract) => format!("sub {int} {lhs}, {divisor}"),
+        (Overflow::Wrap, BinaryOp::Multiply) => format!("mul {int} {lhs}, {divisor}"),
+        (Overflow::Wrap, BinaryOp::Divide) => {
+            format!("call {int} @wrapping_div({int} {lhs}, {int} {divisor})")
+        }
+        // The remainder of `MIN / -1` is `0`, even though the quotient overflows.
+        (_, BinaryOp::Remainder) => {
+            format!("call {int} @wrapping_rem({int} {lhs}, {int} {divisor})")
+        }
+        (Overflow::Trap, _) => {
+            let name = match op {
+                BinaryOp::Add => "add",
+                BinaryOp::Subtract => "sub",
+                BinaryOp::Multiply => "mul",
+                _ => "div",
+            };
+            format!("call {int} @checked_{name}({int} {lhs}, {int} {divisor}, i32 {r}, i32 {c})")
+        }
+        (_, BinaryOp::Compare) => unreachable!(),
+    };
+    writeln!(output, "  {result} = {expr}").ok();
+    if result != dst {
+        writeln!(
+            output,
+            "  {dst} = select i1 {dst}.zero, {int} 0, {int} {result}"
+        )
+        .ok();
+    }
+}
+
+// Call popping a value from `storage`.
+fn pop(storage: usize) -> String {
+    match StorageKind::from(storage) {
+        StorageKind::Stack => format!("@pop_back(ptr {})", address(storage)),
+        StorageKind::Queue => format!("@pop_front(ptr {})", address(storage)),
+        StorageKind::Stream => "@aheui_ext_pop()".to_string(),
+    }
+}
+
+// Constant address of the `storage`th element of `@storage`.
+fn address(storage: usize) -> String {
+    format!("getelementptr ([28 x %Storage], ptr @storage, i64 0, i64 {storage})")
+}
@@ -1,19 +1,196 @@
-use clap::Parser;
+use std::io::{self, Read, Write};
+
+use clap::{Parser, Subcommand};
+use palheui::{Error, Options, Overflow, Width, WrapMode, ZeroDivision};
 
 /// Simple Ahui to Readable C transpiler
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// source file, enter "-" to read from stdin
-    input: String,
+    #[arg(required = true)]
+    input: Option<String>,
+    /// use arbitrary-precision integers in the generated code
+    #[arg(long, conflicts_with_all = ["width", "overflow"])]
+    bigint: bool,
+    /// language of the generated code
+    #[arg(long, value_name = "LANG", value_parser = ["c", "llvm", "wat", "wasm", "rust", "js", "python", "asm", "exe"], default_value = "c")]
+    emit: String,
+    #[command(flatten)]
+    execution: ExecutionArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the program with the built-in interpreter instead of transpiling it
+    Run {
+        /// source file, enter "-" to read from stdin
+        input: String,
+        /// compile the program to native code before running it
+        #[cfg(feature = "jit")]
+        #[arg(long, conflicts_with = "threaded")]
+        jit: bool,
+        /// compile the program to closures before running it
+        #[arg(long)]
+        threaded: bool,
+        #[command(flatten)]
+        execution: ExecutionArgs,
+    },
+    /// Compile the program to bytecode, which `exec` runs without the source
+    Compile {
+        /// source file, enter "-" to read from stdin
+        input: String,
+        /// format of the compiled program
+        #[arg(long, value_name = "FORMAT", value_parser = ["bytecode"], default_value = "bytecode")]
+        emit: String,
+        #[command(flatten)]
+        execution: ExecutionArgs,
+    },
+    /// Run bytecode written by `compile`
+    Exec {
+        /// bytecode file, enter "-" to read from stdin
+        input: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct ExecutionArgs {
+    /// bit width of integers
+    #[arg(long, value_name = "BITS", value_parser = ["32", "64", "128"])]
+    width: Option<String>,
+    /// behaviour of arithmetic overflow
+    #[arg(long, value_name = "MODE", value_parser = ["undefined", "wrap", "trap"])]
+    overflow: Option<String>,
+    /// behaviour of division by zero
+    #[arg(long, value_name = "MODE", value_parser = ["undefined", "trap", "zero", "reflect"])]
+    zero_division: Option<String>,
+    /// how the cursor wraps around the code
+    #[arg(long, value_name = "MODE", value_parser = ["rectangle", "spec"])]
+    wrap: Option<String>,
+}
+
+impl ExecutionArgs {
+    fn options(&self) -> Options {
+        let width = match self.width.as_deref() {
+            Some("32") => Width::W32,
+            Some("128") => Width::W128,
+            _ => Width::W64,
+        };
+        let overflow = match self.overflow.as_deref() {
+            Some("wrap") => Overflow::Wrap,
+            Some("trap") => Overflow::Trap,
+            _ => Overflow::Undefined,
+        };
+        let zero_division = match self.zero_division.as_deref() {
+            Some("trap") => ZeroDivision::Trap,
+            Some("zero") => ZeroDivision::Zero,
+            Some("reflect") => ZeroDivision::Reflect,
+            _ => ZeroDivision::Undefined,
+        };
+        let wrap = match self.wrap.as_deref() {
+            Some("spec") => WrapMode::Spec,
+            _ => WrapMode::Rectangle,
+        };
+        Options {
+            width,
+            overflow,
+            zero_division,
+            wrap,
+            ..Options::default()
+        }
+    }
+}
+
+fn read_source(input: &str) -> Result<String, Error> {
+    if input == "-" {
+        Ok(io::read_to_string(io::stdin())?)
+    } else {
+        std::fs::read_to_string(input)
+            .map_err(|e| io::Error::new(e.kind(), format!("{input}: {e}")).into())
+    }
+}
+
+fn read_bytecode(input: &str) -> Result<Vec<u8>, Error> {
+    if input == "-" {
+        let mut bytecode = vec![];
+        io::stdin().read_to_end(&mut bytecode)?;
+        Ok(bytecode)
+    } else {
+        std::fs::read(input).map_err(|e| io::Error::new(e.kind(), format!("{input}: {e}")).into())
+    }
+}
+
+fn execute(args: Args) -> Result<i32, Error> {
+    match args.command {
+        Some(Command::Run {
+            input,
+            execution,
+            #[cfg(feature = "jit")]
+            jit,
+            threaded,
+        }) => {
+            let content = read_source(&input)?;
+            let options = execution.options();
+            #[cfg(feature = "jit")]
+            if jit {
+                let code = palheui::jit::run(&content, io::stdin(), io::stdout(), &options)?;
+                return Ok(code as i32);
+            }
+            let code = if threaded {
+                palheui::threaded::run(&content, io::stdin(), io::stdout(), &options)?
+            } else {
+                palheui::interpret_with(&content, io::stdin(), io::stdout(), &options)?
+            };
+            Ok(code as i32)
+        }
+        Some(Command::Compile {
+            input,
+            emit: _,
+            execution,
+        }) => {
+            let content = read_source(&input)?;
+            let bytecode = palheui::bytecode::compile(&content, &execution.options())?;
+            io::stdout().write_all(&bytecode)?;
+            Ok(0)
+        }
+        Some(Command::Exec { input }) => {
+            let bytecode = read_bytecode(&input)?;
+            let code = palheui::bytecode::run(&bytecode, io::stdin(), io::stdout())?;
+            Ok(code as i32)
+        }
+        None => {
+            let content = read_source(&args.input.unwrap())?;
+            let options = Options {
+                bigint: args.bigint,
+                ..args.execution.options()
+            };
+            let output = match args.emit.as_str() {
+                "llvm" => palheui::transpile_llvm(&content, &options)?.into_bytes(),
+                "wat" => palheui::transpile_wat(&content, &options)?.into_bytes(),
+                "wasm" => palheui::transpile_wasm(&content, &options)?,
+                "rust" => palheui::transpile_rust(&content, &options)?.into_bytes(),
+                "js" => palheui::transpile_js(&content, &options)?.into_bytes(),
+                "python" => palheui::transpile_python(&content, &options)?.into_bytes(),
+                "asm" => palheui::transpile_asm(&content, &options)?.into_bytes(),
+                "exe" => palheui::transpile_exe(&content, &options)?,
+                _ => palheui::transpile_with(&content, &options)?.into_bytes(),
+            };
+            io::stdout().write_all(&output)?;
+            Ok(0)
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
-    let content = if args.input == "-" {
-        std::io::read_to_string(std::io::stdin()).expect("Could not read from stdin")
-    } else {
-        std::fs::read_to_string(args.input).expect("Could not read from input file")
-    };
-    print!("{}", palheui::transpile(&content));
+    match execute(args) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("palheui: {e}");
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,185 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{Options, Overflow, StorageKind, ZeroDivision};
+
+pub(crate) fn emit(blocks: &[Block], options: &Options) -> String {
+    let bits = if options.bigint {
+        0
+    } else {
+        options.width.bits()
+    };
+    let mut output = format!("BITS = {bits}\n\n");
+    output.push_str(include_str!("header.py"));
+    output.push_str(
+        "
+
+# Each block returns the label of the next block, or `None` after setting the exit code.
+def run(input=sys.stdin.buffer, output=sys.stdout.buffer):
+    io = Io(input, output)
+    # The ㅎ storage is a plain stack.
+    s = [deque() if i == 21 else [] for i in range(28)]
+    code = 0
+",
+    );
+    for (label, block) in blocks.iter().enumerate() {
+        emit_block(&mut output, label, block, options);
+    }
+    output.push_str("\n    blocks = (");
+    for label in 0..blocks.len() {
+        write!(output, "b{label}, ").ok();
+    }
+    output.push_str(
+        ")
+    label = 0
+    while label is not None:
+        label = blocks[label]()
+    io.flush()
+    return code
+
+
+if __name__ == \"__main__\":
+    sys.exit(run())
+",
+    );
+    output
+}
+
+fn emit_block(output: &mut String, label: usize, block: &Block, options: &Options) {
+    writeln!(output, "\n    def b{label}():").ok();
+    for instruction in &block.instructions {
+        output.push_str("        ");
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                writeln!(output, "v{dst} = {}", pop(storage)).ok();
+            }
+            Instruction::Push { storage, src } => {
+                writeln!(output, "s[{storage}].append(v{src})").ok();
+            }
+            Instruction::PushFront { storage, src } => {
+                writeln!(output, "s[{storage}].appendleft(v{src})").ok();
+            }
+            Instruction::Const { dst, value } => {
+                writeln!(output, "v{dst} = {value}").ok();
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell: (r, c),
+            } => {
+                let (r, c) = (r + 1, c + 1);
+                if matches!(op, BinaryOp::Divide | BinaryOp::Remainder)
+                    && options.zero_division == ZeroDivision::Trap
+                {
+                    write!(output, "io.check_zero(v{rhs}, {r}, {c})\n        ").ok();
+                }
+                writeln!(output, "v{dst} = {}", binary(op, lhs, rhs, (r, c), options)).ok();
+            }
+            Instruction::PrintDecimal(src) => {
+                writeln!(output, "io.print_decimal(v{src})").ok();
+            }
+            Instruction::PrintUnicode(src) => {
+                writeln!(output, "io.print_utf8(v{src})").ok();
+            }
+            Instruction::ScanDecimal(dst) => {
+                writeln!(output, "v{dst} = io.scan_decimal()").ok();
+            }
+            Instruction::ScanUnicode(dst) => {
+                writeln!(output, "v{dst} = io.scan_utf8()").ok();
+            }
+        }
+    }
+    output.push_str("        ");
+    match block.terminator {
+        Terminator::Goto(target) => {
+            writeln!(output, "return {target}").ok();
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            writeln!(
+                output,
+                "return {less} if len(s[{storage}]) < {size} else {otherwise}"
+            )
+            .ok();
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            writeln!(
+                output,
+                "return {nonzero} if {} != 0 else {zero}",
+                pop(storage)
+            )
+            .ok();
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            let peek = if StorageKind::from(storage) == StorageKind::Queue {
+                format!("s[{storage}][0]")
+            } else {
+                format!("s[{storage}][-1]")
+            };
+            writeln!(output, "return {zero} if {peek} == 0 else {otherwise}").ok();
+        }
+        Terminator::Halt { storage } => {
+            writeln!(
+                output,
+                "nonlocal code\n        code = exit_code({}) if s[{storage}] else 0",
+                pop(storage)
+            )
+            .ok();
+        }
+    }
+}
+
+// The zero check of `ZeroDivision::Trap` is a separate statement, emitted by the caller.
+fn binary(
+    op: BinaryOp,
+    lhs: Register,
+    rhs: Register,
+    (r, c): (usize, usize),
+    options: &Options,
+) -> String {
+    let expr = match op {
+        BinaryOp::Add => format!("v{lhs} + v{rhs}"),
+        BinaryOp::Subtract => format!("v{lhs} - v{rhs}"),
+        BinaryOp::Multiply => format!("v{lhs} * v{rhs}"),
+        BinaryOp::Divide => format!("div(v{lhs}, v{rhs})"),
+        // The remainder is never larger than its operands.
+        BinaryOp::Remainder => format!("rem(v{lhs}, v{rhs})"),
+        BinaryOp::Compare => return format!("int(v{lhs} >= v{rhs})"),
+    };
+    let expr = if options.bigint || op == BinaryOp::Remainder {
+        expr
+    } else if options.overflow == Overflow::Trap {
+        format!("io.checked({expr}, \"{op}\", {r}, {c})")
+    } else {
+        format!("wrap({expr})")
+    };
+    if matches!(op, BinaryOp::Divide | BinaryOp::Remainder)
+        && options.zero_division == ZeroDivision::Zero
+    {
+        format!("0 if v{rhs} == 0 else {expr}")
+    } else {
+        expr
+    }
+}
+
+fn pop(storage: usize) -> String {
+    if StorageKind::from(storage) == StorageKind::Queue {
+        format!("s[{storage}].popleft()")
+    } else {
+        format!("s[{storage}].pop()")
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Instruction, Register, Terminator};
+use crate::{Options, Overflow, StorageKind, ZeroDivision};
+
+pub(crate) fn emit(blocks: &[Block], options: &Options) -> String {
+    let bits = options.width.bits();
+    let mut output = include_str!("header.rs").to_string();
+    writeln!(output, "\ntype Integer = i{bits};").ok();
+    output.push_str(
+        "
+pub fn run<R: Read, W: Write>(input: R, output: W) -> i32 {
+    let mut io = Io::new(input, output);
+    // `s[21]` is unused, as the queue is `q`. The ㅎ storage is a plain stack.
+    let mut s = vec![Vec::<Integer>::new(); 28];
+    let mut q = VecDeque::<Integer>::new();
+    let mut label = 0;
+    loop {
+        match label {
+",
+    );
+    for (label, block) in blocks.iter().enumerate() {
+        emit_block(&mut output, label, block, options);
+    }
+    output.push_str("            _ => unreachable!(),\n        }\n    }\n}\n");
+    output
+}
+
+fn emit_block(output: &mut String, label: usize, block: &Block, options: &Options) {
+    writeln!(output, "            {label} => {{").ok();
+    for instruction in &block.instructions {
+        output.push_str("                ");
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                writeln!(output, "let v{dst} = {};", pop(storage)).ok();
+            }
+            Instruction::Push { storage, src } => {
+                if StorageKind::from(storage) == StorageKind::Queue {
+                    writeln!(output, "q.push_back(v{src});").ok();
+                } else {
+                    writeln!(output, "s[{storage}].push(v{src});").ok();
+                }
+            }
+            Instruction::PushFront { src, .. } => {
+                writeln!(output, "q.push_front(v{src});").ok();
+            }
+            Instruction::Const { dst, value } => {
+                writeln!(output, "let v{dst}: Integer = {value};").ok();
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                writeln!(
+                    output,
+                    "let v{dst} = {};",
+                    binary(op, lhs, rhs, cell, options)
+                )
+                .ok();
+            }
+            Instruction::PrintDecimal(src) => {
+                writeln!(output, "io.print_decimal(v{src});").ok();
+            }
+            Instruction::PrintUnicode(src) => {
+                writeln!(output, "io.print_utf8(v{src});").ok();
+            }
+            Instruction::ScanDecimal(dst) => {
+                writeln!(output, "let v{dst} = io.scan_decimal();").ok();
+            }
+            Instruction::ScanUnicode(dst) => {
+                writeln!(output, "let v{dst} = io.scan_utf8();").ok();
+            }
+        }
+    }
+    output.push_str("                ");
+    match block.terminator {
+        Terminator::Goto(target) => {
+            writeln!(output, "label = {target};").ok();
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            let len = if StorageKind::from(storage) == StorageKind::Queue {
+                "q.len()".to_string()
+            } else {
+                format!("s[{storage}].len()")
+            };
+            writeln!(
+                output,
+                "label = if {len} < {size} {{ {less} }} else {{ {otherwise} }};"
+            )
+            .ok();
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            writeln!(
+                output,
+                "label = if {} != 0 {{ {nonzero} }} else {{ {zero} }};",
+                pop(storage)
+            )
+            .ok();
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            let peek = if StorageKind::from(storage) == StorageKind::Queue {
+                "q.front()".to_string()
+            } else {
+                format!("s[{storage}].last()")
+            };
+            writeln!(
+                output,
+                "label = if *{peek}.unwrap() == 0 {{ {zero} }} else {{ {otherwise} }};"
+            )
+            .ok();
+        }
+        Terminator::Halt { storage } => {
+            let pop = if StorageKind::from(storage) == StorageKind::Queue {
+                "q.pop_front()".to_string()
+            } else {
+                format!("s[{storage}].pop()")
+            };
+            writeln!(
+                output,
+                "io.flush();\n                return {pop}.unwrap_or(0) as i32;"
+            )
+            .ok();
+        }
+    }
+    output.push_str("            }\n");
+}
+
+fn binary(
+    op: BinaryOp,
+    lhs: Register,
+    rhs: Register,
+    (r, c): (usize, usize),
+    options: &Options,
+) -> String {
+    let (r, c) = (r + 1, c + 1);
+    let (plain, wrap, trap) = match op {
+        BinaryOp::Add => ("+", "wrapping_add", "checked_add"),
+        BinaryOp::Subtract => ("-", "wrapping_sub", "checked_sub"),
+        BinaryOp::Multiply => ("*", "wrapping_mul", "checked_mul"),
+        BinaryOp::Divide => ("/", "wrapping_div", "checked_div"),
+        // The remainder of `MIN / -1` is `0`, even though the quotient overflows.
+        BinaryOp::Remainder => ("%", "wrapping_rem", "wrapping_rem"),
+        // Comparison never overflows.
+        BinaryOp::Compare => return format!("(v{lhs} >= v{rhs}) as Integer"),
+    };
+    let division = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+    // A checked divisor is used through the value the check returns, so that rustc does not see a
+    // constant zero one.
+    let divisor = if division && options.zero_division == ZeroDivision::Trap {
+        "d".to_string()
+    } else {
+        format!("v{rhs}")
+    };
+    let expr = match options.overflow {
+        Overflow::Undefined => format!("v{lhs} {plain} {divisor}"),
+        Overflow::Trap if op != BinaryOp::Remainder => {
+            format!("io.{trap}(v{lhs}, {divisor}, {r}, {c})")
+        }
+        Overflow::Wrap | Overflow::Trap => format!("v{lhs}.{wrap}({divisor})"),
+    };
+    if !division {
+        return expr;
+    }
+    match options.zero_division {
+        // Reflection happens before the division, in a separate block.
+        ZeroDivision::Undefined | ZeroDivision::Reflect => expr,
+        ZeroDivision::Trap => format!("{{ let d = io.check_zero(v{rhs}, {r}, {c}); {expr} }}"),
+        ZeroDivision::Zero => format!("if v{rhs} == 0 {{ 0 }} else {{ {expr} }}"),
+    }
+}
+
+fn pop(storage: usize) -> String {
+    if StorageKind::from(storage) == StorageKind::Queue {
+        "q.pop_front().unwrap()".to_string()
+    } else {
+        format!("s[{storage}].pop().unwrap()")
+    }
+}
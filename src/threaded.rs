@@ -0,0 +1,310 @@
+//! Execution of linearized aheui code as chains of pre-built closures.
+//!
+//! Every instruction becomes a closure specialized for its operands, storage kind and options
+//! before the program starts, so running it involves no decoding. Blocks only pop values that the
+//! `JSL` checks of the [`crate::ir`] have already made sure of, so unlike the interpreter, no
+//! instruction checks whether its storage has enough values.
+
+use std::collections::VecDeque;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::interpreter::{print_utf8, Input};
+use crate::ir::{BinaryOp, Block, Instruction, Label, Register, Terminator};
+use crate::{
+    linearize_with, parse, Error, Extension, Integer, Options, Overflow, StorageKind, Width,
+    ZeroDivision, STREAM,
+};
+
+/// Compiles aheui `code` to closures and runs them, reading from `input` and writing to `output`.
+///
+/// Behaves like [`crate::interpret_with`] with the same `options`, and returns the value the
+/// program halts with. The ㅎ storage is a plain stack.
+pub fn run(
+    code: &str,
+    input: impl Read,
+    output: impl Write,
+    options: &Options,
+) -> Result<Integer, Error> {
+    run_with_extension(code, input, output, options, &mut Vec::new())
+}
+
+/// Runs aheui `code` like [`run`], forwarding the ㅎ storage to `extension` like
+/// [`crate::interpret_with_extension`].
+pub fn run_with_extension(
+    code: &str,
+    mut input: impl Read,
+    mut output: impl Write,
+    options: &Options,
+    extension: &mut dyn Extension,
+) -> Result<Integer, Error> {
+    let blocks: Vec<_> = linearize_with(&parse(code), options)?
+        .iter()
+        .map(|block| compile(block, options))
+        .collect();
+    let mut machine = Machine {
+        stacks: vec![vec![]; 28],
+        queue: VecDeque::new(),
+        extension,
+        var: vec![0; registers(&blocks)],
+        input: Input {
+            reader: BufReader::new(&mut input),
+        },
+        output: BufWriter::new(&mut output),
+    };
+    let mut label = 0;
+    loop {
+        let block = &blocks[label];
+        for step in &block.steps {
+            step(&mut machine)?;
+        }
+        match (block.branch)(&mut machine)? {
+            Next::Jump(target) => label = target,
+            Next::Halt(v) => {
+                machine.output.flush()?;
+                return Ok(v);
+            }
+        }
+    }
+}
+
+struct Machine<'a> {
+    /// Every storage but the queue and the ㅎ storage.
+    stacks: Vec<Vec<Integer>>,
+    queue: VecDeque<Integer>,
+    extension: &'a mut dyn Extension,
+    /// Registers of the current block.
+    var: Vec<Integer>,
+    input: Input<&'a mut dyn Read>,
+    output: BufWriter<&'a mut dyn Write>,
+}
+
+type Step = Box<dyn Fn(&mut Machine) -> Result<(), Error>>;
+type Branch = Box<dyn Fn(&mut Machine) -> Result<Next, Error>>;
+
+enum Next {
+    Jump(Label),
+    Halt(Integer),
+}
+
+struct Compiled {
+    steps: Vec<Step>,
+    branch: Branch,
+    /// Number of registers the block uses.
+    registers: usize,
+}
+
+fn registers(blocks: &[Compiled]) -> usize {
+    blocks
+        .iter()
+        .map(|block| block.registers)
+        .max()
+        .unwrap_or(0)
+}
+
+fn compile(block: &Block, options: &Options) -> Compiled {
+    let steps = block
+        .instructions
+        .iter()
+        .map(|instruction| step(instruction, options))
+        .collect();
+    let registers = block
+        .instructions
+        .iter()
+        .filter_map(Instruction::defined)
+        .max()
+        .map_or(0, |r| r + 1);
+    Compiled {
+        steps,
+        branch: branch(&block.terminator),
+        registers,
+    }
+}
+
+fn is_queue(storage: usize) -> bool {
+    StorageKind::from(storage) == StorageKind::Queue
+}
+
+fn step(instruction: &Instruction, options: &Options) -> Step {
+    let width = options.width;
+    match *instruction {
+        Instruction::Pop { dst, storage } if is_queue(storage) => Box::new(move |m| {
+            m.var[dst] = m.queue.pop_front().unwrap();
+            Ok(())
+        }),
+        Instruction::Pop {
+            dst,
+            storage: STREAM,
+        } => Box::new(move |m| {
+            m.var[dst] = m.extension.pop();
+            Ok(())
+        }),
+        Instruction::Pop { dst, storage } => Box::new(move |m| {
+            m.var[dst] = m.stacks[storage].pop().unwrap();
+            Ok(())
+        }),
+        Instruction::Push { storage, src } if is_queue(storage) => Box::new(move |m| {
+            m.queue.push_back(m.var[src]);
+            Ok(())
+        }),
+        Instruction::PushFront { src, .. } => Box::new(move |m| {
+            m.queue.push_front(m.var[src]);
+            Ok(())
+        }),
+        Instruction::Push {
+            storage: STREAM,
+            src,
+        } => Box::new(move |m| {
+            m.extension.push(m.var[src]);
+            Ok(())
+        }),
+        Instruction::Push { storage, src } => Box::new(move |m| {
+            let v = m.var[src];
+            m.stacks[storage].push(v);
+            Ok(())
+        }),
+        Instruction::Const { dst, value } => Box::new(move |m| {
+            m.var[dst] = value;
+            Ok(())
+        }),
+        Instruction::Binary {
+            dst,
+            op,
+            lhs,
+            rhs,
+            cell: (r, c),
+        } => match op {
+            _ if options.overflow == Overflow::Trap => checked(op, dst, lhs, rhs, (r, c), options),
+            BinaryOp::Divide | BinaryOp::Remainder => checked(op, dst, lhs, rhs, (r, c), options),
+            BinaryOp::Add => wrapping(dst, lhs, rhs, width, Integer::wrapping_add),
+            BinaryOp::Subtract => wrapping(dst, lhs, rhs, width, Integer::wrapping_sub),
+            BinaryOp::Multiply => wrapping(dst, lhs, rhs, width, Integer::wrapping_mul),
+            BinaryOp::Compare => wrapping(dst, lhs, rhs, width, |a, b| (a >= b) as Integer),
+        },
+        Instruction::PrintDecimal(src) => Box::new(move |m| {
+            write!(m.output, "{}", m.var[src])?;
+            Ok(())
+        }),
+        Instruction::PrintUnicode(src) => Box::new(move |m| {
+            print_utf8(&mut m.output, m.var[src])?;
+            Ok(())
+        }),
+        Instruction::ScanDecimal(dst) => Box::new(move |m| {
+            m.var[dst] = width.wrap(m.input.scan_decimal()?);
+            Ok(())
+        }),
+        Instruction::ScanUnicode(dst) => Box::new(move |m| {
+            m.var[dst] = m.input.scan_utf8()?;
+            Ok(())
+        }),
+    }
+}
+
+// Arithmetic that wraps around, with `f` inlined into the closure.
+fn wrapping(
+    dst: Register,
+    lhs: Register,
+    rhs: Register,
+    width: Width,
+    f: impl Fn(Integer, Integer) -> Integer + 'static,
+) -> Step {
+    Box::new(move |m| {
+        m.var[dst] = width.wrap(f(m.var[lhs], m.var[rhs]));
+        Ok(())
+    })
+}
+
+// Arithmetic that can stop the program, which is division and anything that traps on overflow.
+fn checked(
+    op: BinaryOp,
+    dst: Register,
+    lhs: Register,
+    rhs: Register,
+    (r, c): (usize, usize),
+    options: &Options,
+) -> Step {
+    let width = options.width;
+    let divides = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+    let zero = options.zero_division == ZeroDivision::Zero;
+    let trap = options.overflow == Overflow::Trap;
+    Box::new(move |m| {
+        let (a, b) = (m.var[lhs], m.var[rhs]);
+        m.var[dst] = if divides && b == 0 {
+            if !zero {
+                m.output.flush()?;
+                return Err(Error::DivisionByZero { r, c });
+            }
+            0
+        } else if trap {
+            match op.checked(a, b).filter(|&v| width.wrap(v) == v) {
+                Some(v) => v,
+                None => {
+                    m.output.flush()?;
+                    return Err(Error::Overflow { op, r, c });
+                }
+            }
+        } else {
+            width.wrap(op.wrapping(a, b))
+        };
+        Ok(())
+    })
+}
+
+fn branch(terminator: &Terminator) -> Branch {
+    match *terminator {
+        Terminator::Goto(target) => Box::new(move |_| Ok(Next::Jump(target))),
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => Box::new(move |m| {
+            let len = if is_queue(storage) {
+                m.queue.len()
+            } else if storage == STREAM {
+                m.extension.len()
+            } else {
+                m.stacks[storage].len()
+            };
+            Ok(Next::Jump(if len < size { less } else { otherwise }))
+        }),
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => Box::new(move |m| {
+            let v = if is_queue(storage) {
+                m.queue.pop_front()
+            } else if storage == STREAM {
+                Some(m.extension.pop())
+            } else {
+                m.stacks[storage].pop()
+            };
+            Ok(Next::Jump(if v.unwrap() != 0 { nonzero } else { zero }))
+        }),
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => Box::new(move |m| {
+            let v = if is_queue(storage) {
+                m.queue.front().copied()
+            } else if storage == STREAM {
+                Some(m.extension.peek())
+            } else {
+                m.stacks[storage].last().copied()
+            };
+            Ok(Next::Jump(if v.unwrap() == 0 { zero } else { otherwise }))
+        }),
+        Terminator::Halt { storage } => Box::new(move |m| {
+            let v = if is_queue(storage) {
+                m.queue.pop_front()
+            } else if storage == STREAM {
+                // The extension is only asked for values it has.
+                (!m.extension.is_empty()).then(|| m.extension.pop())
+            } else {
+                m.stacks[storage].pop()
+            };
+            Ok(Next::Halt(v.unwrap_or(0)))
+        }),
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::interpreter::{print_utf8, storages, Input};
+use crate::ir::{BinaryOp, Block, Instruction, Terminator};
+use crate::{Error, Extension, Integer, Options, Overflow, ZeroDivision};
+
+// Runs decoded bytecode like the interpreter runs the source it came from.
+//
+// The blocks may come from anywhere, so a storage with too few values stops the program with
+// `Error::InvalidBytecode` instead of panicking.
+pub(crate) fn execute(
+    blocks: &[Block],
+    options: &Options,
+    input: impl Read,
+    output: impl Write,
+    extension: &mut dyn Extension,
+) -> Result<Integer, Error> {
+    let width = options.width;
+    let mut input = Input {
+        reader: BufReader::new(input),
+    };
+    let mut output = BufWriter::new(output);
+    let mut storage = storages(extension);
+    let registers = blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(Instruction::defined)
+        .max()
+        .map_or(0, |r| r + 1);
+    let mut var: Vec<Integer> = vec![0; registers];
+    let empty = || Error::InvalidBytecode("not enough values on a storage");
+    let mut label = 0;
+    loop {
+        let block = &blocks[label];
+        for instruction in &block.instructions {
+            match *instruction {
+                Instruction::Pop { dst, storage: s } => {
+                    var[dst] = storage[s].pop().ok_or_else(empty)?;
+                }
+                Instruction::Push { storage: s, src } => storage[s].push(var[src]),
+                Instruction::PushFront { storage: s, src } => storage[s].push_front(var[src]),
+                Instruction::Const { dst, value } => var[dst] = value,
+                Instruction::Binary {
+                    dst,
+                    op,
+                    lhs,
+                    rhs,
+                    cell: (r, c),
+                } => {
+                    let (a, b) = (var[lhs], var[rhs]);
+                    let divides = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+                    var[dst] = if divides && b == 0 {
+                        if options.zero_division != ZeroDivision::Zero {
+                            output.flush()?;
+                            return Err(Error::DivisionByZero { r, c });
+                        }
+                        0
+                    } else if options.overflow == Overflow::Trap {
+                        match op.checked(a, b).filter(|&v| width.wrap(v) == v) {
+                            Some(v) => v,
+                            None => {
+                                output.flush()?;
+                                return Err(Error::Overflow { op, r, c });
+                            }
+                        }
+                    } else {
+                        width.wrap(op.wrapping(a, b))
+                    };
+                }
+                Instruction::PrintDecimal(src) => write!(output, "{}", var[src])?,
+                Instruction::PrintUnicode(src) => print_utf8(&mut output, var[src])?,
+                Instruction::ScanDecimal(dst) => var[dst] = width.wrap(input.scan_decimal()?),
+                Instruction::ScanUnicode(dst) => var[dst] = input.scan_utf8()?,
+            }
+        }
+        label = match block.terminator {
+            Terminator::Goto(target) => target,
+            Terminator::Jsl {
+                storage: s,
+                size,
+                less,
+                otherwise,
+            } => {
+                if storage[s].len() < size {
+                    less
+                } else {
+                    otherwise
+                }
+            }
+            Terminator::Jnz {
+                storage: s,
+                nonzero,
+                zero,
+            } => {
+                if storage[s].pop().ok_or_else(empty)? != 0 {
+                    nonzero
+                } else {
+                    zero
+                }
+            }
+            Terminator::Jz {
+                storage: s,
+                zero,
+                otherwise,
+            } => {
+                if storage[s].len() == 0 {
+                    return Err(empty());
+                }
+                if storage[s].peek() == 0 {
+                    zero
+                } else {
+                    otherwise
+                }
+            }
+            Terminator::Halt { storage: s } => {
+                output.flush()?;
+                return Ok(storage[s].pop().unwrap_or(0));
+            }
+        };
+    }
+}
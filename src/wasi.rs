@@ -0,0 +1,1109 @@
+// The runtime of the WebAssembly backends, which keeps the storages in linear memory and does its
+// I/O through WASI.
+//
+// Memory layout:
+//
+// 0       storages: memory, capacity, front and length, 16 bytes each
+// 512     iovec for WASI calls, then the number of bytes read or written
+// 1024    text of trap messages
+// 2048    digits of print_decimal
+// 4096    output buffer
+// 69632   input buffer
+// 135168  values of the storages, in 8-byte slots regardless of the width
+
+use crate::wasm::Function::{self, *};
+use crate::wasm::Global::*;
+use crate::wasm::Instr::*;
+use crate::wasm::Label::Named;
+use crate::wasm::Op::*;
+use crate::wasm::Type::{self, *};
+use crate::wasm::{Func, Import, Instr, Module};
+
+const WASI: &str = "wasi_snapshot_preview1";
+
+// Returns the module with the runtime for integers of type `int`, to which `main` is added.
+pub(crate) fn runtime(int: Type) -> Module {
+    let fd = |func| Import {
+        module: WASI,
+        func,
+        params: vec![I32; 4],
+        results: vec![I32],
+    };
+    Module {
+        imports: vec![
+            fd(FdRead),
+            fd(FdWrite),
+            Import {
+                module: WASI,
+                func: ProcExit,
+                params: vec![I32],
+                results: vec![],
+            },
+        ],
+        pages: 3,
+        data: vec![
+            (1024, b"overflow in "),
+            (1040, b"division by zero"),
+            (1056, b" at line "),
+            (1072, b", column "),
+            (1088, b"addition"),
+            (1104, b"subtraction"),
+            (1120, b"multiplication"),
+            (1136, b"division"),
+        ],
+        globals: vec![
+            (Heap, 135168),
+            (OutputLen, 0),
+            (InputOff, 0),
+            (InputLen, 0),
+            (InputEof, 0),
+        ],
+        funcs: vec![
+            alloc(),
+            at(),
+            grow(int),
+            size(),
+            push_back(int),
+            push_front(int),
+            peek_back(int),
+            pop_back(int),
+            peek_front(int),
+            pop_front(int),
+            flush(),
+            put(int),
+            write_bytes(int),
+            print_decimal(int),
+            put_continuation(int),
+            print_utf8(int),
+            peek_byte(int),
+            consume(),
+            scan_decimal(int),
+            continue_utf8(int),
+            scan_utf8(int),
+            halt(HaltBack, PopBack, int),
+            halt(HaltFront, PopFront, int),
+            trap_at(int),
+            overflow(int),
+            check_zero(int),
+            checked_add(int),
+            checked_sub(int),
+            checked_mul(int),
+            checked_div(int),
+            wrapping_div(int),
+        ],
+    }
+}
+
+// Declares a function whose locals are numbered in the order of `params` and `locals`.
+fn func(
+    name: Function,
+    params: &[(&str, Type)],
+    results: &[Type],
+    locals: &[(&str, Type)],
+    body: Vec<Instr>,
+) -> Func {
+    let named = |locals: &[(&str, Type)]| {
+        locals
+            .iter()
+            .map(|&(name, t)| (name.to_string(), t))
+            .collect()
+    };
+    Func {
+        name,
+        export: None,
+        params: named(params),
+        results: results.to_vec(),
+        locals: named(locals),
+        body,
+    }
+}
+
+fn alloc() -> Func {
+    let (bytes, p, end) = (0, 1, 2);
+    func(
+        Alloc,
+        &[("bytes", I32)],
+        &[I32],
+        &[("p", I32), ("end", I32)],
+        vec![
+            GlobalGet(Heap),
+            LocalTee(p),
+            LocalGet(bytes),
+            Numeric(I32, Add),
+            LocalTee(end),
+            GlobalSet(Heap),
+            LocalGet(end),
+            MemorySize,
+            Const(I32, 16),
+            Numeric(I32, Shl),
+            Numeric(I32, GtU),
+            If,
+            LocalGet(end),
+            Const(I32, 65535),
+            Numeric(I32, Add),
+            MemorySize,
+            Const(I32, 16),
+            Numeric(I32, Shl),
+            Numeric(I32, Sub),
+            Const(I32, 16),
+            Numeric(I32, ShrU),
+            MemoryGrow,
+            Drop,
+            End,
+            LocalGet(p),
+        ],
+    )
+}
+
+// Returns the address of the `i`th value from the front of storage `s`.
+fn at() -> Func {
+    let (s, i, d) = (0, 1, 2);
+    func(
+        At,
+        &[("s", I32), ("i", I32)],
+        &[I32],
+        &[("d", I32)],
+        vec![
+            LocalGet(s),
+            Const(I32, 4),
+            Numeric(I32, Shl),
+            LocalTee(d),
+            Load(I32, 0),
+            LocalGet(d),
+            Load(I32, 8),
+            LocalGet(i),
+            Numeric(I32, Add),
+            LocalGet(d),
+            Load(I32, 4),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            Numeric(I32, And),
+            Const(I32, 3),
+            Numeric(I32, Shl),
+            Numeric(I32, Add),
+        ],
+    )
+}
+
+// Doubles the capacity of storage `s`, moving its values to the start of the new memory.
+fn grow(int: Type) -> Func {
+    let (s, d, capacity, new, length, i) = (0, 1, 2, 3, 4, 5);
+    func(
+        Grow,
+        &[("s", I32)],
+        &[],
+        &[
+            ("d", I32),
+            ("capacity", I32),
+            ("new", I32),
+            ("length", I32),
+            ("i", I32),
+        ],
+        vec![
+            LocalGet(s),
+            Const(I32, 4),
+            Numeric(I32, Shl),
+            LocalTee(d),
+            Load(I32, 4),
+            LocalTee(capacity),
+            Const(I32, 1),
+            Numeric(I32, Shl),
+            Const(I32, 16),
+            LocalGet(capacity),
+            Select,
+            LocalTee(capacity),
+            Const(I32, 3),
+            Numeric(I32, Shl),
+            Call(Alloc),
+            LocalSet(new),
+            LocalGet(d),
+            Load(I32, 12),
+            LocalSet(length),
+            Block(Named("done")),
+            Loop(Named("copy")),
+            LocalGet(i),
+            LocalGet(length),
+            Numeric(I32, Eq),
+            BrIf(Named("done")),
+            LocalGet(new),
+            LocalGet(i),
+            Const(I32, 3),
+            Numeric(I32, Shl),
+            Numeric(I32, Add),
+            LocalGet(s),
+            LocalGet(i),
+            Call(At),
+            Load(int, 0),
+            Store(int, 0),
+            LocalGet(i),
+            Const(I32, 1),
+            Numeric(I32, Add),
+            LocalSet(i),
+            Br(Named("copy")),
+            End,
+            End,
+            LocalGet(d),
+            LocalGet(new),
+            Store(I32, 0),
+            LocalGet(d),
+            LocalGet(capacity),
+            Store(I32, 4),
+            LocalGet(d),
+            Const(I32, 0),
+            Store(I32, 8),
+        ],
+    )
+}
+
+fn size() -> Func {
+    let s = 0;
+    func(
+        Size,
+        &[("s", I32)],
+        &[I32],
+        &[],
+        vec![LocalGet(s), Const(I32, 4), Numeric(I32, Shl), Load(I32, 12)],
+    )
+}
+
+// Sets `d` to the descriptor of storage `s`, grows the storage if it is full, and leaves the
+// address of the descriptor on the stack.
+fn reserve(s: u32, d: u32) -> [Instr; 13] {
+    [
+        LocalGet(s),
+        Const(I32, 4),
+        Numeric(I32, Shl),
+        LocalTee(d),
+        Load(I32, 12),
+        LocalGet(d),
+        Load(I32, 4),
+        Numeric(I32, Eq),
+        If,
+        LocalGet(s),
+        Call(Grow),
+        End,
+        LocalGet(d),
+    ]
+}
+
+// Increments the length in descriptor `d`, whose address is already on the stack.
+fn lengthen(d: u32) -> [Instr; 5] {
+    [
+        LocalGet(d),
+        Load(I32, 12),
+        Const(I32, 1),
+        Numeric(I32, Add),
+        Store(I32, 12),
+    ]
+}
+
+fn push_back(int: Type) -> Func {
+    let (s, v, d) = (0, 1, 2);
+    let mut body = reserve(s, d).to_vec();
+    body.extend([
+        LocalGet(s),
+        LocalGet(d),
+        Load(I32, 12),
+        Call(At),
+        LocalGet(v),
+        Store(int, 0),
+    ]);
+    body.extend(lengthen(d));
+    func(
+        PushBack,
+        &[("s", I32), ("v", int)],
+        &[],
+        &[("d", I32)],
+        body,
+    )
+}
+
+fn push_front(int: Type) -> Func {
+    let (s, v, d, mask) = (0, 1, 2, 3);
+    let mut body = reserve(s, d).to_vec();
+    body.extend([
+        LocalGet(d),
+        Load(I32, 8),
+        LocalGet(d),
+        Load(I32, 4),
+        Const(I32, 1),
+        Numeric(I32, Sub),
+        LocalTee(mask),
+        Numeric(I32, Add),
+        LocalGet(mask),
+        Numeric(I32, And),
+        Store(I32, 8),
+        LocalGet(s),
+        Const(I32, 0),
+        Call(At),
+        LocalGet(v),
+        Store(int, 0),
+        LocalGet(d),
+    ]);
+    body.extend(lengthen(d));
+    func(
+        PushFront,
+        &[("s", I32), ("v", int)],
+        &[],
+        &[("d", I32), ("mask", I32)],
+        body,
+    )
+}
+
+fn peek_back(int: Type) -> Func {
+    let s = 0;
+    func(
+        PeekBack,
+        &[("s", I32)],
+        &[int],
+        &[],
+        vec![
+            LocalGet(s),
+            LocalGet(s),
+            Call(Size),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            Call(At),
+            Load(int, 0),
+        ],
+    )
+}
+
+fn pop_back(int: Type) -> Func {
+    let (s, d) = (0, 1);
+    func(
+        PopBack,
+        &[("s", I32)],
+        &[int],
+        &[("d", I32)],
+        vec![
+            LocalGet(s),
+            Const(I32, 4),
+            Numeric(I32, Shl),
+            LocalTee(d),
+            LocalGet(d),
+            Load(I32, 12),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            Store(I32, 12),
+            LocalGet(s),
+            LocalGet(d),
+            Load(I32, 12),
+            Call(At),
+            Load(int, 0),
+        ],
+    )
+}
+
+fn peek_front(int: Type) -> Func {
+    let s = 0;
+    func(
+        PeekFront,
+        &[("s", I32)],
+        &[int],
+        &[],
+        vec![LocalGet(s), Const(I32, 0), Call(At), Load(int, 0)],
+    )
+}
+
+fn pop_front(int: Type) -> Func {
+    let (s, d, v) = (0, 1, 2);
+    func(
+        PopFront,
+        &[("s", I32)],
+        &[int],
+        &[("d", I32), ("v", int)],
+        vec![
+            LocalGet(s),
+            Const(I32, 0),
+            Call(At),
+            Load(int, 0),
+            LocalSet(v),
+            LocalGet(s),
+            Const(I32, 4),
+            Numeric(I32, Shl),
+            LocalTee(d),
+            LocalGet(d),
+            Load(I32, 8),
+            Const(I32, 1),
+            Numeric(I32, Add),
+            LocalGet(d),
+            Load(I32, 4),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            Numeric(I32, And),
+            Store(I32, 8),
+            LocalGet(d),
+            LocalGet(d),
+            Load(I32, 12),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            Store(I32, 12),
+            LocalGet(v),
+        ],
+    )
+}
+
+fn flush() -> Func {
+    let fd = 0;
+    func(
+        Flush,
+        &[("fd", I32)],
+        &[],
+        &[],
+        vec![
+            Const(I32, 512),
+            Const(I32, 4096),
+            Store(I32, 0),
+            Const(I32, 512),
+            GlobalGet(OutputLen),
+            Store(I32, 4),
+            LocalGet(fd),
+            Const(I32, 512),
+            Const(I32, 1),
+            Const(I32, 520),
+            Call(FdWrite),
+            Drop,
+            Const(I32, 0),
+            GlobalSet(OutputLen),
+        ],
+    )
+}
+
+// Writes the low byte of `b`.
+fn put(int: Type) -> Func {
+    let b = 0;
+    func(
+        Put,
+        &[("b", int)],
+        &[],
+        &[],
+        vec![
+            GlobalGet(OutputLen),
+            Const(I32, 65536),
+            Numeric(I32, Eq),
+            If,
+            Const(I32, 1),
+            Call(Flush),
+            End,
+            GlobalGet(OutputLen),
+            LocalGet(b),
+            Store8(int, 4096),
+            GlobalGet(OutputLen),
+            Const(I32, 1),
+            Numeric(I32, Add),
+            GlobalSet(OutputLen),
+        ],
+    )
+}
+
+fn write_bytes(int: Type) -> Func {
+    let (p, n) = (0, 1);
+    func(
+        WriteBytes,
+        &[("p", I32), ("n", I32)],
+        &[],
+        &[],
+        vec![
+            Block(Named("done")),
+            Loop(Named("byte")),
+            LocalGet(n),
+            Numeric(I32, Eqz),
+            BrIf(Named("done")),
+            LocalGet(p),
+            Load8U(int, 0),
+            Call(Put),
+            LocalGet(p),
+            Const(I32, 1),
+            Numeric(I32, Add),
+            LocalSet(p),
+            LocalGet(n),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            LocalSet(n),
+            Br(Named("byte")),
+            End,
+            End,
+        ],
+    )
+}
+
+fn print_decimal(int: Type) -> Func {
+    let (v, p, w) = (0, 1, 2);
+    func(
+        PrintDecimal,
+        &[("v", int)],
+        &[],
+        &[("p", I32), ("w", int)],
+        vec![
+            Const(I32, 2112),
+            LocalSet(p),
+            Const(int, 0),
+            LocalGet(v),
+            Numeric(int, Sub),
+            LocalGet(v),
+            LocalGet(v),
+            Const(int, 0),
+            Numeric(int, LtS),
+            Select,
+            LocalSet(w),
+            Loop(Named("digit")),
+            LocalGet(p),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            LocalTee(p),
+            LocalGet(w),
+            Const(int, 10),
+            Numeric(int, RemU),
+            Const(int, 48),
+            Numeric(int, Add),
+            Store8(int, 0),
+            LocalGet(w),
+            Const(int, 10),
+            Numeric(int, DivU),
+            LocalTee(w),
+            Numeric(int, Eqz),
+            Numeric(I32, Eqz),
+            BrIf(Named("digit")),
+            End,
+            LocalGet(v),
+            Const(int, 0),
+            Numeric(int, LtS),
+            If,
+            LocalGet(p),
+            Const(I32, 1),
+            Numeric(I32, Sub),
+            LocalTee(p),
+            Const(int, 45),
+            Store8(int, 0),
+            End,
+            LocalGet(p),
+            Const(I32, 2112),
+            LocalGet(p),
+            Numeric(I32, Sub),
+            Call(WriteBytes),
+        ],
+    )
+}
+
+// Writes `0x80 | ((v >> shift) & 0x3F)`.
+fn put_continuation(int: Type) -> Func {
+    let (v, shift) = (0, 1);
+    func(
+        PutContinuation,
+        &[("v", int), ("shift", int)],
+        &[],
+        &[],
+        vec![
+            LocalGet(v),
+            LocalGet(shift),
+            Numeric(int, ShrU),
+            Const(int, 63),
+            Numeric(int, And),
+            Const(int, 128),
+            Numeric(int, Or),
+            Call(Put),
+        ],
+    )
+}
+
+fn print_utf8(int: Type) -> Func {
+    let v = 0;
+    let mut body = vec![
+        LocalGet(v),
+        Const(int, 0x10ffff),
+        Numeric(int, GtU),
+        If,
+        Return,
+        End,
+        LocalGet(v),
+        Const(int, 0x80),
+        Numeric(int, LtU),
+        If,
+        LocalGet(v),
+        Call(Put),
+        Return,
+        End,
+    ];
+    // Code points below each limit take the lead byte and continuation bytes after it.
+    for (limit, lead, continuations) in [(0x800, 0xc0, 1), (0x10000, 0xe0, 2), (0, 0xf0, 3)] {
+        if limit != 0 {
+            body.extend([LocalGet(v), Const(int, limit), Numeric(int, LtU), If]);
+        }
+        body.extend([
+            LocalGet(v),
+            Const(int, 6 * continuations),
+            Numeric(int, ShrU),
+            Const(int, lead),
+            Numeric(int, Or),
+            Call(Put),
+        ]);
+        for shift in (0..continuations).rev() {
+            body.extend([LocalGet(v), Const(int, 6 * shift), Call(PutContinuation)]);
+        }
+        if limit != 0 {
+            body.extend([Return, End]);
+        }
+    }
+    func(PrintUtf8, &[("v", int)], &[], &[], body)
+}
+
+// Returns the next input byte as a `signed char`, or -1 at the end of the input.
+fn peek_byte(int: Type) -> Func {
+    func(
+        PeekByte,
+        &[],
+        &[int],
+        &[],
+        vec![
+            GlobalGet(InputOff),
+            GlobalGet(InputLen),
+            Numeric(I32, GeU),
+            If,
+            GlobalGet(InputEof),
+            If,
+            Const(int, -1),
+            Return,
+            End,
+            Const(I32, 512),
+            Const(I32, 69632),
+            Store(I32, 0),
+            Const(I32, 512),
+            Const(I32, 65536),
+            Store(I32, 4),
+            Const(I32, 520),
+            Const(I32, 0),
+            Store(I32, 0),
+            Const(I32, 0),
+            Const(I32, 512),
+            Const(I32, 1),
+            Const(I32, 520),
+            Call(FdRead),
+            Drop,
+            Const(I32, 0),
+            GlobalSet(InputOff),
+            Const(I32, 520),
+            Load(I32, 0),
+            GlobalSet(InputLen),
+            GlobalGet(InputLen),
+            Numeric(I32, Eqz),
+            If,
+            Const(I32, 1),
+            GlobalSet(InputEof),
+            Const(int, -1),
+            Return,
+            End,
+            End,
+            GlobalGet(InputOff),
+            Load8S(int, 69632),
+        ],
+    )
+}
+
+fn consume() -> Func {
+    func(
+        Consume,
+        &[],
+        &[],
+        &[],
+        vec![
+            GlobalGet(InputOff),
+            Const(I32, 1),
+            Numeric(I32, Add),
+            GlobalSet(InputOff),
+        ],
+    )
+}
+
+fn scan_decimal(int: Type) -> Func {
+    let (c, v, sign) = (0, 1, 2);
+    func(
+        ScanDecimal,
+        &[],
+        &[int],
+        &[("c", int), ("v", int), ("sign", I32)],
+        vec![
+            Block(Named("start")),
+            Loop(Named("skip")),
+            Call(PeekByte),
+            LocalTee(c),
+            Const(int, -1),
+            Numeric(int, Eq),
+            If,
+            Const(int, -1),
+            Return,
+            End,
+            LocalGet(c),
+            Const(int, 32),
+            Numeric(int, GtS),
+            BrIf(Named("start")),
+            Call(Consume),
+            Br(Named("skip")),
+            End,
+            End,
+            LocalGet(c),
+            Const(int, 45),
+            Numeric(int, Eq),
+            LocalTee(sign),
+            If,
+            Call(Consume),
+            Call(PeekByte),
+            LocalSet(c),
+            End,
+            Block(Named("end")),
+            Loop(Named("digit")),
+            LocalGet(c),
+            Const(int, 48),
+            Numeric(int, LtS),
+            LocalGet(c),
+            Const(int, 57),
+            Numeric(int, GtS),
+            Numeric(I32, Or),
+            BrIf(Named("end")),
+            LocalGet(v),
+            Const(int, 10),
+            Numeric(int, Mul),
+            LocalGet(c),
+            Numeric(int, Add),
+            Const(int, 48),
+            Numeric(int, Sub),
+            LocalSet(v),
+            Call(Consume),
+            Call(PeekByte),
+            LocalSet(c),
+            Br(Named("digit")),
+            End,
+            End,
+            Const(int, 0),
+            LocalGet(v),
+            Numeric(int, Sub),
+            LocalGet(v),
+            LocalGet(sign),
+            Select,
+        ],
+    )
+}
+
+// Appends the low 6 bits of the next byte, or of 0xFF at the end of the input, to `v`.
+fn continue_utf8(int: Type) -> Func {
+    let v = 0;
+    func(
+        ContinueUtf8,
+        &[("v", int)],
+        &[int],
+        &[],
+        vec![
+            LocalGet(v),
+            Const(int, 6),
+            Numeric(int, Shl),
+            Call(PeekByte),
+            Const(int, 63),
+            Numeric(int, And),
+            Numeric(int, Or),
+            Call(Consume),
+        ],
+    )
+}
+
+fn scan_utf8(int: Type) -> Func {
+    let v = 0;
+    let mut body = vec![
+        Call(PeekByte),
+        LocalSet(v),
+        Call(Consume),
+        LocalGet(v),
+        Const(int, -1),
+        Numeric(int, Eq),
+        If,
+        Const(int, -1),
+        Return,
+        End,
+        LocalGet(v),
+        Const(int, 0xff),
+        Numeric(int, And),
+        LocalTee(v),
+        Const(int, 0x80),
+        Numeric(int, And),
+        Numeric(int, Eqz),
+        If,
+        LocalGet(v),
+        Return,
+        End,
+    ];
+    // The lead byte has a zero after as many ones as there are bytes, and the bits after it.
+    for (zero, bits, continuations) in [(0x20, 0x1f, 1), (0x10, 0x0f, 2), (0, 0x07, 3)] {
+        if zero != 0 {
+            body.extend([
+                LocalGet(v),
+                Const(int, zero),
+                Numeric(int, And),
+                Numeric(int, Eqz),
+                If,
+            ]);
+        }
+        body.extend([LocalGet(v), Const(int, bits), Numeric(int, And)]);
+        body.extend((0..continuations).map(|_| Call(ContinueUtf8)));
+        if zero != 0 {
+            body.extend([Return, End]);
+        }
+    }
+    func(ScanUtf8, &[], &[int], &[("v", int)], body)
+}
+
+// Flushes the output, and pops the exit code from storage `s` with `pop`.
+fn halt(name: Function, pop: Function, int: Type) -> Func {
+    let s = 0;
+    func(
+        name,
+        &[("s", I32)],
+        &[int],
+        &[],
+        vec![
+            Const(I32, 1),
+            Call(Flush),
+            LocalGet(s),
+            Call(Size),
+            Numeric(I32, Eqz),
+            If,
+            Const(int, 0),
+            Return,
+            End,
+            LocalGet(s),
+            Call(pop),
+        ],
+    )
+}
+
+// Writes " at line {r}, column {c}" to the standard error and traps.
+fn trap_at(int: Type) -> Func {
+    let (r, c) = (0, 1);
+    func(
+        TrapAt,
+        &[("r", int), ("c", int)],
+        &[],
+        &[],
+        vec![
+            Const(I32, 1056),
+            Const(I32, 9),
+            Call(WriteBytes),
+            LocalGet(r),
+            Call(PrintDecimal),
+            Const(I32, 1072),
+            Const(I32, 9),
+            Call(WriteBytes),
+            LocalGet(c),
+            Call(PrintDecimal),
+            Const(int, 10),
+            Call(Put),
+            Const(I32, 2),
+            Call(Flush),
+            Unreachable,
+        ],
+    )
+}
+
+fn overflow(int: Type) -> Func {
+    let (op, n, r, c) = (0, 1, 2, 3);
+    func(
+        Overflow,
+        &[("op", I32), ("n", I32), ("r", int), ("c", int)],
+        &[],
+        &[],
+        vec![
+            Const(I32, 1),
+            Call(Flush),
+            Const(I32, 1024),
+            Const(I32, 12),
+            Call(WriteBytes),
+            LocalGet(op),
+            LocalGet(n),
+            Call(WriteBytes),
+            LocalGet(r),
+            LocalGet(c),
+            Call(TrapAt),
+        ],
+    )
+}
+
+fn check_zero(int: Type) -> Func {
+    let (b, r, c) = (0, 1, 2);
+    func(
+        CheckZero,
+        &[("b", int), ("r", int), ("c", int)],
+        &[],
+        &[],
+        vec![
+            LocalGet(b),
+            Numeric(int, Eqz),
+            If,
+            Const(I32, 1),
+            Call(Flush),
+            Const(I32, 1040),
+            Const(I32, 16),
+            Call(WriteBytes),
+            LocalGet(r),
+            LocalGet(c),
+            Call(TrapAt),
+            End,
+        ],
+    )
+}
+
+// Operands, cell and result of the checked operations.
+const A: u32 = 0;
+const B: u32 = 1;
+const R: u32 = 2;
+const C: u32 = 3;
+const V: u32 = 4;
+
+// Declares a checked operation, which keeps its result in `v` if `result` is set.
+fn checked(name: Function, int: Type, result: bool, body: Vec<Instr>) -> Func {
+    let locals: &[_] = if result { &[("v", int)] } else { &[] };
+    func(
+        name,
+        &[("a", int), ("b", int), ("r", int), ("c", int)],
+        &[int],
+        locals,
+        body,
+    )
+}
+
+// Reports the overflow of the operation whose name is `length` bytes at `message`.
+fn report(message: i64, length: i64) -> [Instr; 5] {
+    [
+        Const(I32, message),
+        Const(I32, length),
+        LocalGet(R),
+        LocalGet(C),
+        Call(Overflow),
+    ]
+}
+
+fn checked_add(int: Type) -> Func {
+    let mut body = vec![
+        LocalGet(A),
+        LocalGet(B),
+        Numeric(int, Add),
+        LocalSet(V),
+        LocalGet(A),
+        LocalGet(V),
+        Numeric(int, Xor),
+        LocalGet(B),
+        LocalGet(V),
+        Numeric(int, Xor),
+        Numeric(int, And),
+        Const(int, 0),
+        Numeric(int, LtS),
+        If,
+    ];
+    body.extend(report(1088, 8));
+    body.extend([End, LocalGet(V)]);
+    checked(CheckedAdd, int, true, body)
+}
+
+fn checked_sub(int: Type) -> Func {
+    let mut body = vec![
+        LocalGet(A),
+        LocalGet(B),
+        Numeric(int, Sub),
+        LocalSet(V),
+        LocalGet(A),
+        LocalGet(B),
+        Numeric(int, Xor),
+        LocalGet(A),
+        LocalGet(V),
+        Numeric(int, Xor),
+        Numeric(int, And),
+        Const(int, 0),
+        Numeric(int, LtS),
+        If,
+    ];
+    body.extend(report(1104, 11));
+    body.extend([End, LocalGet(V)]);
+    checked(CheckedSub, int, true, body)
+}
+
+// `-1 * MIN` is the only overflowing product that division cannot detect, as it overflows too.
+fn checked_mul(int: Type) -> Func {
+    let mut body = vec![
+        LocalGet(A),
+        LocalGet(B),
+        Numeric(int, Mul),
+        LocalSet(V),
+        LocalGet(A),
+        Const(int, -1),
+        Numeric(int, Eq),
+        If,
+        LocalGet(B),
+        Numeric(int, Eqz),
+        Numeric(I32, Eqz),
+        LocalGet(V),
+        LocalGet(B),
+        Numeric(int, Eq),
+        Numeric(I32, And),
+        If,
+    ];
+    body.extend(report(1120, 14));
+    body.extend([
+        End,
+        Else,
+        LocalGet(A),
+        Numeric(int, Eqz),
+        Numeric(I32, Eqz),
+        If,
+        LocalGet(V),
+        LocalGet(A),
+        Numeric(int, DivS),
+        LocalGet(B),
+        Numeric(int, Ne),
+        If,
+    ]);
+    body.extend(report(1120, 14));
+    body.extend([End, End, End, LocalGet(V)]);
+    checked(CheckedMul, int, true, body)
+}
+
+fn checked_div(int: Type) -> Func {
+    let mut body = vec![
+        LocalGet(B),
+        Const(int, -1),
+        Numeric(int, Eq),
+        LocalGet(A),
+        Numeric(int, Eqz),
+        Numeric(I32, Eqz),
+        Numeric(I32, And),
+        LocalGet(A),
+        Const(int, 0),
+        LocalGet(A),
+        Numeric(int, Sub),
+        Numeric(int, Eq),
+        Numeric(I32, And),
+        If,
+    ];
+    body.extend(report(1136, 8));
+    body.extend([End, LocalGet(A), LocalGet(B), Numeric(int, DivS)]);
+    checked(CheckedDiv, int, false, body)
+}
+
+fn wrapping_div(int: Type) -> Func {
+    func(
+        WrappingDiv,
+        &[("a", int), ("b", int)],
+        &[int],
+        &[],
+        vec![
+            LocalGet(B),
+            Const(int, -1),
+            Numeric(int, Eq),
+            If,
+            Const(int, 0),
+            LocalGet(A),
+            Numeric(int, Sub),
+            Return,
+            End,
+            LocalGet(A),
+            LocalGet(B),
+            Numeric(int, DivS),
+        ],
+    )
+}
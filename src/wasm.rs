@@ -0,0 +1,790 @@
+use std::fmt;
+
+use crate::ir::{BinaryOp, Block, Instruction, Terminator};
+use crate::{wasi, Options, Overflow, StorageKind, Width, ZeroDivision};
+
+// A module for WASI, which the text backend prints and the binary backend encodes.
+pub(crate) struct Module {
+    pub(crate) imports: Vec<Import>,
+    // Pages of the memory, which is exported as `memory`.
+    pub(crate) pages: u32,
+    pub(crate) data: Vec<(u32, &'static [u8])>,
+    // Mutable `i32` globals with their initial values.
+    pub(crate) globals: Vec<(Global, i32)>,
+    pub(crate) funcs: Vec<Func>,
+}
+
+pub(crate) struct Import {
+    pub(crate) module: &'static str,
+    pub(crate) func: Function,
+    pub(crate) params: Vec<Type>,
+    pub(crate) results: Vec<Type>,
+}
+
+pub(crate) struct Func {
+    pub(crate) name: Function,
+    pub(crate) export: Option<&'static str>,
+    pub(crate) params: Vec<(String, Type)>,
+    pub(crate) results: Vec<Type>,
+    pub(crate) locals: Vec<(String, Type)>,
+    pub(crate) body: Vec<Instr>,
+}
+
+impl Func {
+    // Name of the local at `index`, counting the parameters first.
+    pub(crate) fn local(&self, index: u32) -> &str {
+        let mut locals = self.params.iter().chain(&self.locals);
+        &locals.nth(index as usize).unwrap().0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Type {
+    I32,
+    I64,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+        })
+    }
+}
+
+// Functions of the module, imported ones first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Function {
+    FdRead,
+    FdWrite,
+    ProcExit,
+    Alloc,
+    At,
+    Grow,
+    Size,
+    PushBack,
+    PushFront,
+    PeekBack,
+    PopBack,
+    PeekFront,
+    PopFront,
+    Flush,
+    Put,
+    WriteBytes,
+    PrintDecimal,
+    PutContinuation,
+    PrintUtf8,
+    PeekByte,
+    Consume,
+    ScanDecimal,
+    ContinueUtf8,
+    ScanUtf8,
+    HaltBack,
+    HaltFront,
+    TrapAt,
+    Overflow,
+    CheckZero,
+    CheckedAdd,
+    CheckedSub,
+    CheckedMul,
+    CheckedDiv,
+    WrappingDiv,
+    Main,
+}
+
+impl Function {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::FdRead => "fd_read",
+            Self::FdWrite => "fd_write",
+            Self::ProcExit => "proc_exit",
+            Self::Alloc => "alloc",
+            Self::At => "at",
+            Self::Grow => "grow",
+            Self::Size => "size",
+            Self::PushBack => "push_back",
+            Self::PushFront => "push_front",
+            Self::PeekBack => "peek_back",
+            Self::PopBack => "pop_back",
+            Self::PeekFront => "peek_front",
+            Self::PopFront => "pop_front",
+            Self::Flush => "flush",
+            Self::Put => "put",
+            Self::WriteBytes => "write_bytes",
+            Self::PrintDecimal => "print_decimal",
+            Self::PutContinuation => "put_continuation",
+            Self::PrintUtf8 => "print_utf8",
+            Self::PeekByte => "peek_byte",
+            Self::Consume => "consume",
+            Self::ScanDecimal => "scan_decimal",
+            Self::ContinueUtf8 => "continue_utf8",
+            Self::ScanUtf8 => "scan_utf8",
+            Self::HaltBack => "halt_back",
+            Self::HaltFront => "halt_front",
+            Self::TrapAt => "trap_at",
+            Self::Overflow => "overflow",
+            Self::CheckZero => "check_zero",
+            Self::CheckedAdd => "checked_add",
+            Self::CheckedSub => "checked_sub",
+            Self::CheckedMul => "checked_mul",
+            Self::CheckedDiv => "checked_div",
+            Self::WrappingDiv => "wrapping_div",
+            Self::Main => "main",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Global {
+    Heap,
+    OutputLen,
+    InputOff,
+    InputLen,
+    InputEof,
+}
+
+impl Global {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Heap => "heap",
+            Self::OutputLen => "output_len",
+            Self::InputOff => "input_off",
+            Self::InputLen => "input_len",
+            Self::InputEof => "input_eof",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Label {
+    Named(&'static str),
+    // Start of the code of a block of the program.
+    Block(usize),
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "${name}"),
+            Self::Block(label) => write!(f, "$B{label}"),
+        }
+    }
+}
+
+// The instructions the backends use. Blocks, loops and conditionals have no results, locals are
+// indices, and memory offsets are immediates.
+#[derive(Clone)]
+pub(crate) enum Instr {
+    Block(Label),
+    Loop(Label),
+    If,
+    Else,
+    End,
+    Br(Label),
+    BrIf(Label),
+    BrTable(Vec<Label>, Label),
+    Return,
+    Unreachable,
+    Drop,
+    Select,
+    Call(Function),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(Global),
+    GlobalSet(Global),
+    Load(Type, u32),
+    Load8S(Type, u32),
+    Load8U(Type, u32),
+    Store(Type, u32),
+    Store8(Type, u32),
+    MemorySize,
+    MemoryGrow,
+    Const(Type, i64),
+    Numeric(Type, Op),
+    WrapI64,
+    ExtendI32U,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Op {
+    Eqz,
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    GeS,
+    GeU,
+    Add,
+    Sub,
+    Mul,
+    DivS,
+    DivU,
+    RemS,
+    RemU,
+    And,
+    Or,
+    Xor,
+    Shl,
+    ShrU,
+}
+
+impl Op {
+    // Name and the opcodes of the `i32` and `i64` forms.
+    pub(crate) fn encoding(self) -> (&'static str, u8, u8) {
+        match self {
+            Self::Eqz => ("eqz", 0x45, 0x50),
+            Self::Eq => ("eq", 0x46, 0x51),
+            Self::Ne => ("ne", 0x47, 0x52),
+            Self::LtS => ("lt_s", 0x48, 0x53),
+            Self::LtU => ("lt_u", 0x49, 0x54),
+            Self::GtS => ("gt_s", 0x4a, 0x55),
+            Self::GtU => ("gt_u", 0x4b, 0x56),
+            Self::GeS => ("ge_s", 0x4e, 0x59),
+            Self::GeU => ("ge_u", 0x4f, 0x5a),
+            Self::Add => ("add", 0x6a, 0x7c),
+            Self::Sub => ("sub", 0x6b, 0x7d),
+            Self::Mul => ("mul", 0x6c, 0x7e),
+            Self::DivS => ("div_s", 0x6d, 0x7f),
+            Self::DivU => ("div_u", 0x6e, 0x80),
+            Self::RemS => ("rem_s", 0x6f, 0x81),
+            Self::RemU => ("rem_u", 0x70, 0x82),
+            Self::And => ("and", 0x71, 0x83),
+            Self::Or => ("or", 0x72, 0x84),
+            Self::Xor => ("xor", 0x73, 0x85),
+            Self::Shl => ("shl", 0x74, 0x86),
+            Self::ShrU => ("shr_u", 0x76, 0x88),
+        }
+    }
+}
+
+// Local of the next label to run in `main`, followed by the registers.
+const LABEL: u32 = 0;
+const DISPATCH: Label = Label::Named("dispatch");
+
+fn v(register: usize) -> u32 {
+    register as u32 + 1
+}
+
+// Lowers `blocks` to the `main` function of the runtime module.
+//
+// Every block is nested in the blocks of the labels after it, so that `br_table` can jump to the
+// start of any of them, and ends with a jump to the dispatch loop unless it falls through.
+pub(crate) fn module(blocks: &[Block], options: &Options) -> Module {
+    let int = int(options);
+    let mut module = wasi::runtime(int);
+    let registers = blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(Instruction::defined)
+        .max()
+        .map_or(0, |max| max + 1);
+    let mut locals = vec![("label".to_string(), Type::I32)];
+    locals.extend((0..registers).map(|register| (format!("v{register}"), int)));
+    let mut body = vec![Instr::Loop(DISPATCH)];
+    body.extend(
+        (0..blocks.len())
+            .rev()
+            .map(|label| Instr::Block(Label::Block(label))),
+    );
+    body.push(Instr::LocalGet(LABEL));
+    body.push(Instr::BrTable(
+        (0..blocks.len()).map(Label::Block).collect(),
+        Label::Block(0),
+    ));
+    for (label, block) in blocks.iter().enumerate() {
+        body.push(Instr::End);
+        lower_block(&mut body, label, block, options);
+    }
+    body.push(Instr::End);
+    module.funcs.push(Func {
+        name: Function::Main,
+        export: Some("_start"),
+        params: vec![],
+        results: vec![],
+        locals,
+        body,
+    });
+    module
+}
+
+fn lower_block(body: &mut Vec<Instr>, label: usize, block: &Block, options: &Options) {
+    use Instr::*;
+    let int = int(options);
+    let storage_const = |storage: usize| Const(Type::I32, storage as i64);
+    for instruction in &block.instructions {
+        match *instruction {
+            Instruction::Pop { dst, storage } => {
+                body.extend([storage_const(storage), Call(pop(storage)), LocalSet(v(dst))]);
+            }
+            Instruction::Push { storage, src } => {
+                body.extend([
+                    storage_const(storage),
+                    LocalGet(v(src)),
+                    Call(Function::PushBack),
+                ]);
+            }
+            Instruction::PushFront { storage, src } => {
+                body.extend([
+                    storage_const(storage),
+                    LocalGet(v(src)),
+                    Call(Function::PushFront),
+                ]);
+            }
+            Instruction::Const { dst, value } => {
+                body.extend([Const(int, value as i64), LocalSet(v(dst))]);
+            }
+            Instruction::Binary {
+                dst,
+                op,
+                lhs,
+                rhs,
+                cell,
+            } => {
+                binary(body, op, lhs, rhs, cell, options);
+                body.push(LocalSet(v(dst)));
+            }
+            Instruction::PrintDecimal(src) => {
+                body.extend([LocalGet(v(src)), Call(Function::PrintDecimal)]);
+            }
+            Instruction::PrintUnicode(src) => {
+                body.extend([LocalGet(v(src)), Call(Function::PrintUtf8)]);
+            }
+            Instruction::ScanDecimal(dst) => {
+                body.extend([Call(Function::ScanDecimal), LocalSet(v(dst))]);
+            }
+            Instruction::ScanUnicode(dst) => {
+                body.extend([Call(Function::ScanUtf8), LocalSet(v(dst))]);
+            }
+        }
+    }
+    match block.terminator {
+        Terminator::Goto(target) => {
+            if target != label + 1 {
+                jump(body, Const(Type::I32, target as i64));
+            }
+        }
+        Terminator::Jsl {
+            storage,
+            size,
+            less,
+            otherwise,
+        } => {
+            body.extend([
+                Const(Type::I32, less as i64),
+                Const(Type::I32, otherwise as i64),
+                storage_const(storage),
+                Call(Function::Size),
+                Const(Type::I32, size as i64),
+                Numeric(Type::I32, Op::LtU),
+            ]);
+            jump(body, Select);
+        }
+        Terminator::Jnz {
+            storage,
+            nonzero,
+            zero,
+        } => {
+            body.extend([
+                Const(Type::I32, zero as i64),
+                Const(Type::I32, nonzero as i64),
+                storage_const(storage),
+                Call(pop(storage)),
+                Numeric(int, Op::Eqz),
+            ]);
+            jump(body, Select);
+        }
+        Terminator::Jz {
+            storage,
+            zero,
+            otherwise,
+        } => {
+            let peek = match StorageKind::from(storage) {
+                StorageKind::Queue => Function::PeekFront,
+                StorageKind::Stack | StorageKind::Stream => Function::PeekBack,
+            };
+            body.extend([
+                Const(Type::I32, zero as i64),
+                Const(Type::I32, otherwise as i64),
+                storage_const(storage),
+                Call(peek),
+                Numeric(int, Op::Eqz),
+            ]);
+            jump(body, Select);
+        }
+        Terminator::Halt { storage } => {
+            let halt = match StorageKind::from(storage) {
+                StorageKind::Queue => Function::HaltFront,
+                StorageKind::Stack | StorageKind::Stream => Function::HaltBack,
+            };
+            body.extend([storage_const(storage), Call(halt)]);
+            if options.width == Width::W64 {
+                body.push(WrapI64);
+            }
+            body.extend([Call(Function::ProcExit), Return]);
+        }
+    }
+}
+
+// Leaves the value of `lhs op rhs` on the stack.
+fn binary(
+    body: &mut Vec<Instr>,
+    op: BinaryOp,
+    lhs: usize,
+    rhs: usize,
+    (r, c): (usize, usize),
+    options: &Options,
+) {
+    use Instr::*;
+    let int = int(options);
+    let cell = [Const(int, r as i64 + 1), Const(int, c as i64 + 1)];
+    if op == BinaryOp::Compare {
+        body.extend([LocalGet(v(lhs)), LocalGet(v(rhs)), Numeric(int, Op::GeS)]);
+        if options.width == Width::W64 {
+            body.push(ExtendI32U);
+        }
+        return;
+    }
+    let division = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+    // With `ZeroDivision::Zero`, a zero divisor is replaced by `1` and the result by `0`.
+    let zero = division && options.zero_division == ZeroDivision::Zero;
+    if division && options.zero_division == ZeroDivision::Trap {
+        body.push(LocalGet(v(rhs)));
+        body.extend(cell.clone());
+        body.push(Call(Function::CheckZero));
+    }
+    if zero {
+        body.push(Const(int, 0));
+    }
+    body.push(LocalGet(v(lhs)));
+    if zero {
+        body.extend([
+            Const(int, 1),
+            LocalGet(v(rhs)),
+            LocalGet(v(rhs)),
+            Numeric(int, Op::Eqz),
+            Select,
+        ]);
+    } else {
+        body.push(LocalGet(v(rhs)));
+    }
+    let (numeric, checked) = match op {
+        BinaryOp::Add => (Op::Add, Function::CheckedAdd),
+        BinaryOp::Subtract => (Op::Sub, Function::CheckedSub),
+        BinaryOp::Multiply => (Op::Mul, Function::CheckedMul),
+        BinaryOp::Divide | BinaryOp::Remainder => (Op::DivS, Function::CheckedDiv),
+        BinaryOp::Compare => unreachable!(),
+    };
+    match (options.overflow, op) {
+        // The remainder of `MIN / -1` is `0` in WebAssembly, which does not trap on it.
+        (_, BinaryOp::Remainder) => body.push(Numeric(int, Op::RemS)),
+        (Overflow::Trap, _) => {
+            body.extend(cell);
+            body.push(Call(checked));
+        }
+        (Overflow::Wrap, BinaryOp::Divide) => body.push(Call(Function::WrappingDiv)),
+        _ => body.push(Numeric(int, numeric)),
+    }
+    if zero {
+        body.extend([LocalGet(v(rhs)), Numeric(int, Op::Eqz), Select]);
+    }
+}
+
+// Sets the next label to the value `instruction` leaves on the stack, and jumps to it.
+fn jump(body: &mut Vec<Instr>, instruction: Instr) {
+    body.extend([instruction, Instr::LocalSet(LABEL), Instr::Br(DISPATCH)]);
+}
+
+// The ㅎ storage is a plain stack, as there is no host to forward it to.
+fn pop(storage: usize) -> Function {
+    match StorageKind::from(storage) {
+        StorageKind::Queue => Function::PopFront,
+        StorageKind::Stack | StorageKind::Stream => Function::PopBack,
+    }
+}
+
+fn int(options: &Options) -> Type {
+    match options.width {
+        Width::W32 => Type::I32,
+        _ => Type::I64,
+    }
+}
+
+// Encodes `module` in the binary format.
+pub(crate) fn encode(module: &Module) -> Vec<u8> {
+    let mut types: Vec<(Vec<Type>, Vec<Type>)> = vec![];
+    let mut type_of = |params: Vec<Type>, results: &[Type]| {
+        let signature = (params, results.to_vec());
+        let index = types.iter().position(|t| *t == signature);
+        index.unwrap_or_else(|| {
+            types.push(signature);
+            types.len() - 1
+        }) as u64
+    };
+    let import_types: Vec<_> = module
+        .imports
+        .iter()
+        .map(|import| type_of(import.params.clone(), &import.results))
+        .collect();
+    let func_types: Vec<_> = module
+        .funcs
+        .iter()
+        .map(|func| type_of(func.params.iter().map(|&(_, t)| t).collect(), &func.results))
+        .collect();
+    let functions: Vec<_> = module
+        .imports
+        .iter()
+        .map(|import| import.func)
+        .chain(module.funcs.iter().map(|func| func.name))
+        .collect();
+    let scope = Scope {
+        functions: &functions,
+        globals: &module.globals,
+    };
+
+    let mut output = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut output, 1, types.len(), |s| {
+        for (params, results) in &types {
+            s.push(0x60);
+            value_types(s, params);
+            value_types(s, results);
+        }
+    });
+    section(&mut output, 2, module.imports.len(), |s| {
+        for (import, &index) in module.imports.iter().zip(&import_types) {
+            vector(s, import.module.as_bytes());
+            vector(s, import.func.name().as_bytes());
+            s.push(0x00);
+            unsigned(s, index);
+        }
+    });
+    section(&mut output, 3, module.funcs.len(), |s| {
+        for &index in &func_types {
+            unsigned(s, index);
+        }
+    });
+    section(&mut output, 5, 1, |s| {
+        s.push(0x00);
+        unsigned(s, module.pages as u64);
+    });
+    section(&mut output, 6, module.globals.len(), |s| {
+        for &(_, init) in &module.globals {
+            s.extend([value_type(Type::I32), 0x01, 0x41]);
+            signed(s, init as i64);
+            s.push(0x0b);
+        }
+    });
+    let exports: Vec<_> = module.funcs.iter().filter(|f| f.export.is_some()).collect();
+    section(&mut output, 7, exports.len() + 1, |s| {
+        vector(s, b"memory");
+        s.extend([0x02, 0x00]);
+        for func in exports {
+            vector(s, func.export.unwrap().as_bytes());
+            s.push(0x00);
+            unsigned(s, scope.function(func.name));
+        }
+    });
+    section(&mut output, 10, module.funcs.len(), |s| {
+        for func in &module.funcs {
+            let code = code(func, &scope);
+            unsigned(s, code.len() as u64);
+            s.extend(code);
+        }
+    });
+    section(&mut output, 11, module.data.len(), |s| {
+        for &(offset, bytes) in &module.data {
+            s.extend([0x00, 0x41]);
+            signed(s, offset as i64);
+            s.push(0x0b);
+            vector(s, bytes);
+        }
+    });
+    output
+}
+
+struct Scope<'a> {
+    functions: &'a [Function],
+    globals: &'a [(Global, i32)],
+}
+
+impl Scope<'_> {
+    fn function(&self, function: Function) -> u64 {
+        self.functions.iter().position(|&f| f == function).unwrap() as u64
+    }
+
+    fn global(&self, global: Global) -> u64 {
+        self.globals.iter().position(|&(g, _)| g == global).unwrap() as u64
+    }
+}
+
+fn code(func: &Func, scope: &Scope) -> Vec<u8> {
+    let mut code = vec![];
+    // Locals are declared in runs of the same type.
+    let mut runs: Vec<(u64, Type)> = vec![];
+    for &(_, t) in &func.locals {
+        match runs.last_mut() {
+            Some((count, last)) if *last == t => *count += 1,
+            _ => runs.push((1, t)),
+        }
+    }
+    unsigned(&mut code, runs.len() as u64);
+    for (count, t) in runs {
+        unsigned(&mut code, count);
+        code.push(value_type(t));
+    }
+    // Labels of the enclosing blocks, innermost last, with `None` for conditionals.
+    let mut labels: Vec<Option<Label>> = vec![];
+    let depth = |labels: &[Option<Label>], label: Label| {
+        let index = labels.iter().rposition(|&l| l == Some(label));
+        (labels.len() - 1 - index.expect("branch to an enclosing label")) as u64
+    };
+    for instruction in &func.body {
+        match *instruction {
+            Instr::Block(label) => {
+                code.extend([0x02, 0x40]);
+                labels.push(Some(label));
+            }
+            Instr::Loop(label) => {
+                code.extend([0x03, 0x40]);
+                labels.push(Some(label));
+            }
+            Instr::If => {
+                code.extend([0x04, 0x40]);
+                labels.push(None);
+            }
+            Instr::Else => code.push(0x05),
+            Instr::End => {
+                code.push(0x0b);
+                labels.pop();
+            }
+            Instr::Br(label) => {
+                code.push(0x0c);
+                unsigned(&mut code, depth(&labels, label));
+            }
+            Instr::BrIf(label) => {
+                code.push(0x0d);
+                unsigned(&mut code, depth(&labels, label));
+            }
+            Instr::BrTable(ref targets, default) => {
+                code.push(0x0e);
+                unsigned(&mut code, targets.len() as u64);
+                for &target in targets {
+                    unsigned(&mut code, depth(&labels, target));
+                }
+                unsigned(&mut code, depth(&labels, default));
+            }
+            Instr::Return => code.push(0x0f),
+            Instr::Unreachable => code.push(0x00),
+            Instr::Drop => code.push(0x1a),
+            Instr::Select => code.push(0x1b),
+            Instr::Call(function) => {
+                code.push(0x10);
+                unsigned(&mut code, scope.function(function));
+            }
+            Instr::LocalGet(index) | Instr::LocalSet(index) | Instr::LocalTee(index) => {
+                code.push(match instruction {
+                    Instr::LocalGet(_) => 0x20,
+                    Instr::LocalSet(_) => 0x21,
+                    _ => 0x22,
+                });
+                unsigned(&mut code, index as u64);
+            }
+            Instr::GlobalGet(global) => {
+                code.push(0x23);
+                unsigned(&mut code, scope.global(global));
+            }
+            Instr::GlobalSet(global) => {
+                code.push(0x24);
+                unsigned(&mut code, scope.global(global));
+            }
+            Instr::Load(t, offset) => memory(&mut code, t.pick(0x28, 0x29), t.pick(2, 3), offset),
+            Instr::Load8S(t, offset) => memory(&mut code, t.pick(0x2c, 0x30), 0, offset),
+            Instr::Load8U(t, offset) => memory(&mut code, t.pick(0x2d, 0x31), 0, offset),
+            Instr::Store(t, offset) => memory(&mut code, t.pick(0x36, 0x37), t.pick(2, 3), offset),
+            Instr::Store8(t, offset) => memory(&mut code, t.pick(0x3a, 0x3c), 0, offset),
+            Instr::MemorySize => code.extend([0x3f, 0x00]),
+            Instr::MemoryGrow => code.extend([0x40, 0x00]),
+            Instr::Const(t, value) => {
+                code.push(t.pick(0x41, 0x42));
+                signed(&mut code, value);
+            }
+            Instr::Numeric(t, op) => {
+                let (_, i32, i64) = op.encoding();
+                code.push(t.pick(i32, i64));
+            }
+            Instr::WrapI64 => code.push(0xa7),
+            Instr::ExtendI32U => code.push(0xad),
+        }
+    }
+    code.push(0x0b);
+    code
+}
+
+impl Type {
+    // Returns `i32` or `i64` depending on the type.
+    fn pick(self, i32: u8, i64: u8) -> u8 {
+        match self {
+            Self::I32 => i32,
+            Self::I64 => i64,
+        }
+    }
+}
+
+fn memory(code: &mut Vec<u8>, opcode: u8, alignment: u8, offset: u32) {
+    code.extend([opcode, alignment]);
+    unsigned(code, offset as u64);
+}
+
+fn value_type(t: Type) -> u8 {
+    t.pick(0x7f, 0x7e)
+}
+
+fn value_types(output: &mut Vec<u8>, types: &[Type]) {
+    unsigned(output, types.len() as u64);
+    output.extend(types.iter().map(|&t| value_type(t)));
+}
+
+// Appends a section with `count` entries written by `write`, unless it is empty.
+fn section(output: &mut Vec<u8>, id: u8, count: usize, write: impl FnOnce(&mut Vec<u8>)) {
+    if count == 0 {
+        return;
+    }
+    let mut contents = vec![];
+    unsigned(&mut contents, count as u64);
+    write(&mut contents);
+    output.push(id);
+    unsigned(output, contents.len() as u64);
+    output.extend(contents);
+}
+
+fn vector(output: &mut Vec<u8>, bytes: &[u8]) {
+    unsigned(output, bytes.len() as u64);
+    output.extend(bytes);
+}
+
+fn unsigned(output: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+fn signed(output: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Write;
+
+use crate::wasm::{Func, Instr, Module};
+
+// Prints `module` in the text format, with the names of its functions, globals and locals.
+pub(crate) fn emit(module: &Module) -> String {
+    let mut output = "(module\n".to_string();
+    for import in &module.imports {
+        write!(
+            output,
+            "  (import \"{}\" \"{}\" (func ${}",
+            import.module,
+            import.func.name(),
+            import.func.name()
+        )
+        .ok();
+        for param in &import.params {
+            write!(output, " (param {param})").ok();
+        }
+        for result in &import.results {
+            write!(output, " (result {result})").ok();
+        }
+        output.push_str("))\n");
+    }
+    writeln!(output, "  (memory (export \"memory\") {})", module.pages).ok();
+    for &(offset, bytes) in &module.data {
+        write!(output, "  (data (i32.const {offset}) \"").ok();
+        for &byte in bytes {
+            if (b' '..=b'~').contains(&byte) && byte != b'"' && byte != b'\\' {
+                output.push(byte as char);
+            } else {
+                write!(output, "\\{byte:02x}").ok();
+            }
+        }
+        output.push_str("\")\n");
+    }
+    for &(global, init) in &module.globals {
+        writeln!(
+            output,
+            "  (global ${} (mut i32) (i32.const {init}))",
+            global.name()
+        )
+        .ok();
+    }
+    for func in &module.funcs {
+        emit_func(&mut output, func);
+    }
+    output.push_str(")\n");
+    output
+}
+
+fn emit_func(output: &mut String, func: &Func) {
+    write!(output, "  (func ${}", func.name.name()).ok();
+    if let Some(export) = func.export {
+        write!(output, " (export \"{export}\")").ok();
+    }
+    for (name, t) in &func.params {
+        write!(output, " (param ${name} {t})").ok();
+    }
+    for result in &func.results {
+        write!(output, " (result {result})").ok();
+    }
+    output.push('\n');
+    for (name, t) in &func.locals {
+        writeln!(output, "    (local ${name} {t})").ok();
+    }
+    for instruction in &func.body {
+        output.push_str("    ");
+        match instruction {
+            Instr::Block(label) => write!(output, "block {label}"),
+            Instr::Loop(label) => write!(output, "loop {label}"),
+            Instr::If => write!(output, "if"),
+            Instr::Else => write!(output, "else"),
+            Instr::End => write!(output, "end"),
+            Instr::Br(label) => write!(output, "br {label}"),
+            Instr::BrIf(label) => write!(output, "br_if {label}"),
+            Instr::BrTable(targets, default) => {
+                output.push_str("br_table");
+                for target in targets {
+                    write!(output, " {target}").ok();
+                }
+                write!(output, " {default}")
+            }
+            Instr::Return => write!(output, "return"),
+            Instr::Unreachable => write!(output, "unreachable"),
+            Instr::Drop => write!(output, "drop"),
+            Instr::Select => write!(output, "select"),
+            Instr::Call(function) => write!(output, "call ${}", function.name()),
+            Instr::LocalGet(index) => write!(output, "local.get ${}", func.local(*index)),
+            Instr::LocalSet(index) => write!(output, "local.set ${}", func.local(*index)),
+            Instr::LocalTee(index) => write!(output, "local.tee ${}", func.local(*index)),
+            Instr::GlobalGet(global) => write!(output, "global.get ${}", global.name()),
+            Instr::GlobalSet(global) => write!(output, "global.set ${}", global.name()),
+            Instr::Load(t, offset) => memory(output, &format!("{t}.load"), *offset),
+            Instr::Load8S(t, offset) => memory(output, &format!("{t}.load8_s"), *offset),
+            Instr::Load8U(t, offset) => memory(output, &format!("{t}.load8_u"), *offset),
+            Instr::Store(t, offset) => memory(output, &format!("{t}.store"), *offset),
+            Instr::Store8(t, offset) => memory(output, &format!("{t}.store8"), *offset),
+            Instr::MemorySize => write!(output, "memory.size"),
+            Instr::MemoryGrow => write!(output, "memory.grow"),
+            Instr::Const(t, value) => write!(output, "{t}.const {value}"),
+            Instr::Numeric(t, op) => write!(output, "{t}.{}", op.encoding().0),
+            Instr::WrapI64 => write!(output, "i32.wrap_i64"),
+            Instr::ExtendI32U => write!(output, "i64.extend_i32_u"),
+        }
+        .ok();
+        output.push('\n');
+    }
+    output.push_str("  )\n");
+}
+
+fn memory(output: &mut String, name: &str, offset: u32) -> std::fmt::Result {
+    output.push_str(name);
+    if offset != 0 {
+        write!(output, " offset={offset}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,376 @@
+use std::fmt;
+
+// An x86-64 program, which the assembly backend prints in AT&T syntax and the executable backend
+// encodes.
+#[derive(Default)]
+pub(crate) struct Program {
+    pub(crate) text: Vec<Inst>,
+    // Read-only data, placed after the code.
+    pub(crate) rodata: Vec<(Symbol, Vec<u8>)>,
+    // Zeroed data with its size, aligned to 16 bytes as a whole.
+    pub(crate) bss: Vec<(Symbol, usize)>,
+    locals: usize,
+}
+
+impl Program {
+    // Returns `N` new local labels.
+    pub(crate) fn locals<const N: usize>(&mut self) -> [Symbol; N] {
+        self.locals += N;
+        std::array::from_fn(|i| Symbol::Local(self.locals - N + i))
+    }
+
+    pub(crate) fn push(&mut self, inst: Inst) {
+        self.text.push(inst);
+    }
+
+    pub(crate) fn extend(&mut self, insts: impl IntoIterator<Item = Inst>) {
+        self.text.extend(insts);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Symbol {
+    // Function or variable of the runtime, or the entry point.
+    Named(&'static str),
+    // Start of the code of a block of the program.
+    Block(usize),
+    // Code reporting a trap, and its message.
+    Trap(usize),
+    Message(usize),
+    Local(usize),
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Named(name) => f.write_str(name),
+            Self::Block(label) => write!(f, ".LB{label}"),
+            Self::Trap(index) => write!(f, ".Lt{index}"),
+            Self::Message(index) => write!(f, ".Lm{index}"),
+            Self::Local(index) => write!(f, ".L{index}"),
+        }
+    }
+}
+
+// General purpose registers, in the order of their numbers in the encoding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Reg {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    // Clobbered by `syscall`, so it never holds anything.
+    #[allow(dead_code)]
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl Reg {
+    pub(crate) fn number(self) -> u8 {
+        self as u8
+    }
+
+    fn name(self, size: Size) -> &'static str {
+        const NAMES: [[&str; 16]; 3] = [
+            [
+                "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b",
+                "r12b", "r13b", "r14b", "r15b",
+            ],
+            [
+                "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d",
+                "r11d", "r12d", "r13d", "r14d", "r15d",
+            ],
+            [
+                "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11",
+                "r12", "r13", "r14", "r15",
+            ],
+        ];
+        NAMES[size as usize][self as usize]
+    }
+}
+
+// Size of the operands of an instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Size {
+    B,
+    L,
+    Q,
+}
+
+impl Size {
+    fn suffix(self) -> char {
+        match self {
+            Self::B => 'b',
+            Self::L => 'l',
+            Self::Q => 'q',
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Operand {
+    Reg(Reg),
+    Imm(i64),
+    // `disp(base, index, scale)`.
+    Mem {
+        base: Option<Reg>,
+        index: Option<(Reg, u8)>,
+        disp: i32,
+    },
+    // `symbol+addend(%rip)`.
+    Rip(Symbol, i32),
+}
+
+impl From<Reg> for Operand {
+    fn from(reg: Reg) -> Self {
+        Self::Reg(reg)
+    }
+}
+
+pub(crate) fn imm(value: i64) -> Operand {
+    Operand::Imm(value)
+}
+
+pub(crate) fn mem(base: Reg, disp: i32) -> Operand {
+    Operand::Mem {
+        base: Some(base),
+        index: None,
+        disp,
+    }
+}
+
+// `disp(base, index, scale)`, where `base` may be left out.
+pub(crate) fn indexed(base: Option<Reg>, index: Reg, scale: u8, disp: i32) -> Operand {
+    Operand::Mem {
+        base,
+        index: Some((index, scale)),
+        disp,
+    }
+}
+
+pub(crate) fn rip(name: &'static str, addend: i32) -> Operand {
+    Operand::Rip(Symbol::Named(name), addend)
+}
+
+// The instructions the backends use, with the source before the destination as in AT&T syntax.
+#[derive(Clone, Debug)]
+pub(crate) enum Inst {
+    Label(Symbol),
+    Mov(Size, Operand, Operand),
+    // Loads a 64-bit immediate.
+    Movabs(i64, Reg),
+    Movzbl(Operand, Reg),
+    Movsbq(Operand, Reg),
+    Movslq(Operand, Reg),
+    Lea(Operand, Reg),
+    Alu(Alu, Size, Operand, Operand),
+    Test(Size, Operand, Operand),
+    Unary(Unary, Size, Operand),
+    Shift(Shift, Size, u8, Operand),
+    Imul(Size, Operand, Reg),
+    // Multiplies the source by an immediate.
+    ImulImm(Size, i64, Operand, Reg),
+    Setge(Reg),
+    Push(Reg),
+    Pop(Reg),
+    Jmp(Symbol),
+    J(Cond, Symbol),
+    Call(Symbol),
+    Ret,
+    Syscall,
+    Cltd,
+    Cqto,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Alu {
+    Add,
+    Or,
+    And,
+    Sub,
+    Xor,
+    Cmp,
+}
+
+impl Alu {
+    // Name, and the extension of the opcode in the ModRM byte.
+    pub(crate) fn encoding(self) -> (&'static str, u8) {
+        match self {
+            Self::Add => ("add", 0),
+            Self::Or => ("or", 1),
+            Self::And => ("and", 4),
+            Self::Sub => ("sub", 5),
+            Self::Xor => ("xor", 6),
+            Self::Cmp => ("cmp", 7),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Unary {
+    Inc,
+    Dec,
+    Neg,
+    Div,
+    Idiv,
+}
+
+impl Unary {
+    // Name, the opcode for bytes, and the extension of the opcode in the ModRM byte.
+    pub(crate) fn encoding(self) -> (&'static str, u8, u8) {
+        match self {
+            Self::Inc => ("inc", 0xfe, 0),
+            Self::Dec => ("dec", 0xfe, 1),
+            Self::Neg => ("neg", 0xf6, 3),
+            Self::Div => ("div", 0xf6, 6),
+            Self::Idiv => ("idiv", 0xf6, 7),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Shift {
+    Shl,
+    Shr,
+}
+
+impl Shift {
+    // Name, and the extension of the opcode in the ModRM byte.
+    pub(crate) fn encoding(self) -> (&'static str, u8) {
+        match self {
+            Self::Shl => ("shl", 4),
+            Self::Shr => ("shr", 5),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Cond {
+    O,
+    B,
+    Ae,
+    E,
+    Ne,
+    Be,
+    A,
+    Ns,
+    L,
+    Le,
+    G,
+}
+
+impl Cond {
+    // Name, and the condition code in the opcode.
+    pub(crate) fn encoding(self) -> (&'static str, u8) {
+        match self {
+            Self::O => ("o", 0x0),
+            Self::B => ("b", 0x2),
+            Self::Ae => ("ae", 0x3),
+            Self::E => ("e", 0x4),
+            Self::Ne => ("ne", 0x5),
+            Self::Be => ("be", 0x6),
+            Self::A => ("a", 0x7),
+            Self::Ns => ("ns", 0x9),
+            Self::L => ("l", 0xc),
+            Self::Le => ("le", 0xe),
+            Self::G => ("g", 0xf),
+        }
+    }
+}
+
+// Formats `operand`, naming registers in `size`.
+struct Sized(Operand, Size);
+
+impl fmt::Display for Sized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Operand::Reg(reg) => write!(f, "%{}", reg.name(self.1)),
+            Operand::Imm(value) => write!(f, "${value}"),
+            Operand::Mem { base, index, disp } => {
+                if disp != 0 || base.is_none() {
+                    write!(f, "{disp}")?;
+                }
+                f.write_str("(")?;
+                if let Some(base) = base {
+                    write!(f, "%{}", base.name(Size::Q))?;
+                }
+                if let Some((index, scale)) = index {
+                    write!(f, ", %{}, {scale}", index.name(Size::Q))?;
+                }
+                f.write_str(")")
+            }
+            Operand::Rip(symbol, 0) => write!(f, "{symbol}(%rip)"),
+            Operand::Rip(symbol, addend) => write!(f, "{symbol}+{addend}(%rip)"),
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let q = |operand| Sized(operand, Size::Q);
+        match *self {
+            Self::Label(symbol) => write!(f, "{symbol}:"),
+            Self::Mov(size, src, dst) => {
+                let s = size.suffix();
+                write!(f, "\tmov{s} {}, {}", Sized(src, size), Sized(dst, size))
+            }
+            Self::Movabs(value, dst) => write!(f, "\tmovabsq ${value}, {}", q(dst.into())),
+            Self::Movzbl(src, dst) => {
+                let dst = Sized(dst.into(), Size::L);
+                write!(f, "\tmovzbl {}, {dst}", Sized(src, Size::B))
+            }
+            Self::Movsbq(src, dst) => {
+                write!(f, "\tmovsbq {}, {}", Sized(src, Size::B), q(dst.into()))
+            }
+            Self::Movslq(src, dst) => {
+                write!(f, "\tmovslq {}, {}", Sized(src, Size::L), q(dst.into()))
+            }
+            Self::Lea(src, dst) => write!(f, "\tleaq {}, {}", q(src), q(dst.into())),
+            Self::Alu(op, size, src, dst) => {
+                let (name, s) = (op.encoding().0, size.suffix());
+                write!(f, "\t{name}{s} {}, {}", Sized(src, size), Sized(dst, size))
+            }
+            Self::Test(size, src, dst) => {
+                let s = size.suffix();
+                write!(f, "\ttest{s} {}, {}", Sized(src, size), Sized(dst, size))
+            }
+            Self::Unary(op, size, dst) => {
+                let (name, s) = (op.encoding().0, size.suffix());
+                write!(f, "\t{name}{s} {}", Sized(dst, size))
+            }
+            Self::Shift(op, size, count, dst) => {
+                let (name, s) = (op.encoding().0, size.suffix());
+                write!(f, "\t{name}{s} ${count}, {}", Sized(dst, size))
+            }
+            Self::Imul(size, src, dst) => {
+                let dst = Sized(dst.into(), size);
+                write!(f, "\timul{} {}, {dst}", size.suffix(), Sized(src, size))
+            }
+            Self::ImulImm(size, value, src, dst) => {
+                let dst = Sized(dst.into(), size);
+                let s = size.suffix();
+                write!(f, "\timul{s} ${value}, {}, {dst}", Sized(src, size))
+            }
+            Self::Setge(dst) => write!(f, "\tsetge {}", Sized(dst.into(), Size::B)),
+            Self::Push(reg) => write!(f, "\tpushq {}", q(reg.into())),
+            Self::Pop(reg) => write!(f, "\tpopq {}", q(reg.into())),
+            Self::Jmp(symbol) => write!(f, "\tjmp {symbol}"),
+            Self::J(cond, symbol) => write!(f, "\tj{} {symbol}", cond.encoding().0),
+            Self::Call(symbol) => write!(f, "\tcall {symbol}"),
+            Self::Ret => f.write_str("\tret"),
+            Self::Syscall => f.write_str("\tsyscall"),
+            Self::Cltd => f.write_str("\tcltd"),
+            Self::Cqto => f.write_str("\tcqto"),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! Assembles the x86-64 assembly generated for every case, where `as` and `ld` are available,
+//! and compares it with the interpreter.
+
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+mod common;
+
+use palheui::{transpile_asm, Width};
+
+#[test]
+fn asm_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        (cases.iter())
+            .map(|(code, inputs, options)| {
+                common::run_asm(&transpile_asm(code, options).unwrap(), inputs)
+            })
+            .collect()
+    });
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{Case, COUNTDOWN, COUNTDOWN_INPUTS};
+use palheui::{transpile_with, Options, Width};
+
+// Sums the values pushed to the ㄱ storage into the default one, with a loop that stops when ㄱ
+// runs out.
+const SUM: &str = "숙아아\n분아아\n분아아\n분아호\n수아몽\n부아소\n삭삭쑤\n아도서";
+// Pushes 8, 6, 4, 2 and 0 to ㄱ with a countdown, which the linearizer does not keep count of,
+// and 2 and 2 that it does. The loop summing them is entered through both entries of a block.
+const COUNTDOWN_SUM: &str =
+    "붏아수써사망희\n뿌아다속\n우아아본\n우아아본\n우아아속\n아싹빠추\n아뽀터번";
+
+// Labels of the blocks a `goto` enters with values in locals.
+fn register_entries(c: &str) -> Vec<&str> {
+    c.lines()
+        .filter_map(|line| line.trim().strip_prefix("goto R"))
+        .map(|label| label.trim_end_matches(';'))
+        .collect()
+}
+
+// Labels of the blocks branches enter with values in memory.
+fn branch_targets(c: &str) -> Vec<&str> {
+    let mut targets = vec![];
+    for line in c.lines().map(str::trim) {
+        let Some((name, args)) = line.split_once('(') else {
+            continue;
+        };
+        let operands = match name {
+            "JSL" | "JSLE" => 2,
+            "JNZS" | "JNZQ" | "JNZE" | "JZS" | "JZQ" | "JZE" => 1,
+            _ => continue,
+        };
+        let args = args.trim_end_matches(';').trim_end_matches(')');
+        targets.extend(args.split(", ").skip(operands));
+    }
+    targets
+}
+
+fn check(code: &str) {
+    for bigint in [false, true] {
+        let options = Options {
+            bigint,
+            ..Options::default()
+        };
+        let c = transpile_with(code, &options).unwrap();
+        let Some(outcomes) = common::run_c(&c, &[""]) else {
+            return;
+        };
+        common::check(&outcomes[0], code, "", &Options::default());
+    }
+}
+
+#[test]
+fn keeps_loop_values_in_locals() {
+    let c = transpile_with(SUM, &Options::default()).unwrap();
+    assert!(!register_entries(&c).is_empty());
+    check(SUM);
+}
+
+#[test]
+fn enters_block_through_both_entries() {
+    let c = transpile_with(COUNTDOWN_SUM, &Options::default()).unwrap();
+    let targets = branch_targets(&c);
+    let entries = register_entries(&c);
+    assert!(entries.iter().any(|label| targets.contains(label)));
+    check(COUNTDOWN_SUM);
+}
+
+#[test]
+fn reloads_values_entering_by_branch() {
+    // One entry to the loop is a `goto` passing values in locals, and the others are branches
+    // that reload them from memory.
+    let c = transpile_with(COUNTDOWN, &Options::default()).unwrap();
+    let targets = branch_targets(&c);
+    let entries = register_entries(&c);
+    assert!(entries.iter().any(|label| targets.contains(label)));
+    let mut matrix = common::matrix(&[Width::W32, Width::W64]);
+    matrix.push(Options {
+        bigint: true,
+        ..Options::default()
+    });
+    let cases: Vec<Case> = (matrix.into_iter())
+        .map(|options| (COUNTDOWN, COUNTDOWN_INPUTS, options))
+        .collect();
+    common::check_backend(&cases, |cases| {
+        (cases.iter())
+            .map(|(code, inputs, options)| {
+                common::run_c(&transpile_with(code, options).unwrap(), inputs)
+            })
+            .collect()
+    });
+}
@@ -0,0 +1,403 @@
+// Every test binary uses a different part of these helpers.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use palheui::{interpret_with, Error, Integer, Options, Overflow, Width, ZeroDivision};
+
+/// Programs with the inputs to run them with.
+pub const CASES: &[(&str, &[&str])] = &[
+    // Reads two values, prints the result of an operation on them, and exits with it.
+    ("방방다빠망하", ARITHMETIC),
+    ("방방타빠망하", ARITHMETIC),
+    ("방방따빠망하", ARITHMETIC),
+    ("방방나빠망하", ARITHMETIC),
+    ("방방라빠망하", ARITHMETIC),
+    ("방방자빠망하", ARITHMETIC),
+    // Echoes a character, and prints the one with the code point it reads, even a surrogate.
+    (
+        "밯맣방맣희",
+        &["A65", "가55296", "😀 128512", "\u{80}-1", ""],
+    ),
+    // Counts down from 8 in steps of 2.
+    (
+        "박밣빠망반타빠추\n아아오어어어어어\n아아아아아아아희",
+        &[""],
+    ),
+    (COUNTDOWN, COUNTDOWN_INPUTS),
+    // Uses the ㅎ storage, which is a stack unless the host extends it.
+    ("샇반받다빠망망희", &[""]),
+    // Overflows the 64-bit integers.
+    ("밝빠따빠따빠따빠따빠따빠따망하", &[""]),
+    ("반밝타빠반나망반라망하", &[""]),
+    ("반받싹사망삭망하", &[""]),
+    // Uses values pushed before a branch, which a block may know to be there.
+    ("반반반차다망하", &[""]),
+    ("샇반반반차다망희", &[""]),
+    ("상반반반차다망하", &[""]),
+    // Takes values from the front of the queue in a block that also pushes to it.
+    ("상박받반차밖밙다망하", &[""]),
+    ("상박받사밤차상밝망희", &[""]),
+    ("상박받반반반차파차망망희", &[""]),
+    ("상박받반반차빠차망망희", &[""]),
+];
+
+/// Counts down from the number it reads in steps of 2, and sums the values with a loop that a
+/// `goto` and branches both enter.
+pub const COUNTDOWN: &str =
+    "붕아수써사망희\n뿌아다속\n우아아본\n우아아본\n우아아속\n아싹빠추\n아뽀터번";
+pub const COUNTDOWN_INPUTS: &[&str] = &["8", "4", "0", "12"];
+
+/// Squares 7 six times, which needs more than 128 bits, and what it prints.
+pub const SQUARES: (&str, &str) = (
+    "밝빠따빠따빠따빠따빠따빠따망하",
+    "1219760487635835700138573862562971820755615294131238401",
+);
+
+// Operands around the limits of every width, and zero divisors.
+const ARITHMETIC: &[&str] = &[
+    "7 2",
+    "-7 2",
+    "7 -2",
+    "7 0",
+    "2147483647 1",
+    "-2147483648 -1",
+    "-2147483648 1",
+    "65536 65536",
+    "9223372036854775807 1",
+    "-9223372036854775808 -1",
+    "4294967296 4294967296",
+    "170141183460469231731687303715884105727 1",
+];
+
+/// A program with the inputs and the options to run it with.
+pub type Case = (&'static str, &'static [&'static str], Options);
+
+/// Every program of [`CASES`] with each of `matrix`.
+pub fn cases(matrix: &[Options]) -> Vec<Case> {
+    let mut cases = vec![];
+    for options in matrix {
+        for &(code, inputs) in CASES {
+            cases.push((code, inputs, options.clone()));
+        }
+    }
+    cases
+}
+
+/// Cases whose values fit in 128 bits, with options for big integers, on which the backends must
+/// agree with the interpreter's 128-bit integers.
+pub fn bigint_cases() -> Vec<Case> {
+    let mut cases = vec![];
+    for options in matrix(&[Width::W128]) {
+        for &(code, inputs) in &CASES[..6] {
+            let options = Options {
+                bigint: true,
+                ..options.clone()
+            };
+            // The last input overflows 128 bits.
+            cases.push((code, &inputs[..inputs.len() - 1], options));
+        }
+    }
+    cases
+}
+
+/// Options whose behaviour every backend defines, for each of `widths`.
+///
+/// Undefined overflow and division by zero are left out, as the generated code may do anything.
+pub fn matrix(widths: &[Width]) -> Vec<Options> {
+    let mut matrix = vec![];
+    for &width in widths {
+        for overflow in [Overflow::Wrap, Overflow::Trap] {
+            for zero_division in [
+                ZeroDivision::Trap,
+                ZeroDivision::Zero,
+                ZeroDivision::Reflect,
+            ] {
+                matrix.push(Options {
+                    width,
+                    overflow,
+                    zero_division,
+                    ..Options::default()
+                });
+            }
+        }
+    }
+    matrix
+}
+
+/// What a program wrote, and how it ended.
+#[derive(Debug)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    /// Exit code, or `None` if a signal killed the program.
+    pub code: Option<i32>,
+    pub error: String,
+}
+
+/// Asserts that `outcome` is what the interpreter does with `code`, `input` and `options`.
+///
+/// A trap must end the program unsuccessfully, with the message of the interpreter's error.
+pub fn check(outcome: &Outcome, code: &str, input: &str, options: &Options) {
+    let mut expected = vec![];
+    let result = interpret_with(code, input.as_bytes(), &mut expected, options);
+    let case = format!("{code:?} with {input:?} and {options:?}");
+    assert_eq!(outcome.output, expected, "{case}");
+    match result {
+        Ok(exit) => assert_eq!(outcome.code, Some(exit as u8 as i32), "{case}"),
+        Err(e) => {
+            assert_ne!(outcome.code, Some(0), "{case}");
+            assert!(
+                outcome.error.contains(&e.to_string()),
+                "{case}: {outcome:?}"
+            );
+        }
+    }
+}
+
+/// Runs `cases` with the backend `run`, and checks every outcome against the interpreter.
+///
+/// `run` returns the outcomes of each case for each of its inputs, or `None` where the tools of
+/// the backend are missing.
+pub fn check_backend(cases: &[Case], run: impl FnOnce(&[Case]) -> Option<Vec<Vec<Outcome>>>) {
+    let Some(outcomes) = run(cases) else {
+        return;
+    };
+    for ((code, inputs, options), outcomes) in cases.iter().zip(outcomes) {
+        for (outcome, input) in outcomes.iter().zip(*inputs) {
+            check(outcome, code, input, options);
+        }
+    }
+}
+
+/// Runs every case with `run`, which executes a program in this process like the interpreter.
+///
+/// An error stands for a trap, which no exit code reports.
+pub fn run_in_process(
+    cases: &[Case],
+    run: impl Fn(&str, &str, &mut Vec<u8>, &Options) -> Result<Integer, Error>,
+) -> Option<Vec<Vec<Outcome>>> {
+    let outcomes = (cases.iter())
+        .map(|(code, inputs, options)| {
+            (inputs.iter())
+                .map(|input| {
+                    let mut output = vec![];
+                    let result = run(code, input, &mut output, options);
+                    Outcome {
+                        output,
+                        code: result.as_ref().ok().map(|&exit| exit as u8 as i32),
+                        error: result.err().map_or(String::new(), |e| e.to_string()),
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Some(outcomes)
+}
+
+/// Returns whether `tool` can be run, and tells that the test is skipped where it is missing.
+pub fn available(tool: &str) -> bool {
+    let found = Command::new(tool)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok();
+    if !found {
+        // The harness captures `eprintln!`, which would hide the notice from passing tests.
+        writeln!(std::io::stderr(), "skipping: `{tool}` is not available").ok();
+    }
+    found
+}
+
+/// Creates an empty directory for the files of one test.
+///
+/// Tests run in parallel, so every call returns a new directory.
+pub fn build_dir() -> PathBuf {
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+    let build = BUILDS.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("palheui-{}-{build}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `command` with `input`, and returns what it did.
+pub fn execute(command: &mut Command, input: &str) -> Outcome {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The program may exit before reading everything.
+    child.stdin.take().unwrap().write_all(input.as_bytes()).ok();
+    let result = child.wait_with_output().unwrap();
+    Outcome {
+        output: result.stdout,
+        code: result.status.code(),
+        error: String::from_utf8_lossy(&result.stderr).into_owned(),
+    }
+}
+
+/// Compiles C `source` and runs it with each of `inputs`, or returns `None` where no C compiler
+/// is available.
+pub fn run_c(source: &str, inputs: &[&str]) -> Option<Vec<Outcome>> {
+    run_c_extended(source, "", inputs)
+}
+
+/// Compiles C `source`, linking it with the C `extension` for the ㅎ storage, and runs it like
+/// [`run_c`].
+pub fn run_c_extended(source: &str, extension: &str, inputs: &[&str]) -> Option<Vec<Outcome>> {
+    if !available("cc") {
+        return None;
+    }
+    let dir = build_dir();
+    fs::write(dir.join("main.c"), source).unwrap();
+    fs::write(dir.join("extension.c"), extension).unwrap();
+    let mut cc = Command::new("cc");
+    cc.arg("-w")
+        .arg(dir.join("main.c"))
+        .arg(dir.join("extension.c"));
+    build(&mut cc, &dir);
+    Some(run_built(&dir, inputs))
+}
+
+/// Compiles LLVM IR `source` and runs it like [`run_c`], or returns `None` where `llc` or a C
+/// compiler to link with is missing.
+pub fn run_llvm(source: &str, inputs: &[&str]) -> Option<Vec<Outcome>> {
+    if !available("llc") || !available("cc") {
+        return None;
+    }
+    let dir = build_dir();
+    fs::write(dir.join("main.ll"), source).unwrap();
+    let version = Command::new("llc")
+        .arg("--version")
+        .output()
+        .unwrap()
+        .stdout;
+    let mut llc = Command::new("llc");
+    // Opaque pointers are the default from LLVM 15 on.
+    if String::from_utf8_lossy(&version).contains("LLVM version 14.") {
+        llc.arg("-opaque-pointers");
+    }
+    let status = llc
+        .args(["-filetype=obj", "-relocation-model=pic"])
+        .arg(dir.join("main.ll"))
+        .arg("-o")
+        .arg(dir.join("main.o"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    build(Command::new("cc").arg(dir.join("main.o")), &dir);
+    Some(run_built(&dir, inputs))
+}
+
+/// Assembles and links GNU assembly `source` and runs it like [`run_c`], or returns `None` where
+/// `as` or `ld` is missing.
+pub fn run_asm(source: &str, inputs: &[&str]) -> Option<Vec<Outcome>> {
+    if !available("as") || !available("ld") {
+        return None;
+    }
+    let dir = build_dir();
+    fs::write(dir.join("main.s"), source).unwrap();
+    let status = Command::new("as")
+        .arg(dir.join("main.s"))
+        .arg("-o")
+        .arg(dir.join("main.o"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    build(Command::new("ld").arg(dir.join("main.o")), &dir);
+    Some(run_built(&dir, inputs))
+}
+
+/// Writes the executable `binary`, and runs it like [`run_c`].
+pub fn run_exe(binary: &[u8], inputs: &[&str]) -> Vec<Outcome> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = build_dir();
+    let path = dir.join("main");
+    fs::write(&path, binary).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    run_built(&dir, inputs)
+}
+
+// Runs the compiler `command`, telling it to write `main` in `dir`.
+fn build(command: &mut Command, dir: &Path) {
+    let status = command.arg("-o").arg(dir.join("main")).status().unwrap();
+    assert!(status.success());
+}
+
+// Runs the `main` built in `dir` with each of `inputs`, and removes `dir`.
+fn run_built(dir: &Path, inputs: &[&str]) -> Vec<Outcome> {
+    let binary = dir.join("main");
+    let outcomes = inputs
+        .iter()
+        .map(|input| execute(&mut Command::new(&binary), input))
+        .collect();
+    fs::remove_dir_all(dir).ok();
+    outcomes
+}
+
+/// Programs that one process of an interpreter runs together, as starting it for every program
+/// would take longer than running them.
+///
+/// The driver gets the directory of the batch as its argument. It runs each `{n}.{extension}`
+/// with the input in every `{n}-{k}.in`, writing the output, the error output and the exit code,
+/// or `trap` if the program trapped, to `{n}-{k}.out`, `{n}-{k}.err` and `{n}-{k}.code`.
+pub struct Batch {
+    dir: PathBuf,
+    inputs: Vec<usize>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self {
+            dir: build_dir(),
+            inputs: vec![],
+        }
+    }
+
+    /// Adds `program` with the file `extension`, to be run with each of `inputs`.
+    pub fn add(&mut self, extension: &str, program: &[u8], inputs: &[&str]) {
+        let n = self.inputs.len();
+        fs::write(self.dir.join(format!("{n}.{extension}")), program).unwrap();
+        for (k, input) in inputs.iter().enumerate() {
+            fs::write(self.dir.join(format!("{n}-{k}.in")), input).unwrap();
+        }
+        self.inputs.push(inputs.len());
+    }
+
+    /// Runs the batch with `driver`, written to the file `name`, under `tool`, and returns the
+    /// outcomes of each program.
+    pub fn run(self, tool: &str, name: &str, driver: &str) -> Vec<Vec<Outcome>> {
+        let path = self.dir.join(name);
+        fs::write(&path, driver).unwrap();
+        let status = Command::new(tool)
+            .arg(&path)
+            .arg(&self.dir)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let read = |n: usize, k: usize, extension: &str| {
+            fs::read(self.dir.join(format!("{n}-{k}.{extension}"))).unwrap()
+        };
+        let outcomes = (self.inputs.iter().enumerate())
+            .map(|(n, &inputs)| {
+                (0..inputs)
+                    .map(|k| Outcome {
+                        output: read(n, k, "out"),
+                        code: String::from_utf8(read(n, k, "code")).unwrap().parse().ok(),
+                        error: String::from_utf8_lossy(&read(n, k, "err")).into_owned(),
+                    })
+                    .collect()
+            })
+            .collect();
+        fs::remove_dir_all(&self.dir).ok();
+        outcomes
+    }
+}
@@ -0,0 +1,188 @@
+//! Runs the same programs on the interpreter, on the generated code and on the other executors,
+//! which must agree with it.
+
+mod common;
+
+use common::Case;
+use palheui::{
+    bytecode, interpret_with_extension, threaded, transpile, transpile_with, Extension, Integer,
+    Options, Width,
+};
+
+const WIDTHS: &[Width] = &[Width::W32, Width::W64, Width::W128];
+
+// Uses the ㅎ storage, adding what the extension sends and branching on the sum.
+const EXTENDED: &str = "샇반다차망희";
+
+// Extension that sends increasing values and records the ones it receives.
+#[derive(Default)]
+struct Counter {
+    last: Integer,
+    received: Vec<Integer>,
+}
+
+impl Extension for Counter {
+    fn len(&self) -> usize {
+        2
+    }
+
+    fn push(&mut self, v: Integer) {
+        self.received.push(v);
+    }
+
+    fn pop(&mut self) -> Integer {
+        self.last += 1;
+        self.last
+    }
+
+    fn peek(&self) -> Integer {
+        self.last + 1
+    }
+}
+
+// `Counter` for the generated C code, writing the values it receives to the error output.
+const COUNTER: &str = r#"
+#include <stdint.h>
+#include <stdio.h>
+
+static int64_t last;
+
+int aheui_ext_size(void) {
+    return 2;
+}
+void aheui_ext_push(int64_t v) {
+    fprintf(stderr, "%lld\n", (long long)v);
+}
+int64_t aheui_ext_pop(void) {
+    return ++last;
+}
+int64_t aheui_ext_peek(void) {
+    return last + 1;
+}
+"#;
+
+fn interpreted_extended() -> (Vec<u8>, Integer, Vec<Integer>) {
+    let mut counter = Counter::default();
+    let mut output = vec![];
+    let exit = interpret_with_extension(
+        EXTENDED,
+        &b""[..],
+        &mut output,
+        &Options::default(),
+        &mut counter,
+    )
+    .unwrap();
+    (output, exit, counter.received)
+}
+
+fn run_c(cases: &[Case]) -> Option<Vec<Vec<common::Outcome>>> {
+    (cases.iter())
+        .map(|(code, inputs, options)| {
+            common::run_c(&transpile_with(code, options).unwrap(), inputs)
+        })
+        .collect()
+}
+
+#[test]
+fn c_matches_interpreter() {
+    common::check_backend(&common::cases(&common::matrix(WIDTHS)), run_c);
+}
+
+#[test]
+fn c_bigint_matches_interpreter() {
+    let options = Options {
+        bigint: true,
+        ..Options::default()
+    };
+    common::check_backend(&common::bigint_cases(), run_c);
+    let (square, squared) = common::SQUARES;
+    if let Some(outcomes) = common::run_c(&transpile_with(square, &options).unwrap(), &[""]) {
+        assert_eq!(outcomes[0].output, squared.as_bytes());
+    }
+}
+
+#[test]
+fn c_forwards_extension() {
+    let (output, exit, received) = interpreted_extended();
+    assert_eq!(
+        (&output[..], exit, &received[..]),
+        (&b"4"[..], 5, &[2, 3][..])
+    );
+    let c = transpile(EXTENDED).unwrap();
+    if let Some(outcomes) = common::run_c_extended(&c, COUNTER, &[""]) {
+        assert_eq!(outcomes[0].output, output);
+        assert_eq!(outcomes[0].code, Some(exit as i32));
+        assert_eq!(outcomes[0].error, "2\n3\n");
+    }
+}
+
+#[test]
+fn threaded_matches_interpreter() {
+    common::check_backend(&common::cases(&common::matrix(WIDTHS)), |cases| {
+        common::run_in_process(cases, |code, input, output, options| {
+            threaded::run(code, input.as_bytes(), output, options)
+        })
+    });
+}
+
+#[test]
+fn threaded_forwards_extension() {
+    let mut counter = Counter::default();
+    let mut output = vec![];
+    let exit = threaded::run_with_extension(
+        EXTENDED,
+        &b""[..],
+        &mut output,
+        &Options::default(),
+        &mut counter,
+    )
+    .unwrap();
+    assert_eq!((output, exit, counter.received), interpreted_extended());
+}
+
+#[test]
+fn bytecode_matches_interpreter() {
+    common::check_backend(&common::cases(&common::matrix(WIDTHS)), |cases| {
+        common::run_in_process(cases, |code, input, output, options| {
+            let compiled = bytecode::compile(code, options)?;
+            bytecode::run(&compiled, input.as_bytes(), output)
+        })
+    });
+}
+
+#[test]
+fn bytecode_forwards_extension() {
+    let compiled = bytecode::compile(EXTENDED, &Options::default()).unwrap();
+    let mut counter = Counter::default();
+    let mut output = vec![];
+    let exit =
+        bytecode::run_with_extension(&compiled, &b""[..], &mut output, &mut counter).unwrap();
+    assert_eq!((output, exit, counter.received), interpreted_extended());
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn jit_matches_interpreter() {
+    let cases = common::cases(&common::matrix(&[Width::W32, Width::W64]));
+    common::check_backend(&cases, |cases| {
+        common::run_in_process(cases, |code, input, output, options| {
+            palheui::jit::run(code, input.as_bytes(), output, options)
+        })
+    });
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn jit_forwards_extension() {
+    let mut counter = Counter::default();
+    let mut output = vec![];
+    let exit = palheui::jit::run_with_extension(
+        EXTENDED,
+        &b""[..],
+        &mut output,
+        &Options::default(),
+        &mut counter,
+    )
+    .unwrap();
+    assert_eq!((output, exit, counter.received), interpreted_extended());
+}
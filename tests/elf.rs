@@ -0,0 +1,23 @@
+//! Runs the x86-64 executables generated for every case, on x86-64 Linux, and compares them with
+//! the interpreter.
+
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+mod common;
+
+use palheui::{transpile_exe, Width};
+
+#[test]
+fn exe_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        (cases.iter())
+            .map(|(code, inputs, options)| {
+                Some(common::run_exe(
+                    &transpile_exe(code, options).unwrap(),
+                    inputs,
+                ))
+            })
+            .collect()
+    });
+}
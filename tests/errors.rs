@@ -0,0 +1,54 @@
+use std::process::Command;
+
+use palheui::{bytecode, interpret, linearize, parse, transpile, Error, Options};
+
+#[test]
+fn rejects_program_without_code() {
+    assert!(matches!(transpile(""), Err(Error::EmptyProgram)));
+    assert!(matches!(
+        interpret("", &b""[..], vec![]),
+        Err(Error::EmptyProgram)
+    ));
+    // Cells which are not instructions would make the program loop forever.
+    assert!(matches!(transpile("abc\n\n"), Err(Error::NoCode)));
+    assert!(matches!(linearize(&parse("abc")), Err(Error::NoCode)));
+    assert!(matches!(
+        interpret("ㅎㅏ", &b""[..], vec![]),
+        Err(Error::NoCode)
+    ));
+}
+
+#[test]
+fn rejects_invalid_bytecode() {
+    let compiled = bytecode::compile("반망하", &Options::default()).unwrap();
+    let reason = |bytecode: &[u8]| match bytecode::decode(bytecode) {
+        Err(Error::InvalidBytecode(reason)) => reason,
+        result => panic!("{:?}", result.map(|_| ())),
+    };
+    assert_eq!(reason(b"PAHB"), "not palheui bytecode");
+    assert_eq!(reason(&compiled[1..]), "not palheui bytecode");
+    let mut newer = compiled.clone();
+    newer[4] += 1;
+    assert_eq!(reason(&newer), "unsupported version");
+    let mut width = compiled.clone();
+    width[6] = 16;
+    assert_eq!(reason(&width), "unknown width");
+    assert!(matches!(
+        bytecode::decode(&compiled[..compiled.len() - 1]),
+        Err(Error::InvalidBytecode(_))
+    ));
+}
+
+#[test]
+fn reports_errors_on_stderr() {
+    let missing = std::env::temp_dir().join("palheui-missing.aheui");
+    let result = Command::new(env!("CARGO_BIN_EXE_palheui"))
+        .arg(&missing)
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(1));
+    assert!(result.stdout.is_empty());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.starts_with("palheui: "), "{stderr}");
+    assert!(stderr.contains(&*missing.to_string_lossy()), "{stderr}");
+}
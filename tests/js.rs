@@ -0,0 +1,120 @@
+//! Runs the JavaScript generated for every case under Node.js, where it is available, and
+//! compares it with the interpreter.
+
+mod common;
+
+use common::{Batch, Case, Outcome};
+use palheui::{transpile_js, Options, Width};
+
+// Imports each module and runs it, writing the message of a trap to the error output. With
+// `<strings>` true, runs the string entry point instead of the byte one, feeding the input one
+// UTF-16 code unit at a time and writing the output as UTF-16.
+const DRIVER: &str = r#"
+import { readdirSync, readFileSync, writeFileSync } from "node:fs";
+import { pathToFileURL } from "node:url";
+
+const strings = <strings>;
+const dir = process.argv[2];
+for (const name of readdirSync(dir).filter((name) => name.endsWith(".in"))) {
+  const run = `${dir}/${name.slice(0, -3)}`;
+  const module = await import(pathToFileURL(run.replace(/-[0-9]+$/, ".mjs")));
+  let input = readFileSync(`${run}.in`);
+  const output = [];
+  let read = () => {
+    const chunk = input;
+    input = null;
+    return chunk;
+  };
+  if (strings) {
+    let text = input.toString();
+    let n = 0;
+    read = () => (n < text.length ? text[n++] : null);
+  }
+  let code;
+  let error = "";
+  try {
+    const entry = strings ? module.run : module.runBytes;
+    code = String(entry(read, (chunk) => output.push(chunk)) & 255);
+  } catch (e) {
+    code = "trap";
+    error = e.message;
+  }
+  const bytes = strings ? Buffer.from(output.join(""), "utf16le") : Buffer.concat(output);
+  writeFileSync(`${run}.out`, bytes);
+  writeFileSync(`${run}.err`, error);
+  writeFileSync(`${run}.code`, code);
+}
+"#;
+
+// Runs the JavaScript generated for each of `cases` in one process of Node.js, through the string
+// entry point if `strings` is set.
+fn run_with(cases: &[Case], strings: bool) -> Option<Vec<Vec<Outcome>>> {
+    if !common::available("node") {
+        return None;
+    }
+    let mut batch = Batch::new();
+    for (code, inputs, options) in cases {
+        let module = transpile_js(code, options).unwrap();
+        batch.add("mjs", module.as_bytes(), inputs);
+    }
+    let driver = DRIVER.replace("<strings>", &strings.to_string());
+    Some(batch.run("node", "driver.mjs", &driver))
+}
+
+fn run(cases: &[Case]) -> Option<Vec<Vec<Outcome>>> {
+    run_with(cases, false)
+}
+
+// Encodes the UTF-16 output of the string entry point in UTF-8 like the interpreter prints it,
+// which writes a lone surrogate as the three bytes of its code point.
+fn wtf8(utf16: &[u8]) -> Vec<u8> {
+    let units = utf16
+        .chunks(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    let mut bytes = vec![];
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(e) => {
+                let s = e.unpaired_surrogate();
+                bytes.extend([
+                    0xe0 | (s >> 12) as u8,
+                    0x80 | (s >> 6 & 0x3f) as u8,
+                    0x80 | (s & 0x3f) as u8,
+                ]);
+            }
+        }
+    }
+    bytes
+}
+
+#[test]
+fn js_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64, Width::W128]);
+    common::check_backend(&common::cases(&matrix), run);
+}
+
+#[test]
+fn js_bigint_matches_interpreter() {
+    common::check_backend(&common::bigint_cases(), run);
+    let (square, squared) = common::SQUARES;
+    let options = Options {
+        bigint: true,
+        ..Options::default()
+    };
+    if let Some(outcomes) = run(&[(square, &[""], options)]) {
+        assert_eq!(outcomes[0][0].output, squared.as_bytes());
+    }
+}
+
+#[test]
+fn js_strings_match_interpreter() {
+    let matrix = common::matrix(&[Width::W64]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        let mut outcomes = run_with(cases, true)?;
+        for outcome in outcomes.iter_mut().flatten() {
+            outcome.output = wtf8(&outcome.output);
+        }
+        Some(outcomes)
+    });
+}
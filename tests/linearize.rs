@@ -0,0 +1,216 @@
+use palheui::ir::{BinaryOp, Block, Instruction, Terminator};
+use palheui::{
+    bytecode, interpret, interpret_with, linearize, linearize_with, parse, threaded, Error,
+    Integer, Options, Overflow, Width, ZeroDivision,
+};
+
+fn blocks(code: &str) -> Vec<Block> {
+    linearize(&parse(code)).unwrap()
+}
+
+fn checks(code: &str) -> usize {
+    blocks(code)
+        .iter()
+        .filter(|block| matches!(block.terminator, Terminator::Jsl { .. }))
+        .count()
+}
+
+// Values of the constants left after folding, or `None` if an operation was not folded.
+fn folded(code: &str, options: &Options) -> Option<Vec<Integer>> {
+    let blocks = linearize_with(&parse(code), options).unwrap();
+    let mut values = vec![];
+    for instruction in blocks.iter().flat_map(|block| &block.instructions) {
+        match *instruction {
+            Instruction::Binary { .. } => return None,
+            Instruction::Const { value, .. } => values.push(value),
+            _ => {}
+        }
+    }
+    Some(values)
+}
+
+fn interpreted_with(code: &str, options: &Options) -> Result<String, Error> {
+    let mut output = vec![];
+    interpret_with(code, &b""[..], &mut output, options)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+// Runs `code` on the interpreter and on executors of the linearized code, which must agree.
+fn run(code: &str) -> (String, i128) {
+    let mut expected = vec![];
+    let code_expected = interpret(code, &b""[..], &mut expected).unwrap();
+    let mut output = vec![];
+    let exit = threaded::run(code, &b""[..], &mut output, &Options::default()).unwrap();
+    assert_eq!((&output, exit), (&expected, code_expected), "threaded");
+    let mut output = vec![];
+    let compiled = bytecode::compile(code, &Options::default()).unwrap();
+    let exit = bytecode::run(&compiled, &b""[..], &mut output).unwrap();
+    assert_eq!((&output, exit), (&expected, code_expected), "bytecode");
+    (String::from_utf8(expected).unwrap(), code_expected)
+}
+
+#[test]
+fn lowers_to_registers() {
+    // Values pushed and popped inside a block never reach the storage. The input keeps the
+    // addition from being folded.
+    assert_eq!(
+        blocks("방반다망하"),
+        [Block {
+            instructions: vec![
+                Instruction::ScanDecimal(0),
+                Instruction::Const { dst: 1, value: 2 },
+                Instruction::Binary {
+                    dst: 2,
+                    op: BinaryOp::Add,
+                    lhs: 0,
+                    rhs: 1,
+                    cell: (0, 2),
+                },
+                Instruction::PrintDecimal(2),
+            ],
+            terminator: Terminator::Halt { storage: 0 },
+        }]
+    );
+}
+
+#[test]
+fn jumps_stay_in_program() {
+    let blocks = blocks("박밣빠망반타빠추\n아아오어어어어어\n아아아아아아아희");
+    // Every jump targets a block of the program.
+    for block in &blocks {
+        let targets = match block.terminator {
+            Terminator::Goto(target) => vec![target],
+            Terminator::Jsl {
+                less, otherwise, ..
+            } => vec![less, otherwise],
+            Terminator::Jnz { nonzero, zero, .. } => vec![nonzero, zero],
+            Terminator::Jz {
+                zero, otherwise, ..
+            } => vec![zero, otherwise],
+            Terminator::Halt { .. } => vec![],
+        };
+        assert!(targets.iter().all(|&target| target < blocks.len()));
+    }
+}
+
+#[test]
+fn carried_bounds_remove_checks() {
+    // The block after ㅊ adds two of the values pushed before it.
+    let code = "반반반차다망하";
+    assert_eq!(checks(code), 0);
+    assert_eq!(run(code), ("4".to_owned(), 0));
+    // The host may change the ㅎ storage between blocks, so the addition is checked.
+    let code = "샇반반반차다망희";
+    assert_ne!(checks(code), 0);
+    assert_eq!(run(code), ("4".to_owned(), 0));
+}
+
+#[test]
+fn carried_bounds_on_queue() {
+    let code = "상반반반차다망하";
+    assert_eq!(checks(code), 0);
+    assert_eq!(run(code), ("4".to_owned(), 0));
+}
+
+#[test]
+fn carried_bounds_do_not_copy_blocks() {
+    // A random grid full of branches and checks, which made 144 blocks before the bounds were
+    // carried.
+    let code = "텀윩뻘뽟삾눇좣ㅇ\nㅇㅇ쪃심쭠덶똤팶\nㅇ킧툛퐍버청뽏즟\n츩껂쩚ㅇ곭넌숖킡\n\
+                ㅇ딼싁햘탹ㅇ먾쳄\n쎔팿싗싐캏딾듒뺊\n쌻쌗툝얾칯슬ㅇ꼱\n펒놕깽쑵끊섮떶폆";
+    assert!(blocks(code).len() <= 144, "{}", blocks(code).len());
+}
+
+#[test]
+fn queue_pushes_go_after_older_values() {
+    // ㅁ takes the 4 pushed in the last block, since the 3 and 2 from before it are added first.
+    assert_eq!(run("상박받반차밖밙다망하"), ("4".to_owned(), 5));
+    // The older values are not known to be in the queue, so they are reloaded after the pushes.
+    assert_eq!(run("상박받사밤차상밝망희"), ("2".to_owned(), 3));
+}
+
+#[test]
+fn queue_restores_unused_front() {
+    // ㅍ swaps the front two values, which go back to the queue before ㅊ pops one of them.
+    assert_eq!(run("상박받반반반차파차망망희"), ("32".to_owned(), 2));
+    // ㅃ duplicates the front value.
+    assert_eq!(run("상박받반반차빠차망망희"), ("32".to_owned(), 2));
+}
+
+// 7 to the 4th and 16th power.
+const SMALL_POWER: &str = "밝빠따빠따망하";
+const LARGE_POWER: &str = "밝빠따빠따빠따빠따망하";
+
+#[test]
+fn folds_in_range_when_trapping() {
+    let options = Options {
+        width: Width::W32,
+        overflow: Overflow::Trap,
+        ..Options::default()
+    };
+    assert_eq!(folded(SMALL_POWER, &options), Some(vec![2401]));
+    assert_eq!(interpreted_with(SMALL_POWER, &options).unwrap(), "2401");
+    // The overflow is left to trap when the program runs.
+    assert_eq!(folded(LARGE_POWER, &options), None);
+    assert!(matches!(
+        interpreted_with(LARGE_POWER, &options),
+        Err(Error::Overflow { .. })
+    ));
+    let result = threaded::run(LARGE_POWER, &b""[..], &mut vec![], &options);
+    assert!(matches!(result, Err(Error::Overflow { .. })));
+}
+
+#[test]
+fn folds_wrapping() {
+    let options = Options {
+        width: Width::W32,
+        overflow: Overflow::Wrap,
+        ..Options::default()
+    };
+    let output = interpreted_with(LARGE_POWER, &options).unwrap();
+    assert_eq!(
+        folded(LARGE_POWER, &options),
+        Some(vec![output.parse().unwrap()])
+    );
+}
+
+#[test]
+fn folds_bigint_within_i64() {
+    let options = Options {
+        bigint: true,
+        ..Options::default()
+    };
+    let exact = Options {
+        width: Width::W128,
+        ..Options::default()
+    };
+    let output = interpreted_with(LARGE_POWER, &exact).unwrap();
+    assert_eq!(
+        folded(LARGE_POWER, &options),
+        Some(vec![output.parse().unwrap()])
+    );
+    // 7 to the 32nd power does not fit in 64 bits.
+    assert_eq!(folded("밝빠따빠따빠따빠따빠따망하", &options), None);
+}
+
+#[test]
+fn folds_zero_division_to_zero_only() {
+    let code = "반바나망하";
+    let options = Options {
+        zero_division: ZeroDivision::Zero,
+        ..Options::default()
+    };
+    assert_eq!(folded(code, &options), Some(vec![0]));
+    assert_eq!(interpreted_with(code, &options).unwrap(), "0");
+    for zero_division in [ZeroDivision::Undefined, ZeroDivision::Trap] {
+        let options = Options {
+            zero_division,
+            ..Options::default()
+        };
+        assert_eq!(folded(code, &options), None);
+        assert!(matches!(
+            interpreted_with(code, &options),
+            Err(Error::DivisionByZero { .. })
+        ));
+    }
+}
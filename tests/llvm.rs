@@ -0,0 +1,18 @@
+//! Runs the LLVM IR generated for every case, where `llc` is available, and compares it with the
+//! interpreter.
+
+mod common;
+
+use palheui::{transpile_llvm, Width};
+
+#[test]
+fn llvm_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64, Width::W128]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        (cases.iter())
+            .map(|(code, inputs, options)| {
+                common::run_llvm(&transpile_llvm(code, options).unwrap(), inputs)
+            })
+            .collect()
+    });
+}
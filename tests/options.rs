@@ -0,0 +1,174 @@
+//! Checks what each option does in the interpreter, and that the C code generated with it agrees.
+
+mod common;
+
+use palheui::ir::BinaryOp;
+use palheui::{
+    interpret_with, transpile_with, Error, Integer, Options, Overflow, Width, WrapMode,
+    ZeroDivision,
+};
+
+fn interpreted(code: &str, input: &str, options: &Options) -> (String, Result<Integer, Error>) {
+    let mut output = vec![];
+    let exit = interpret_with(code, input.as_bytes(), &mut output, options);
+    (String::from_utf8(output).unwrap(), exit)
+}
+
+// Runs the C code generated with `options` where a C compiler is available.
+fn check_c(code: &str, input: &str, options: &Options) {
+    let c = transpile_with(code, options).unwrap();
+    if let Some(outcomes) = common::run_c(&c, &[input]) {
+        common::check(&outcomes[0], code, input, options);
+    }
+}
+
+// Reads two values and prints their sum.
+const ADD: &str = "방방다빠망하";
+
+#[test]
+fn wraps_to_width() {
+    let options = Options {
+        width: Width::W32,
+        ..Options::default()
+    };
+    let (output, exit) = interpreted(ADD, "2147483647 1", &options);
+    assert_eq!(
+        (output.as_str(), exit.unwrap()),
+        ("-2147483648", -2147483648)
+    );
+    // Values are read in the width too.
+    let (output, _) = interpreted(ADD, "4294967297 0", &options);
+    assert_eq!(output, "1");
+    check_c(ADD, "2147483647 1", &options);
+
+    let options = Options {
+        width: Width::W128,
+        ..Options::default()
+    };
+    let (output, _) = interpreted(ADD, "9223372036854775807 1", &options);
+    assert_eq!(output, "9223372036854775808");
+    check_c(ADD, "9223372036854775807 1", &options);
+}
+
+#[test]
+fn wraps_overflow() {
+    for width in [Width::W32, Width::W64] {
+        let options = Options {
+            width,
+            overflow: Overflow::Wrap,
+            ..Options::default()
+        };
+        let min = format!("{} -1", width.wrap(1 << (width.bits() - 1)));
+        // `MIN / -1` wraps to `MIN`, and its remainder is `0`.
+        let (output, _) = interpreted("방방나빠망하", &min, &options);
+        assert_eq!(output, width.wrap(1 << (width.bits() - 1)).to_string());
+        let (output, _) = interpreted("방방라빠망하", &min, &options);
+        assert_eq!(output, "0");
+        for code in [
+            "방방다빠망하",
+            "방방타빠망하",
+            "방방따빠망하",
+            "방방나빠망하",
+            "방방라빠망하",
+        ] {
+            check_c(code, &min, &options);
+        }
+    }
+}
+
+#[test]
+fn traps_overflow() {
+    let options = Options {
+        width: Width::W32,
+        overflow: Overflow::Trap,
+        ..Options::default()
+    };
+    // Prints the values before adding them.
+    let (output, exit) = interpreted("방빠망방빠망다하", "2147483647 1", &options);
+    assert_eq!(output, "21474836471");
+    let Err(Error::Overflow { op, r, c }) = exit else {
+        panic!("{exit:?}");
+    };
+    assert_eq!((op, r, c), (BinaryOp::Add, 0, 6));
+    // The cell is found on other lines too.
+    let code = "붕\n붕\n뚜\n뭉\n후";
+    let (_, exit) = interpreted(code, "65536 65536", &options);
+    let error = exit.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "overflow in multiplication at line 3, column 1"
+    );
+    // `MIN / -1` is the only overflowing division.
+    let (_, exit) = interpreted("방방나빠망하", "-2147483648 -1", &options);
+    assert!(matches!(
+        exit,
+        Err(Error::Overflow {
+            op: BinaryOp::Divide,
+            ..
+        })
+    ));
+    let (output, _) = interpreted("방방라빠망하", "-2147483648 -1", &options);
+    assert_eq!(output, "0");
+    check_c("방빠망방빠망다하", "2147483647 1", &options);
+    check_c(code, "65536 65536", &options);
+    check_c("방방나빠망하", "-2147483648 -1", &options);
+}
+
+// Reads two values and divides them, reading more values to the left when reflected.
+const DIVIDE: &str = "아뱅뱅내맹해";
+
+#[test]
+fn traps_division_by_zero() {
+    let options = Options {
+        zero_division: ZeroDivision::Trap,
+        ..Options::default()
+    };
+    let (_, exit) = interpreted(DIVIDE, "7 0", &options);
+    assert!(matches!(exit, Err(Error::DivisionByZero { r: 0, c: 3 })));
+    check_c(DIVIDE, "7 0", &options);
+    check_c("방방라빠망하", "7 0", &options);
+}
+
+#[test]
+fn divides_by_zero_to_zero() {
+    let options = Options {
+        zero_division: ZeroDivision::Zero,
+        ..Options::default()
+    };
+    let (output, exit) = interpreted(DIVIDE, "7 0", &options);
+    assert_eq!((output.as_str(), exit.unwrap()), ("0", 0));
+    let (output, _) = interpreted("방방라빠망하", "7 0", &options);
+    assert_eq!(output, "0");
+    check_c(DIVIDE, "7 0", &options);
+    check_c("방방라빠망하", "7 0", &options);
+}
+
+#[test]
+fn reflects_division_by_zero() {
+    let options = Options {
+        zero_division: ZeroDivision::Reflect,
+        ..Options::default()
+    };
+    // The cursor goes back through both reads, which push -1 at the end of the input, and comes
+    // back to divide -1 by -1.
+    let (output, exit) = interpreted(DIVIDE, "7 0", &options);
+    assert_eq!((output.as_str(), exit.unwrap()), ("1", -1));
+    check_c(DIVIDE, "7 0", &options);
+}
+
+// Goes down column 1 with speed 2 from row 2, which is the last row reaching the column. Wrapping
+// around the rectangle reaches row 0, and wrapping around the column reaches row 1.
+const RAGGED: &str = "우망희\n우하\n아뷴\n우\n우";
+
+#[test]
+fn wraps_around_rectangle_or_extents() {
+    for (wrap, expected) in [(WrapMode::Rectangle, ("2", 0)), (WrapMode::Spec, ("", 2))] {
+        let options = Options {
+            wrap,
+            ..Options::default()
+        };
+        let (output, exit) = interpreted(RAGGED, "", &options);
+        assert_eq!((output.as_str(), exit.unwrap()), expected, "{wrap:?}");
+        check_c(RAGGED, "", &options);
+    }
+}
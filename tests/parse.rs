@@ -0,0 +1,60 @@
+use palheui::{parse, Consonant, Syllable, Vowel};
+
+#[test]
+fn decodes_grid() {
+    // A syllable without meaning, a non-Hangul character and a shorter second row.
+    let program = parse("밝뺘꺄\nx희");
+    assert_eq!((program.field.w, program.field.h), (3, 2));
+    assert_eq!(program.field.widths, [3, 2]);
+    assert_eq!(program.field.heights, [2, 2, 1]);
+    assert_eq!(
+        program.get(0, 0),
+        Some(&Syllable {
+            consonant: Some(Consonant::Push(7)),
+            vowel: Some(Vowel::Right),
+        })
+    );
+    assert_eq!(
+        program.get(0, 1),
+        Some(&Syllable {
+            consonant: Some(Consonant::Duplicate),
+            vowel: Some(Vowel::RightTwo),
+        })
+    );
+    assert_eq!(
+        program.get(0, 2),
+        Some(&Syllable {
+            consonant: None,
+            vowel: Some(Vowel::RightTwo),
+        })
+    );
+    assert_eq!(program.get(1, 0), Some(&Syllable::default()));
+    assert_eq!(
+        program.get(1, 1),
+        Some(&Syllable {
+            consonant: Some(Consonant::Halt),
+            vowel: Some(Vowel::Flip),
+        })
+    );
+    // Cells past the end of a row are in the field, but empty.
+    assert_eq!(program.get(1, 2), Some(&Syllable::default()));
+    assert_eq!(program.get(2, 0), None);
+    assert_eq!(program.get(0, 3), None);
+}
+
+#[test]
+fn decodes_trailing_consonants() {
+    let program = parse("방맣삭싻");
+    let consonants: Vec<_> = (0..4)
+        .map(|c| program.get(0, c).unwrap().consonant)
+        .collect();
+    assert_eq!(
+        consonants,
+        [
+            Some(Consonant::ScanDecimal),
+            Some(Consonant::PrintUnicode),
+            Some(Consonant::Select(1)),
+            Some(Consonant::Move(3)),
+        ]
+    );
+}
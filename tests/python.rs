@@ -0,0 +1,72 @@
+//! Runs the Python generated for every case, where Python 3 is available, and compares it with
+//! the interpreter.
+
+mod common;
+
+use common::{Batch, Case, Outcome};
+use palheui::{transpile_python, Options, Width};
+
+// Imports each module and runs it, writing the message of a trap to the error output.
+const DRIVER: &str = r#"
+import importlib.util
+import io
+import os
+import sys
+
+dir = sys.argv[1]
+for name in os.listdir(dir):
+    if not name.endswith(".in"):
+        continue
+    run = os.path.join(dir, name[:-3])
+    path = run.rsplit("-", 1)[0] + ".py"
+    spec = importlib.util.spec_from_file_location("program", path)
+    module = importlib.util.module_from_spec(spec)
+    spec.loader.exec_module(module)
+    with open(run + ".in", "rb") as file:
+        input = io.BytesIO(file.read())
+    output = io.BytesIO()
+    error = ""
+    try:
+        code = str(module.run(input, output) & 255)
+    except SystemExit as e:
+        code = "trap"
+        error = str(e.code)
+    with open(run + ".out", "wb") as file:
+        file.write(output.getvalue())
+    with open(run + ".err", "w") as file:
+        file.write(error)
+    with open(run + ".code", "w") as file:
+        file.write(code)
+"#;
+
+// Runs the Python generated for each of `cases` in one process of Python.
+fn run(cases: &[Case]) -> Option<Vec<Vec<Outcome>>> {
+    if !common::available("python3") {
+        return None;
+    }
+    let mut batch = Batch::new();
+    for (code, inputs, options) in cases {
+        let program = transpile_python(code, options).unwrap();
+        batch.add("py", program.as_bytes(), inputs);
+    }
+    Some(batch.run("python3", "driver.py", DRIVER))
+}
+
+#[test]
+fn python_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64, Width::W128]);
+    common::check_backend(&common::cases(&matrix), run);
+}
+
+#[test]
+fn python_bigint_matches_interpreter() {
+    common::check_backend(&common::bigint_cases(), run);
+    let (square, squared) = common::SQUARES;
+    let options = Options {
+        bigint: true,
+        ..Options::default()
+    };
+    if let Some(outcomes) = run(&[(square, &[""], options)]) {
+        assert_eq!(outcomes[0][0].output, squared.as_bytes());
+    }
+}
@@ -0,0 +1,98 @@
+//! Compiles the Rust generated for every case, where `rustc` is available, and compares it with
+//! the interpreter.
+
+mod common;
+
+use std::fmt::Write;
+use std::fs;
+use std::process::Command;
+
+use palheui::{transpile_rust, Options, Overflow, Width, ZeroDivision};
+
+// Includes the programs `0.rs` to `{count - 1}.rs` as modules, and runs the one whose number is
+// the argument. A trap aborts the whole process, so every run needs one of its own.
+fn driver(count: usize) -> String {
+    let mut driver = String::new();
+    for n in 0..count {
+        writeln!(driver, "#[path = \"{n}.rs\"]\nmod p{n};").ok();
+    }
+    driver.push_str(
+        "
+fn main() {
+    let (input, output) = (std::io::stdin().lock(), std::io::stdout().lock());
+    std::process::exit(match std::env::args().nth(1).unwrap().as_str() {
+",
+    );
+    for n in 0..count {
+        writeln!(driver, "        \"{n}\" => p{n}::run(input, output),").ok();
+    }
+    driver.push_str("        _ => unreachable!(),\n    });\n}\n");
+    driver
+}
+
+#[test]
+fn rust_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64, Width::W128]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        if !common::available("rustc") {
+            return None;
+        }
+        let dir = common::build_dir();
+        for (n, (code, _, options)) in cases.iter().enumerate() {
+            let source = transpile_rust(code, options).unwrap();
+            fs::write(dir.join(format!("{n}.rs")), source).unwrap();
+        }
+        fs::write(dir.join("main.rs"), driver(cases.len())).unwrap();
+        let status = Command::new("rustc")
+            .arg(dir.join("main.rs"))
+            .arg("-o")
+            .arg(dir.join("main"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let binary = dir.join("main");
+        let outcomes = (cases.iter().enumerate())
+            .map(|(n, (_, inputs, _))| {
+                (inputs.iter())
+                    .map(|input| common::execute(Command::new(&binary).arg(n.to_string()), input))
+                    .collect()
+            })
+            .collect();
+        fs::remove_dir_all(&dir).ok();
+        Some(outcomes)
+    });
+}
+
+#[test]
+fn compiles_zero_divisor() {
+    if !common::available("rustc") {
+        return;
+    }
+    let dir = common::build_dir();
+    // Divides and takes the remainder by a literal zero.
+    let code = "반바나반바라망망하";
+    for zero_division in [
+        ZeroDivision::Undefined,
+        ZeroDivision::Reflect,
+        ZeroDivision::Trap,
+        ZeroDivision::Zero,
+    ] {
+        for overflow in [Overflow::Undefined, Overflow::Wrap, Overflow::Trap] {
+            let options = Options {
+                zero_division,
+                overflow,
+                ..Options::default()
+            };
+            let source = dir.join("main.rs");
+            fs::write(&source, transpile_rust(code, &options).unwrap()).unwrap();
+            let status = Command::new("rustc")
+                .arg(&source)
+                .arg("-o")
+                .arg(dir.join("main"))
+                .status()
+                .unwrap();
+            assert!(status.success(), "{zero_division:?} {overflow:?}");
+        }
+    }
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,53 @@
+//! Runs the WebAssembly modules generated for every case under the WASI of Node.js, where it is
+//! available, and compares them with the interpreter.
+
+mod common;
+
+use common::Batch;
+use palheui::{transpile_wasm, Width};
+
+// Runs each module with returnOnExit, so `proc_exit` returns from `start`.
+const DRIVER: &str = r#"
+import { closeSync, openSync, readdirSync, readFileSync, writeFileSync } from "node:fs";
+import { WASI } from "node:wasi";
+
+const dir = process.argv[2];
+const modules = new Map();
+for (const name of readdirSync(dir).filter((name) => name.endsWith(".in"))) {
+  const run = `${dir}/${name.slice(0, -3)}`;
+  const path = run.replace(/-[0-9]+$/, ".wasm");
+  if (!modules.has(path)) {
+    modules.set(path, await WebAssembly.compile(readFileSync(path)));
+  }
+  const stdin = openSync(`${run}.in`, "r");
+  const stdout = openSync(`${run}.out`, "w");
+  const stderr = openSync(`${run}.err`, "w");
+  const wasi = new WASI({ version: "preview1", returnOnExit: true, stdin, stdout, stderr });
+  const instance = await WebAssembly.instantiate(modules.get(path), wasi.getImportObject());
+  let code;
+  try {
+    code = String(wasi.start(instance) & 255);
+  } catch {
+    code = "trap";
+  }
+  for (const fd of [stdin, stdout, stderr]) {
+    closeSync(fd);
+  }
+  writeFileSync(`${run}.code`, code);
+}
+"#;
+
+#[test]
+fn wasm_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        if !common::available("node") {
+            return None;
+        }
+        let mut batch = Batch::new();
+        for (code, inputs, options) in cases {
+            batch.add("wasm", &transpile_wasm(code, options).unwrap(), inputs);
+        }
+        Some(batch.run("node", "driver.mjs", DRIVER))
+    });
+}
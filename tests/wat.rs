@@ -0,0 +1,33 @@
+//! Runs the WebAssembly text generated for every case under Wasmtime, where it is available, and
+//! compares it with the interpreter.
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use palheui::{transpile_wat, Width};
+
+#[test]
+fn wat_matches_interpreter() {
+    let matrix = common::matrix(&[Width::W32, Width::W64]);
+    common::check_backend(&common::cases(&matrix), |cases| {
+        if !common::available("wasmtime") {
+            return None;
+        }
+        let dir = common::build_dir();
+        let module = dir.join("main.wat");
+        let outcomes = (cases.iter())
+            .map(|(code, inputs, options)| {
+                fs::write(&module, transpile_wat(code, options).unwrap()).unwrap();
+                (inputs.iter())
+                    .map(|input| {
+                        common::execute(Command::new("wasmtime").arg("run").arg(&module), input)
+                    })
+                    .collect()
+            })
+            .collect();
+        fs::remove_dir_all(&dir).ok();
+        Some(outcomes)
+    });
+}